@@ -0,0 +1,229 @@
+// Picking frames out of animated GIFs and WebPs. `image::open` only returns the
+// first frame, so the decoders' frame iterators are used instead. Frames come
+// out already composited onto the full canvas, with disposal applied. Going the
+// other way, a folder of still frames can be joined into an animated WebP.
+
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, Frames, ImageFormat, RgbaImage};
+
+// Number of frames in an animation; 1 if it can't be read or isn't animated.
+// Counted from the file's structure without decoding any frame, and a frame
+// cut off by truncation isn't counted.
+pub fn frame_count(bytes: &[u8], format: ImageFormat) -> usize {
+    let count = match format {
+        ImageFormat::Gif => gif_frame_count(bytes),
+        ImageFormat::WebP => webp_frame_count(bytes),
+        _ => 1,
+    };
+    count.max(1)
+}
+
+// Walks the blocks after the header and global color table, counting image
+// descriptors and skipping over extensions and the compressed pixel data.
+fn gif_frame_count(bytes: &[u8]) -> usize {
+    const EXTENSION: u8 = 0x21;
+    const IMAGE_DESCRIPTOR: u8 = 0x2C;
+
+    let Some(&screen_flags) = bytes.get(10) else {
+        return 0;
+    };
+    let mut pos = 13 + gif_color_table_len(screen_flags);
+    let mut count = 0;
+    while let Some(&introducer) = bytes.get(pos) {
+        let next = match introducer {
+            // Then the label and the extension's data.
+            EXTENSION => skip_gif_sub_blocks(bytes, pos + 2),
+            // Then position, size and flags, a local color table and the LZW code size.
+            IMAGE_DESCRIPTOR => bytes
+                .get(pos + 9)
+                .and_then(|&flags| skip_gif_sub_blocks(bytes, pos + 11 + gif_color_table_len(flags))),
+            // The trailer, or something unreadable.
+            _ => None,
+        };
+        let Some(next) = next else {
+            break;
+        };
+        if introducer == IMAGE_DESCRIPTOR {
+            count += 1;
+        }
+        pos = next;
+    }
+    count
+}
+
+fn gif_color_table_len(flags: u8) -> usize {
+    if flags & 0x80 == 0 { 0 } else { 3 << ((flags & 0x07) + 1) }
+}
+
+// Where the data sub-blocks starting at `pos` end, if all of them are there.
+fn skip_gif_sub_blocks(bytes: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *bytes.get(pos)? as usize;
+        pos += 1 + len;
+        if len == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+// Counts the ANMF chunks after the RIFF header; a still WebP has none.
+fn webp_frame_count(bytes: &[u8]) -> usize {
+    let mut pos = 12;
+    let mut count = 0;
+    while let Some(header) = bytes.get(pos..pos + 8) {
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let end = pos + 8 + len;
+        if end > bytes.len() {
+            break;
+        }
+        if &header[..4] == b"ANMF" {
+            count += 1;
+        }
+        // Chunks are padded to an even length.
+        pos = end + len % 2;
+    }
+    count
+}
+
+pub fn decode_frame(bytes: &[u8], format: ImageFormat, index: usize) -> Result<DynamicImage, String> {
+    match frames(bytes, format)?.nth(index) {
+        Some(Ok(frame)) => Ok(DynamicImage::ImageRgba8(frame.into_buffer())),
+        Some(Err(e)) => Err(format!("Error loading image: {e}")),
+        None => Err(format!("Error: frame {} not found", index + 1)),
+    }
+}
+
+// Every frame tiled left to right, top to bottom, `columns` to a row.
+pub fn sprite_sheet(bytes: &[u8], format: ImageFormat, columns: u32) -> Result<DynamicImage, String> {
+    let mut buffers = Vec::new();
+    for frame in frames(bytes, format)? {
+        match frame {
+            Ok(frame) => buffers.push(frame.into_buffer()),
+            Err(e) => return Err(format!("Error loading image: {e}")),
+        }
+    }
+    let Some(first) = buffers.first() else {
+        return Err("Error: the animation has no frames".to_string());
+    };
+
+    let (cell_width, cell_height) = (first.width(), first.height());
+    let columns = columns.clamp(1, buffers.len() as u32);
+    let rows = (buffers.len() as u32).div_ceil(columns);
+    let mut sheet = RgbaImage::new(cell_width * columns, cell_height * rows);
+    for (i, buffer) in buffers.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        image::imageops::replace(
+            &mut sheet,
+            buffer,
+            (column * cell_width) as i64,
+            (row * cell_height) as i64,
+        );
+    }
+    Ok(DynamicImage::ImageRgba8(sheet))
+}
+
+fn frames(bytes: &[u8], format: ImageFormat) -> Result<Frames<'_>, String> {
+    let result = match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(bytes)).map(|decoder| decoder.into_frames()),
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(bytes)).and_then(|decoder| {
+            if decoder.has_animation() {
+                Ok(decoder.into_frames())
+            } else {
+                // A still WebP is a single frame.
+                DynamicImage::from_decoder(decoder).map(|image| {
+                    Frames::new(Box::new(std::iter::once(Ok(image::Frame::new(image.to_rgba8())))))
+                })
+            }
+        }),
+        _ => return Err("Error: only GIF and WebP inputs have frames".to_string()),
+    };
+    match result {
+        Ok(frames) => Ok(frames),
+        Err(e) => Err(format!("Error loading image: {e}")),
+    }
+}
+
+// Puts numbered frame files in counting order, so frame2.png comes before
+// frame10.png; names are otherwise compared as text.
+pub fn sort_frames(paths: &mut [PathBuf]) {
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    enum Chunk {
+        Number(u64),
+        Text(String),
+    }
+
+    paths.sort_by_cached_key(|path| {
+        let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let mut chunks = Vec::new();
+        let mut rest = name.as_str();
+        while let Some(first) = rest.chars().next() {
+            let is_digit = first.is_ascii_digit();
+            let end = rest.find(|c: char| c.is_ascii_digit() != is_digit).unwrap_or(rest.len());
+            let (chunk, tail) = rest.split_at(end);
+            chunks.push(match chunk.parse() {
+                Ok(number) if is_digit => Chunk::Number(number),
+                _ => Chunk::Text(chunk.to_string()),
+            });
+            rest = tail;
+        }
+        chunks
+    });
+}
+
+// Encodes `frames` as an animated WebP that loops forever, showing each for
+// `delay_ms`. Every frame has to be the size of the first.
+pub fn encode_webp(
+    frames: impl IntoIterator<Item = Result<RgbaImage, String>>,
+    delay_ms: u32,
+    quality: u8,
+    lossless: bool,
+) -> Result<Vec<u8>, String> {
+    use webp_animation::{Encoder, EncoderOptions, EncodingConfig, EncodingType};
+
+    let config = EncodingConfig {
+        encoding_type: if lossless { EncodingType::Lossless } else { EncodingType::new_lossy() },
+        quality: quality as f32,
+        ..Default::default()
+    };
+    let mut encoder: Option<(Encoder, (u32, u32))> = None;
+    let mut timestamp = 0;
+    for (index, frame) in frames.into_iter().enumerate() {
+        let frame = frame?;
+        let (encoder, size) = match &mut encoder {
+            Some(existing) => existing,
+            None => {
+                let options = EncoderOptions { encoding_config: Some(config.clone()), ..Default::default() };
+                match Encoder::new_with_options(frame.dimensions(), options) {
+                    Ok(created) => encoder.insert((created, frame.dimensions())),
+                    Err(e) => return Err(format!("Error encoding animation: {e}")),
+                }
+            }
+        };
+        if frame.dimensions() != *size {
+            return Err(format!(
+                "Error: frame {} is {}x{}, but the first frame is {}x{}",
+                index + 1,
+                frame.width(),
+                frame.height(),
+                size.0,
+                size.1
+            ));
+        }
+        if let Err(e) = encoder.add_frame(frame.as_raw(), timestamp) {
+            return Err(format!("Error encoding animation: {e}"));
+        }
+        timestamp = timestamp.saturating_add(delay_ms.min(i32::MAX as u32) as i32);
+    }
+
+    let Some((encoder, _)) = encoder else {
+        return Err("Error: no frames to build an animation from".to_string());
+    };
+    match encoder.finalize(timestamp) {
+        Ok(data) => Ok(data.to_vec()),
+        Err(e) => Err(format!("Error encoding animation: {e}")),
+    }
+}
@@ -0,0 +1,89 @@
+// A record of what a folder batch did to each file, written to the output
+// folder as compression_report.csv and compression_report.json once the batch
+// is done.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{savings_percent, CompressionTask};
+
+const REPORT_NAME: &str = "compression_report";
+
+#[derive(Serialize)]
+pub struct Entry {
+    pub input: String,
+    pub output: String,
+    // None when the file couldn't be read, or no output was encoded.
+    pub input_size: Option<u64>,
+    pub output_size: Option<u64>,
+    pub savings_percent: Option<f32>,
+    pub quality: u8,
+    // "written", why the file was skipped, or the error.
+    pub result: String,
+    // Placeholder for web pages, when the batch computes one.
+    pub blurhash: Option<String>,
+}
+
+impl Entry {
+    pub fn new(
+        task: &CompressionTask,
+        input_size: Option<u64>,
+        output_size: Option<u64>,
+        result: impl Into<String>,
+    ) -> Self {
+        Self {
+            input: task.input_path.display().to_string(),
+            output: task.output_path.display().to_string(),
+            input_size,
+            output_size,
+            savings_percent: input_size
+                .zip(output_size)
+                .map(|(input, output)| savings_percent(input, output)),
+            quality: task.quality,
+            result: result.into(),
+            blurhash: None,
+        }
+    }
+}
+
+pub fn write(dir: &Path, entries: &[Entry]) -> Result<(), String> {
+    let json = match serde_json::to_string_pretty(entries) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Error serializing batch report: {e}")),
+    };
+
+    let mut csv = String::from("input,output,input_size,output_size,savings_percent,quality,result,blurhash\n");
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    for entry in entries {
+        let fields = [
+            csv_field(&entry.input),
+            csv_field(&entry.output),
+            optional(entry.input_size.map(|size| size.to_string())),
+            optional(entry.output_size.map(|size| size.to_string())),
+            optional(entry.savings_percent.map(|savings| format!("{savings:.1}"))),
+            entry.quality.to_string(),
+            csv_field(&entry.result),
+            optional(entry.blurhash.as_deref().map(csv_field)),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    for (extension, contents) in [("csv", csv), ("json", json)] {
+        let path = dir.join(format!("{REPORT_NAME}.{extension}"));
+        if let Err(e) = std::fs::write(path, contents) {
+            return Err(format!("Error writing batch report: {e}"));
+        }
+    }
+    Ok(())
+}
+
+// Quotes a field when it holds a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,43 @@
+// Screenshots of the primary monitor, saved as a PNG in the temp folder so
+// they can be used like any other single-file input; the crop tool then picks
+// the region to keep. Needs the `capture` feature.
+
+use std::path::PathBuf;
+
+#[cfg(feature = "capture")]
+pub fn capture_screen() -> Result<PathBuf, String> {
+    let monitors = match xcap::Monitor::all() {
+        Ok(monitors) => monitors,
+        Err(e) => return Err(format!("Error listing screens: {e}")),
+    };
+    let Some(monitor) = monitors
+        .iter()
+        .find(|monitor| monitor.is_primary().unwrap_or(false))
+        .or(monitors.first())
+    else {
+        return Err("Error: no screen to capture".to_string());
+    };
+    let image = match monitor.capture_image() {
+        Ok(image) => image,
+        Err(e) => return Err(format!("Error capturing screen: {e}")),
+    };
+
+    let dir = std::env::temp_dir().join(format!("image-compressor-capture-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        return Err(format!("Error creating folder for screen capture: {e}"));
+    }
+    // Named by time so each capture is a new file, e.g. screenshot-1760400000.png.
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join(format!("screenshot-{seconds}.png"));
+    match image.save(&path) {
+        Ok(()) => Ok(path),
+        Err(e) => Err(format!("Error saving screen capture: {e}")),
+    }
+}
+
+#[cfg(not(feature = "capture"))]
+pub fn capture_screen() -> Result<PathBuf, String> {
+    Err("Error: screen capture is not available in this build".to_string())
+}
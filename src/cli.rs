@@ -0,0 +1,741 @@
+// Command-line mode: with any arguments the app compresses one file and exits
+// instead of opening the window. The GUI's "Copy as CLI command" writes the same
+// flags, so a setup found interactively can be scripted.
+//
+//     image1 [options] <input> <output>
+//
+// Only settings that differ from the defaults are written out, and uploads are
+// left off so no credentials end up in shell history. Defaults come from
+// config.toml when there is one (see config.rs); flags take precedence.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+
+use crate::config;
+use crate::decode_cache::DecodeCache;
+use crate::watermark::{Watermark, WatermarkPosition};
+use crate::{
+    decode_bytes, decode_with_timeout, free_output_path, perform_compression, AspectCrop, CompressionTask, CropRect, ExistingOutputs, PngDepth,
+    ResizeFilter, ResizeTarget, Rotation, WebpMode, WorkerEvent,
+};
+
+const USAGE: &str = "\
+Usage: image1 [options] <input> <output>
+
+The output format follows the output file's extension. An input of - reads the
+image from stdin, e.g. curl -s https://example.com/photo.png | image1 - out.jpg
+
+Options:
+  --input <file|->               The input, in place of the first argument
+  --overwrite                    Replace an existing output (the default)
+  --skip-existing                Leave an existing output alone and exit
+  --rename                       Write <name>_1.<ext> and so on beside an existing output
+  --keep-original                Rename an output about to be overwritten to <name>.orig
+  --quality <1-100>              JPEG/WebP quality
+  --png-level <0-9>              PNG compression, 0 stores it uncompressed
+  --srgb                         Convert from the embedded color profile to sRGB
+  --rotate <90|180|270>          Rotate clockwise
+  --crop <x,y,w,h>               Keep only this region, in source pixels
+  --aspect-crop <W:H>            Then crop to this ratio, e.g. 16:9
+  --smart-crop                   Place the aspect crop on the most detailed part
+  --resize <WxH>                 Fit within this size
+  --stretch                      Resize to exactly WxH instead of fitting
+  --shrink-only                  Leave images that already fit at their size
+  --filter <nearest|triangle|catmull-rom|lanczos3>
+                                 Resampling for the resize and thumbnail
+  --no-resize                    Ignore max_width/max_height from config.toml
+  --protect <x,y,w,h>            Region kept at --protect-quality (JPEG)
+  --protect-quality <1-100>
+  --page <n>                     Page of a TIFF or frame of an animation, from 1
+  --sprite-sheet <columns>       Tile all frames of an animation into one image
+  --webp <lossless|near-lossless|lossy>
+  --near-lossless <0-100>
+  --png-depth <auto|l8|la8|rgb8|rgba8|rgb16|rgba16>
+  --background <RRGGBB>          Fill for transparency in JPEG/PPM output
+  --flatten                      Fill transparency with the background in every format
+  --no-defringe                  Resize with straight rather than premultiplied alpha
+  --deskew                       Detect a scan's tilt and rotate it level
+  --auto-levels                  Stretch each channel to the full range
+  --brightness <-100-100>
+  --contrast <-100-100>
+  --posterize <2-64>             Round each channel to this many levels
+  --restart-interval <MCUs>      JPEG restart markers
+  --decode-timeout <seconds>     Give up on slow inputs; 0 waits forever
+  --salvage-truncated            Compress what decodes of a truncated input
+  --timing                       Print decode/transform/encode/write times
+  --thumbnail <px>               Also write <name>_thumb.<ext>
+  --blurhash                     Print a BlurHash placeholder of the output
+  --note <text>                  Written to <output>.txt
+  --artist <name>                EXIF author of the output
+  --copyright <text>             EXIF copyright notice
+  --description <text>           EXIF image description
+  --post-command <command>       Run on the output; {file} is its path
+  --temp-dir <folder>            Write here first, then move into place
+  --watermark <text>
+  --watermark-image <path>
+  --watermark-position <top-left|top-right|center|bottom-left|bottom-right>
+  --watermark-opacity <0-1>
+  --watermark-size <percent>
+  --watermark-color <RRGGBB>
+  --help";
+
+// Runs command-line mode and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return 0;
+    }
+    let defaults = match config_defaults() {
+        Ok(defaults) => defaults,
+        Err(err) => {
+            eprintln!("{err}");
+            return 2;
+        }
+    };
+    let parsed = existing_outputs(args).and_then(|(existing, args)| Ok((existing, parse(&args, defaults)?)));
+    let (existing, mut task) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return 2;
+        }
+    };
+
+    // Never prompts, so scripts get the same result every run.
+    match existing {
+        ExistingOutputs::Skip if task.output_path.exists() => {
+            println!("Skipped: {} already exists", task.output_path.display());
+            return 0;
+        }
+        ExistingOutputs::Rename => task.output_path = free_output_path(&task.output_path),
+        _ => {}
+    }
+
+    // Stdin is decoded here and handed to the pipeline through the cache, which
+    // then never looks for a file named "-".
+    let cache = if task.input_path == Path::new("-") {
+        match decode_stdin(&task) {
+            Ok(cache) => Some(cache),
+            Err(err) => {
+                eprintln!("{err}");
+                return 1;
+            }
+        }
+    } else {
+        None
+    };
+
+    // Progress is ignored here; log lines (the timings) go to stderr.
+    let (events, received) = channel();
+    let result = perform_compression(task, &events, cache.as_ref());
+    for event in received.try_iter() {
+        if let WorkerEvent::Log(line) = event {
+            eprintln!("{line}");
+        }
+    }
+    match result {
+        Ok(msg) => {
+            println!("{msg}");
+            0
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            1
+        }
+    }
+}
+
+// What the flags are applied over: the built-in settings with config.toml's on top.
+fn config_defaults() -> Result<CompressionTask, String> {
+    let config = config::load()?;
+    let mut defaults = CompressionTask::default();
+    config.apply_to_task(&mut defaults);
+    Ok(defaults)
+}
+
+// Reads and decodes the image piped in on stdin, in memory, the same way a file
+// input would be apart from the extension check.
+fn decode_stdin(task: &CompressionTask) -> Result<DecodeCache, String> {
+    if task.sprite_columns.is_some() {
+        return Err("Error: --sprite-sheet needs an input file rather than stdin".to_string());
+    }
+    let mut bytes = Vec::new();
+    if let Err(e) = std::io::stdin().lock().read_to_end(&mut bytes) {
+        return Err(format!("Error reading stdin: {e}"));
+    }
+    if bytes.is_empty() {
+        return Err("Error: nothing was piped in on stdin".to_string());
+    }
+    if image::guess_format(&bytes).is_err() {
+        return Err("Error: stdin doesn't hold an image in a recognized format".to_string());
+    }
+    let page = task.page;
+    let mut notes = Vec::new();
+    let decoded = decode_with_timeout(task.decode_timeout_secs, &mut notes, move |notes| {
+        decode_bytes(&bytes, page, notes)
+    })?;
+    Ok(DecodeCache::holding(&task.input_path, page, decoded, notes))
+}
+
+// Takes the overwrite policy flags out of `args`, since they decide what to do
+// with the task rather than being part of it.
+fn existing_outputs(args: &[String]) -> Result<(ExistingOutputs, Vec<String>), String> {
+    let mut policy = None;
+    let mut rest = Vec::new();
+    for arg in args {
+        let flag_policy = match arg.as_str() {
+            "--overwrite" => ExistingOutputs::Overwrite,
+            "--skip-existing" => ExistingOutputs::Skip,
+            "--rename" => ExistingOutputs::Rename,
+            _ => {
+                rest.push(arg.clone());
+                continue;
+            }
+        };
+        if policy.is_some_and(|policy| policy != flag_policy) {
+            return Err("Error: use only one of --overwrite, --skip-existing and --rename".to_string());
+        }
+        policy = Some(flag_policy);
+    }
+    Ok((policy.unwrap_or_default(), rest))
+}
+
+// Flags are applied over `defaults`, which carry the config file's settings.
+pub fn parse(args: &[String], defaults: CompressionTask) -> Result<CompressionTask, String> {
+    let mut task = defaults;
+    let mut positional = Vec::new();
+    let mut resize = None;
+    let mut stretch = false;
+    let mut shrink_only = false;
+    let mut aspect = None;
+    let mut smart_crop = false;
+    let mut watermark: Option<Watermark> = None;
+    let mut input = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(PathBuf::from(arg));
+            continue;
+        }
+        let flag = arg.as_str();
+        let mut value = || match args.next() {
+            Some(value) => Ok(value.as_str()),
+            None => Err(format!("Error: {flag} needs a value")),
+        };
+        match flag {
+            "--input" => input = Some(PathBuf::from(value()?)),
+            "--quality" => task.quality = number(flag, value()?, 1, 100)?,
+            "--png-level" => task.png_level = number(flag, value()?, 0, 9)?,
+            "--srgb" => task.convert_to_srgb = true,
+            "--rotate" => {
+                task.rotation = match value()? {
+                    "90" => Rotation::Cw90,
+                    "180" => Rotation::Cw180,
+                    "270" => Rotation::Cw270,
+                    other => return Err(format!("Error: --rotate must be 90, 180 or 270, not {other}")),
+                }
+            }
+            "--crop" => task.crop = Some(rect(flag, value()?)?),
+            "--aspect-crop" => aspect = Some(ratio(flag, value()?)?),
+            "--smart-crop" => smart_crop = true,
+            "--resize" => resize = Some(Some(size(flag, value()?)?)),
+            "--no-resize" => resize = Some(None),
+            "--stretch" => stretch = true,
+            "--shrink-only" => shrink_only = true,
+            "--filter" => {
+                let name = value()?;
+                task.resize_filter = match RESIZE_FILTERS.iter().find(|(_, n)| *n == name) {
+                    Some((filter, _)) => *filter,
+                    None => return Err(format!("Error: unknown resize filter {name}")),
+                }
+            }
+            "--protect" => task.protect_region = Some(rect(flag, value()?)?),
+            "--protect-quality" => task.protect_quality = number(flag, value()?, 1, 100)?,
+            "--page" => task.page = number::<usize>(flag, value()?, 1, usize::MAX)? - 1,
+            "--sprite-sheet" => task.sprite_columns = Some(number(flag, value()?, 1, 64)?),
+            "--webp" => {
+                let name = value()?;
+                task.webp_mode = match WEBP_MODES.iter().find(|(_, n)| *n == name) {
+                    Some((mode, _)) => *mode,
+                    None => return Err(format!("Error: unknown WebP mode {name}")),
+                }
+            }
+            "--near-lossless" => task.webp_near_lossless = number(flag, value()?, 0, 100)?,
+            "--png-depth" => {
+                let name = value()?;
+                task.png_depth = match PNG_DEPTHS.iter().find(|(_, n)| *n == name) {
+                    Some((depth, _)) => *depth,
+                    None => return Err(format!("Error: unknown PNG depth {name}")),
+                }
+            }
+            "--background" => task.background = color(flag, value()?)?,
+            "--flatten" => task.flatten = true,
+            "--no-defringe" => task.defringe = false,
+            "--deskew" => task.deskew = true,
+            "--auto-levels" => task.auto_levels = true,
+            "--brightness" => task.brightness = number(flag, value()?, -100, 100)?,
+            "--contrast" => task.contrast = number(flag, value()?, -100.0, 100.0)?,
+            "--posterize" => task.posterize_levels = Some(number(flag, value()?, 2, 64)?),
+            "--restart-interval" => task.jpeg_restart_interval = number(flag, value()?, 0, u16::MAX)?,
+            "--decode-timeout" => task.decode_timeout_secs = number(flag, value()?, 0, u32::MAX)?,
+            "--salvage-truncated" => task.salvage_truncated = true,
+            "--timing" => task.log_timing = true,
+            "--thumbnail" => task.thumbnail_size = Some(number(flag, value()?, 1, u32::MAX)?),
+            "--blurhash" => task.blurhash = true,
+            "--note" => task.note = value()?.to_string(),
+            "--artist" => task.metadata.artist = value()?.to_string(),
+            "--copyright" => task.metadata.copyright = value()?.to_string(),
+            "--description" => task.metadata.description = value()?.to_string(),
+            "--post-command" => task.post_command = value()?.to_string(),
+            "--temp-dir" => task.temp_dir = Some(PathBuf::from(value()?)),
+            "--keep-original" => task.keep_original = true,
+            "--watermark" => watermark.get_or_insert_with(Watermark::default).text = value()?.to_string(),
+            "--watermark-image" => {
+                watermark.get_or_insert_with(Watermark::default).image = Some(PathBuf::from(value()?))
+            }
+            "--watermark-position" => {
+                let name = value()?;
+                watermark.get_or_insert_with(Watermark::default).position =
+                    match WATERMARK_POSITIONS.iter().find(|(_, n)| *n == name) {
+                        Some((position, _)) => *position,
+                        None => return Err(format!("Error: unknown watermark position {name}")),
+                    }
+            }
+            "--watermark-opacity" => {
+                watermark.get_or_insert_with(Watermark::default).opacity = number(flag, value()?, 0.0, 1.0)?
+            }
+            "--watermark-size" => {
+                watermark.get_or_insert_with(Watermark::default).size = number(flag, value()?, 0.1, 100.0)?
+            }
+            "--watermark-color" => {
+                watermark.get_or_insert_with(Watermark::default).color = color(flag, value()?)?
+            }
+            _ => return Err(format!("Error: unknown option {flag}")),
+        }
+    }
+
+    if let Some(input) = input {
+        positional.insert(0, input);
+    }
+    let [input, output] = <[PathBuf; 2]>::try_from(positional)
+        .map_err(|_| "Error: expected an input and an output file".to_string())?;
+    task.input_path = input;
+    task.output_path = output;
+    // Without --resize, the config file's caps (if any) stay in place.
+    match resize {
+        Some(Some((width, height))) => {
+            task.resize = Some(ResizeTarget { width, height, keep_aspect: !stretch, shrink_only })
+        }
+        Some(None) => task.resize = None,
+        None => {}
+    }
+    task.aspect_crop = match aspect {
+        Some((width, height)) => Some(AspectCrop { width, height, smart: smart_crop }),
+        None if smart_crop => return Err("Error: --smart-crop needs --aspect-crop".to_string()),
+        None => None,
+    };
+    task.watermark = watermark;
+    Ok(task)
+}
+
+// The command line that reproduces `task`, quoted for the platform's shell.
+pub fn command_line(task: &CompressionTask) -> String {
+    let program = std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "image1".to_string());
+    // Only what differs from the config gets a flag, as that's what the command
+    // starts from. A config.toml that doesn't load fails the command either way.
+    let defaults = config_defaults().unwrap_or_default();
+    let mut words = vec![program];
+    words.extend(to_args(task, &defaults));
+    words.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ")
+}
+
+fn to_args(task: &CompressionTask, defaults: &CompressionTask) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut push = |flag: &str, value: String| {
+        args.push(flag.to_string());
+        if !value.is_empty() {
+            args.push(value);
+        }
+    };
+
+    if task.quality != defaults.quality {
+        push("--quality", task.quality.to_string());
+    }
+    if task.png_level != defaults.png_level {
+        push("--png-level", task.png_level.to_string());
+    }
+    if task.convert_to_srgb {
+        push("--srgb", String::new());
+    }
+    match task.rotation {
+        Rotation::None => {}
+        Rotation::Cw90 => push("--rotate", "90".to_string()),
+        Rotation::Cw180 => push("--rotate", "180".to_string()),
+        Rotation::Cw270 => push("--rotate", "270".to_string()),
+    }
+    if let Some(crop) = task.crop {
+        push("--crop", format_rect(crop));
+    }
+    if let Some(aspect) = task.aspect_crop {
+        push("--aspect-crop", format!("{}:{}", aspect.width, aspect.height));
+        if aspect.smart {
+            push("--smart-crop", String::new());
+        }
+    }
+    match task.resize {
+        resize if resize == defaults.resize => {}
+        Some(resize) => {
+            push("--resize", format!("{}x{}", resize.width, resize.height));
+            if !resize.keep_aspect {
+                push("--stretch", String::new());
+            }
+            if resize.shrink_only {
+                push("--shrink-only", String::new());
+            }
+        }
+        // Turns off the config's size caps.
+        None => push("--no-resize", String::new()),
+    }
+    if task.resize_filter != defaults.resize_filter {
+        push("--filter", name_of(RESIZE_FILTERS, task.resize_filter).to_string());
+    }
+    if let Some(region) = task.protect_region {
+        push("--protect", format_rect(region));
+        if task.protect_quality != defaults.protect_quality {
+            push("--protect-quality", task.protect_quality.to_string());
+        }
+    }
+    if task.page != defaults.page {
+        push("--page", (task.page + 1).to_string());
+    }
+    if let Some(columns) = task.sprite_columns {
+        push("--sprite-sheet", columns.to_string());
+    }
+    if task.webp_mode != defaults.webp_mode {
+        push("--webp", name_of(WEBP_MODES, task.webp_mode).to_string());
+    }
+    if task.webp_mode == WebpMode::NearLossless && task.webp_near_lossless != defaults.webp_near_lossless {
+        push("--near-lossless", task.webp_near_lossless.to_string());
+    }
+    if task.png_depth != defaults.png_depth {
+        push("--png-depth", name_of(PNG_DEPTHS, task.png_depth).to_string());
+    }
+    if task.background != defaults.background {
+        push("--background", format_color(task.background));
+    }
+    if task.flatten {
+        push("--flatten", String::new());
+    }
+    if !task.defringe {
+        push("--no-defringe", String::new());
+    }
+    if task.deskew {
+        push("--deskew", String::new());
+    }
+    if task.auto_levels {
+        push("--auto-levels", String::new());
+    }
+    if task.brightness != defaults.brightness {
+        push("--brightness", task.brightness.to_string());
+    }
+    if task.contrast != defaults.contrast {
+        push("--contrast", task.contrast.to_string());
+    }
+    if let Some(levels) = task.posterize_levels {
+        push("--posterize", levels.to_string());
+    }
+    if task.jpeg_restart_interval != defaults.jpeg_restart_interval {
+        push("--restart-interval", task.jpeg_restart_interval.to_string());
+    }
+    if task.decode_timeout_secs != defaults.decode_timeout_secs {
+        push("--decode-timeout", task.decode_timeout_secs.to_string());
+    }
+    if task.salvage_truncated {
+        push("--salvage-truncated", String::new());
+    }
+    if task.log_timing {
+        push("--timing", String::new());
+    }
+    if let Some(size) = task.thumbnail_size {
+        push("--thumbnail", size.to_string());
+    }
+    if task.blurhash {
+        push("--blurhash", String::new());
+    }
+    if !task.note.is_empty() {
+        push("--note", task.note.clone());
+    }
+    for (flag, value) in [
+        ("--artist", &task.metadata.artist),
+        ("--copyright", &task.metadata.copyright),
+        ("--description", &task.metadata.description),
+    ] {
+        if !value.trim().is_empty() {
+            push(flag, value.clone());
+        }
+    }
+    if !task.post_command.trim().is_empty() {
+        push("--post-command", task.post_command.clone());
+    }
+    if let Some(dir) = &task.temp_dir {
+        push("--temp-dir", dir.display().to_string());
+    }
+    if task.keep_original {
+        push("--keep-original", String::new());
+    }
+    if let Some(watermark) = &task.watermark {
+        let defaults = Watermark::default();
+        match &watermark.image {
+            Some(path) => push("--watermark-image", path.display().to_string()),
+            None => push("--watermark", watermark.text.clone()),
+        }
+        if watermark.position != defaults.position {
+            push("--watermark-position", name_of(WATERMARK_POSITIONS, watermark.position).to_string());
+        }
+        if watermark.opacity != defaults.opacity {
+            push("--watermark-opacity", watermark.opacity.to_string());
+        }
+        if watermark.size != defaults.size {
+            push("--watermark-size", watermark.size.to_string());
+        }
+        if watermark.image.is_none() && watermark.color != defaults.color {
+            push("--watermark-color", format_color(watermark.color));
+        }
+    }
+
+    args.push(task.input_path.display().to_string());
+    args.push(task.output_path.display().to_string());
+    args
+}
+
+const WEBP_MODES: &[(WebpMode, &str)] = &[
+    (WebpMode::Lossless, "lossless"),
+    (WebpMode::NearLossless, "near-lossless"),
+    (WebpMode::Lossy, "lossy"),
+];
+
+const RESIZE_FILTERS: &[(ResizeFilter, &str)] = &[
+    (ResizeFilter::Nearest, "nearest"),
+    (ResizeFilter::Triangle, "triangle"),
+    (ResizeFilter::CatmullRom, "catmull-rom"),
+    (ResizeFilter::Lanczos3, "lanczos3"),
+];
+
+const PNG_DEPTHS: &[(PngDepth, &str)] = &[
+    (PngDepth::Auto, "auto"),
+    (PngDepth::L8, "l8"),
+    (PngDepth::La8, "la8"),
+    (PngDepth::Rgb8, "rgb8"),
+    (PngDepth::Rgba8, "rgba8"),
+    (PngDepth::Rgb16, "rgb16"),
+    (PngDepth::Rgba16, "rgba16"),
+];
+
+const WATERMARK_POSITIONS: &[(WatermarkPosition, &str)] = &[
+    (WatermarkPosition::TopLeft, "top-left"),
+    (WatermarkPosition::TopRight, "top-right"),
+    (WatermarkPosition::Center, "center"),
+    (WatermarkPosition::BottomLeft, "bottom-left"),
+    (WatermarkPosition::BottomRight, "bottom-right"),
+];
+
+fn name_of<T: PartialEq>(names: &[(T, &'static str)], value: T) -> &'static str {
+    names
+        .iter()
+        .find(|(candidate, _)| *candidate == value)
+        .map(|(_, name)| *name)
+        .unwrap_or_default()
+}
+
+fn number<T: std::str::FromStr + PartialOrd + std::fmt::Display>(
+    flag: &str,
+    value: &str,
+    min: T,
+    max: T,
+) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        _ => Err(format!("Error: {flag} must be a number from {min} to {max}, not {value}")),
+    }
+}
+
+// "x,y,w,h"
+fn rect(flag: &str, value: &str) -> Result<CropRect, String> {
+    let parts: Vec<_> = value.split(',').map(|part| part.trim().parse::<u32>()).collect();
+    match parts.as_slice() {
+        [Ok(x), Ok(y), Ok(width), Ok(height)] if *width > 0 && *height > 0 => Ok(CropRect {
+            x: *x,
+            y: *y,
+            width: *width,
+            height: *height,
+        }),
+        _ => Err(format!("Error: {flag} expects x,y,width,height, not {value}")),
+    }
+}
+
+fn format_rect(rect: CropRect) -> String {
+    format!("{},{},{},{}", rect.x, rect.y, rect.width, rect.height)
+}
+
+// "WxH"
+fn size(flag: &str, value: &str) -> Result<(u32, u32), String> {
+    match value.split_once(['x', 'X']) {
+        Some((width, height)) => match (width.parse::<u32>(), height.parse::<u32>()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+            _ => Err(format!("Error: {flag} expects WIDTHxHEIGHT, not {value}")),
+        },
+        None => Err(format!("Error: {flag} expects WIDTHxHEIGHT, not {value}")),
+    }
+}
+
+// "W:H", e.g. "16:9".
+fn ratio(flag: &str, value: &str) -> Result<(u32, u32), String> {
+    match value.split_once(':').map(|(width, height)| (width.parse::<u32>(), height.parse::<u32>())) {
+        Some((Ok(width), Ok(height))) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!("Error: {flag} expects W:H, not {value}")),
+    }
+}
+
+// "RRGGBB", with or without a leading '#'.
+fn color(flag: &str, value: &str) -> Result<[u8; 3], String> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() == 6
+        && let Ok(rgb) = u32::from_str_radix(hex, 16)
+    {
+        return Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]);
+    }
+    Err(format!("Error: {flag} expects a hex color like ff8800, not {value}"))
+}
+
+fn format_color([r, g, b]: [u8; 3]) -> String {
+    format!("{r:02x}{g:02x}{b:02x}")
+}
+
+// Leaves plain words alone and quotes anything a shell would split or expand.
+pub fn quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || "-_./:=,+@".contains(c)
+                || if cfg!(windows) { c == '\\' } else { c == '%' }
+        });
+    if plain {
+        word.to_string()
+    } else if cfg!(windows) {
+        // cmd expands %VAR% even inside quotes, and ^ only escapes outside them,
+        // so the quotes close and reopen around each %: "100"^%" done.png".
+        format!("\"{}\"", word.replace('"', "\\\"").replace('%', "\"^%\""))
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output_metadata::OutputMetadata;
+
+    // Every setting the command line has a flag for, away from its default.
+    fn busy_task() -> CompressionTask {
+        CompressionTask {
+            input_path: PathBuf::from("in put.png"),
+            output_path: PathBuf::from("out.webp"),
+            quality: 42,
+            png_level: 3,
+            convert_to_srgb: true,
+            rotation: Rotation::Cw270,
+            crop: Some(CropRect { x: 1, y: 2, width: 30, height: 40 }),
+            aspect_crop: Some(AspectCrop { width: 16, height: 9, smart: true }),
+            resize: Some(ResizeTarget { width: 800, height: 600, keep_aspect: false, shrink_only: true }),
+            resize_filter: ResizeFilter::CatmullRom,
+            protect_region: Some(CropRect { x: 5, y: 6, width: 7, height: 8 }),
+            protect_quality: 90,
+            page: 2,
+            sprite_columns: Some(4),
+            webp_mode: WebpMode::NearLossless,
+            webp_near_lossless: 60,
+            png_depth: PngDepth::Rgba16,
+            background: [0x12, 0xab, 0xff],
+            flatten: true,
+            defringe: false,
+            deskew: true,
+            auto_levels: true,
+            brightness: -15,
+            contrast: 12.5,
+            posterize_levels: Some(8),
+            jpeg_restart_interval: 4,
+            decode_timeout_secs: 30,
+            salvage_truncated: true,
+            log_timing: true,
+            thumbnail_size: Some(200),
+            blurhash: true,
+            note: "a note, with spaces".to_string(),
+            metadata: OutputMetadata {
+                artist: "Ann".to_string(),
+                copyright: "2026 Ann".to_string(),
+                description: "A test".to_string(),
+            },
+            post_command: "echo {file}".to_string(),
+            temp_dir: Some(PathBuf::from("/tmp/work")),
+            keep_original: true,
+            watermark: Some(Watermark {
+                text: "draft".to_string(),
+                color: [1, 2, 3],
+                image: None,
+                position: WatermarkPosition::TopLeft,
+                opacity: 0.25,
+                size: 7.5,
+            }),
+            ..CompressionTask::default()
+        }
+    }
+
+    #[test]
+    fn flags_round_trip() {
+        let defaults = CompressionTask::default();
+        let task = busy_task();
+        assert!(parse(&to_args(&task, &defaults), defaults).unwrap() == task);
+    }
+
+    #[test]
+    fn defaults_need_no_flags() {
+        let defaults = CompressionTask::default();
+        let task = CompressionTask { input_path: "a.png".into(), output_path: "b.jpg".into(), ..defaults.clone() };
+        assert_eq!(to_args(&task, &defaults), ["a.png", "b.jpg"]);
+    }
+
+    // Config values count as the defaults, so settings the GUI has moved away
+    // from them still get flags.
+    #[test]
+    fn config_defaults_round_trip() {
+        let config = config::Config { quality: Some(60), max_width: Some(1000), ..Default::default() };
+        let mut defaults = CompressionTask::default();
+        config.apply_to_task(&mut defaults);
+
+        let task = CompressionTask { input_path: "a.png".into(), output_path: "b.jpg".into(), ..Default::default() };
+        let flags = to_args(&task, &defaults);
+        assert_eq!(flags, ["--quality", "80", "--no-resize", "a.png", "b.jpg"]);
+        assert!(parse(&flags, defaults.clone()).unwrap() == task);
+
+        let same_as_config =
+            CompressionTask { input_path: "a.png".into(), output_path: "b.jpg".into(), ..defaults.clone() };
+        assert_eq!(to_args(&same_as_config, &defaults), ["a.png", "b.jpg"]);
+    }
+
+    #[test]
+    fn rejects_bad_flags() {
+        let parse = |words: &[&str]| {
+            let words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+            parse(&words, CompressionTask::default())
+        };
+        assert!(parse(&["--quality", "101", "a.png", "b.jpg"]).is_err());
+        assert!(parse(&["--quality"]).is_err());
+        assert!(parse(&["--frobnicate", "a.png", "b.jpg"]).is_err());
+        assert!(parse(&["--smart-crop", "a.png", "b.jpg"]).is_err());
+        assert!(parse(&["a.png"]).is_err());
+    }
+}
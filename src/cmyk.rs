@@ -0,0 +1,125 @@
+// CMYK and YCCK JPEGs, as exported for print. `image` turns these into RGB with
+// a fixed formula that ignores the embedded color profile and always assumes
+// Adobe's inverted samples, so they are decoded here instead: through the
+// profile when the `color-management` feature is on, else with a plain formula.
+
+use image::{DynamicImage, RgbImage};
+use zune_core::bytestream::ZCursor;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+use crate::jpeg_segments;
+
+pub fn is_cmyk(bytes: &[u8]) -> bool {
+    let mut decoder = JpegDecoder::new(ZCursor::new(bytes));
+    decoder.decode_headers().is_ok()
+        && matches!(decoder.input_colorspace(), Some(ColorSpace::CMYK | ColorSpace::YCCK))
+}
+
+pub fn decode(bytes: &[u8], notes: &mut Vec<String>) -> Result<DynamicImage, String> {
+    let mut decoder = JpegDecoder::new_with_options(
+        ZCursor::new(bytes),
+        DecoderOptions::default().set_strict_mode(false),
+    );
+    if let Err(e) = decoder.decode_headers() {
+        return Err(format!("Error loading image: {e:?}"));
+    }
+    let ycck = decoder.input_colorspace() == Some(ColorSpace::YCCK);
+    // Ask for the samples as stored; zune only converts these to RGB itself.
+    decoder.set_options(decoder.options().jpeg_set_out_colorspace(if ycck {
+        ColorSpace::YCCK
+    } else {
+        ColorSpace::CMYK
+    }));
+    let mut samples = match decoder.decode() {
+        Ok(samples) => samples,
+        Err(e) => return Err(format!("Error loading image: {e:?}")),
+    };
+    let Some((width, height)) = decoder.dimensions() else {
+        return Err("Error loading image: missing dimensions".to_string());
+    };
+
+    // YCCK is CMY run through the usual YCbCr transform, with K left as is.
+    // Undoing the transform and flipping gives the same samples as Adobe CMYK.
+    if ycck {
+        for pixel in samples.chunks_exact_mut(4) {
+            let [y, cb, cr] = [pixel[0], pixel[1], pixel[2]].map(f32::from);
+            let (cb, cr) = (cb - 128.0, cr - 128.0);
+            let channel = |v: f32| 255 - v.round().clamp(0.0, 255.0) as u8;
+            pixel[0] = channel(y + 1.402 * cr);
+            pixel[1] = channel(y - 0.344_136 * cb - 0.714_136 * cr);
+            pixel[2] = channel(y + 1.772 * cb);
+        }
+    }
+    // Photoshop, which writes the Adobe marker, stores 255 for no ink.
+    let inverted = ycck || has_adobe_marker(bytes);
+    let (width, height) = (width as u32, height as u32);
+
+    if let Some(profile) = decoder.icc_profile()
+        && let Some(rgb) = convert_with_profile(&samples, &profile, inverted, notes)
+        && let Some(image) = RgbImage::from_raw(width, height, rgb)
+    {
+        notes.push("converted from CMYK using its color profile".to_string());
+        return Ok(DynamicImage::ImageRgb8(image));
+    }
+
+    let rgb = samples
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let ink = |v: u8| if inverted { 255 - v } else { v };
+            let k = 255 - ink(pixel[3]) as u32;
+            [0, 1, 2].map(|i| ((255 - ink(pixel[i]) as u32) * k / 255) as u8)
+        })
+        .collect();
+    let Some(image) = RgbImage::from_raw(width, height, rgb) else {
+        return Err("Error loading image: truncated CMYK data".to_string());
+    };
+    notes.push("converted from CMYK without a color profile, colors are approximate".to_string());
+    Ok(DynamicImage::ImageRgb8(image))
+}
+
+#[cfg(feature = "color-management")]
+fn convert_with_profile(
+    samples: &[u8],
+    profile: &[u8],
+    inverted: bool,
+    notes: &mut Vec<String>,
+) -> Option<Vec<u8>> {
+    use lcms2::{Intent, PixelFormat, Profile, Transform};
+
+    let source = match Profile::new_icc(profile) {
+        Ok(source) => source,
+        Err(e) => {
+            notes.push(format!("embedded CMYK profile is invalid ({e})"));
+            return None;
+        }
+    };
+    let format = if inverted { PixelFormat::CMYK_8_REV } else { PixelFormat::CMYK_8 };
+    let transform = match Transform::<u8, u8>::new(
+        &source,
+        format,
+        &Profile::new_srgb(),
+        PixelFormat::RGB_8,
+        Intent::Perceptual,
+    ) {
+        Ok(transform) => transform,
+        Err(e) => {
+            notes.push(format!("could not convert CMYK profile to sRGB ({e})"));
+            return None;
+        }
+    };
+    let mut rgb = vec![0; samples.len() / 4 * 3];
+    transform.transform_pixels(samples, &mut rgb);
+    Some(rgb)
+}
+
+#[cfg(not(feature = "color-management"))]
+fn convert_with_profile(_: &[u8], _: &[u8], _: bool, _: &mut Vec<String>) -> Option<Vec<u8>> {
+    None
+}
+
+// Looks for an APP14 "Adobe" segment before the image data starts.
+fn has_adobe_marker(bytes: &[u8]) -> bool {
+    jpeg_segments::segments(bytes).any(|segment| segment.marker == 0xEE && segment.data.starts_with(b"Adobe"))
+}
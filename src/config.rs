@@ -0,0 +1,139 @@
+// Defaults read at startup from config.toml in the user's config folder, so
+// every machine can start from the same settings:
+//
+//     quality = 75
+//     format = "webp"
+//     max_width = 2560
+//     max_height = 2560
+//     name_template = "{name}_web"
+//     hotkey = "Ctrl+Shift+S"
+//     hotkey_folder = "/home/me/Pictures/Screenshots"
+//
+// Every key is optional. Environment variables named after the keys, e.g.
+// IMAGE_COMPRESSOR_QUALITY=60, override the file, and command-line flags
+// override both.
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::{CompressionTask, ResizeTarget};
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub quality: Option<u8>,
+    // Extension of the batch output format, e.g. "jpg".
+    pub format: Option<String>,
+    // Turns Resize on, fitting outputs within these dimensions.
+    // A missing one is left unbounded.
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    // Batch output file name, with `{name}` standing for the input's.
+    pub name_template: Option<String>,
+    // Turns the capture hotkey on with this shortcut, saving into `hotkey_folder`.
+    pub hotkey: Option<String>,
+    pub hotkey_folder: Option<PathBuf>,
+}
+
+// A missing file is the same as an empty one.
+pub fn load() -> Result<Config, String> {
+    let mut config = match config_dir().map(|dir| dir.join("config.toml")) {
+        Some(path) if path.exists() => {
+            let text = match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) => return Err(format!("Error reading config.toml: {e}")),
+            };
+            match toml::from_str(&text) {
+                Ok(config) => config,
+                Err(e) => return Err(format!("Error in config.toml: {}", e.message())),
+            }
+        }
+        _ => Config::default(),
+    };
+
+    if let Some(quality) = env_number("QUALITY", 1, 100)? {
+        config.quality = Some(quality);
+    }
+    if let Some(format) = env("FORMAT") {
+        config.format = Some(format);
+    }
+    if let Some(width) = env_number("MAX_WIDTH", 1, u32::MAX)? {
+        config.max_width = Some(width);
+    }
+    if let Some(height) = env_number("MAX_HEIGHT", 1, u32::MAX)? {
+        config.max_height = Some(height);
+    }
+    if let Some(template) = env("NAME_TEMPLATE") {
+        config.name_template = Some(template);
+    }
+    if let Some(hotkey) = env("HOTKEY") {
+        config.hotkey = Some(hotkey);
+    }
+    if let Some(folder) = env("HOTKEY_FOLDER") {
+        config.hotkey_folder = Some(PathBuf::from(folder));
+    }
+    if config.quality.is_some_and(|quality| !(1..=100).contains(&quality)) {
+        return Err("Error in config.toml: quality must be between 1 and 100".to_string());
+    }
+    Ok(config)
+}
+
+impl Config {
+    // Lays the quality and resize caps over `task`; the format and name template
+    // only matter to batches, which pick them up separately.
+    pub fn apply_to_task(&self, task: &mut CompressionTask) {
+        if let Some(quality) = self.quality {
+            task.quality = quality;
+        }
+        if let Some((width, height)) = self.resize_caps() {
+            task.resize = Some(ResizeTarget { width, height, keep_aspect: true, shrink_only: false });
+        }
+    }
+
+    // Largest dimensions the resize settings accept, standing in for a missing cap.
+    const UNBOUNDED: u32 = 65535;
+
+    // Width and height to fit within; None when neither is set.
+    pub fn resize_caps(&self) -> Option<(u32, u32)> {
+        match (self.max_width, self.max_height) {
+            (None, None) => None,
+            (width, height) => Some((
+                width.unwrap_or(Self::UNBOUNDED).clamp(1, Self::UNBOUNDED),
+                height.unwrap_or(Self::UNBOUNDED).clamp(1, Self::UNBOUNDED),
+            )),
+        }
+    }
+}
+
+// e.g. ~/.config/image-compressor on Linux.
+pub fn config_dir() -> Option<PathBuf> {
+    use std::env::var_os;
+
+    let config_dir = if cfg!(target_os = "windows") {
+        var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+    } else {
+        var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }?;
+    Some(config_dir.join("image-compressor"))
+}
+
+// IMAGE_COMPRESSOR_<key>, when set and not empty.
+fn env(key: &str) -> Option<String> {
+    std::env::var(format!("IMAGE_COMPRESSOR_{key}")).ok().filter(|value| !value.is_empty())
+}
+
+fn env_number<T: std::str::FromStr + PartialOrd>(key: &str, min: T, max: T) -> Result<Option<T>, String> {
+    let Some(value) = env(key) else {
+        return Ok(None);
+    };
+    match value.parse::<T>() {
+        Ok(number) if number >= min && number <= max => Ok(Some(number)),
+        _ => Err(format!("Error: IMAGE_COMPRESSOR_{key} is out of range or not a number: {value}")),
+    }
+}
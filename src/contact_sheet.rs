@@ -0,0 +1,63 @@
+// A contact sheet: one image with every picture of a folder shrunk into a
+// grid, each captioned with its file name, for looking over a folder at a
+// glance. Captions use the watermark's text rendering.
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::watermark;
+
+// Space around and between the cells, in pixels.
+const PADDING: u32 = 12;
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const CAPTION_COLOR: [u8; 3] = [40, 40, 40];
+
+// A picture for the sheet, already shrunk to fit within the thumbnail size.
+pub struct Cell {
+    pub name: String,
+    pub thumbnail: DynamicImage,
+}
+
+// Lays `cells` out `columns` wide, each in a `thumb_size` square with its
+// caption below.
+pub fn compose(cells: &[Cell], columns: u32, thumb_size: u32) -> Result<RgbaImage, String> {
+    if cells.is_empty() {
+        return Err("Error: no images to put on the contact sheet".to_string());
+    }
+    let columns = columns.clamp(1, cells.len() as u32);
+    let rows = (cells.len() as u32).div_ceil(columns);
+    let caption_height = (thumb_size / 10).clamp(12, 24);
+    let cell_height = thumb_size + caption_height + PADDING / 2;
+
+    let width = PADDING + columns * (thumb_size + PADDING);
+    let height = PADDING + rows * (cell_height + PADDING);
+    let mut sheet = RgbaImage::from_pixel(width, height, BACKGROUND);
+
+    for (index, cell) in cells.iter().enumerate() {
+        let (column, row) = (index as u32 % columns, index as u32 / columns);
+        let (x, y) = (PADDING + column * (thumb_size + PADDING), PADDING + row * (cell_height + PADDING));
+
+        // Centered in its square, so portrait and landscape pictures line up.
+        let thumbnail = cell.thumbnail.to_rgba8();
+        let offset_x = (thumb_size.saturating_sub(thumbnail.width())) / 2;
+        let offset_y = (thumb_size.saturating_sub(thumbnail.height())) / 2;
+        watermark::blend(&mut sheet, &thumbnail, (x + offset_x) as i64, (y + offset_y) as i64, 1.0);
+
+        let caption = caption(&cell.name, caption_height as f32, thumb_size)?;
+        let caption_x = x + thumb_size.saturating_sub(caption.width()) / 2;
+        watermark::blend(&mut sheet, &caption, caption_x as i64, (y + thumb_size + PADDING / 2) as i64, 1.0);
+    }
+    Ok(sheet)
+}
+
+// The file name, shortened with "…" until it fits `max_width`.
+fn caption(name: &str, height: f32, max_width: u32) -> Result<RgbaImage, String> {
+    let mut text = name.to_string();
+    let mut rendered = watermark::render_text(&text, height, CAPTION_COLOR)?;
+    let mut chars: Vec<char> = name.chars().collect();
+    while rendered.width() > max_width && chars.len() > 1 {
+        chars.pop();
+        text = chars.iter().collect::<String>() + "…";
+        rendered = watermark::render_text(&text, height, CAPTION_COLOR)?;
+    }
+    Ok(rendered)
+}
@@ -0,0 +1,60 @@
+// Keeps a parallel batch from decoding more large images at once than memory
+// allows. Each file reserves an estimate of what it will take before it is
+// decoded and gives it back when done; a file that doesn't fit waits until
+// enough has been given back.
+
+use std::path::Path;
+use std::sync::{Condvar, Mutex, PoisonError};
+
+// Decoded RGBA pixels plus room for a resized or converted copy and the
+// encoder's working buffers.
+const BYTES_PER_PIXEL: u64 = 4 * 3;
+
+pub struct DecodeBudget {
+    limit: u64,
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+// Returns its bytes to the budget when dropped.
+pub struct Reservation<'a> {
+    budget: &'a DecodeBudget,
+    bytes: u64,
+}
+
+impl DecodeBudget {
+    pub fn new(limit: u64) -> Self {
+        Self { limit: limit.max(1), in_use: Mutex::new(0), released: Condvar::new() }
+    }
+
+    // Blocks until `bytes` fit alongside what other files hold. A file larger
+    // than the whole budget waits until it can run alone.
+    pub fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let bytes = bytes.min(self.limit);
+        let in_use = self.in_use.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut in_use = self
+            .released
+            .wait_while(in_use, |in_use| *in_use + bytes > self.limit)
+            .unwrap_or_else(PoisonError::into_inner);
+        *in_use += bytes;
+        Reservation { budget: self, bytes }
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut in_use = self.budget.in_use.lock().unwrap_or_else(PoisonError::into_inner);
+        *in_use -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+// Rough memory needed to compress the image at `path`, from the dimensions in
+// its header. Files whose header can't be read count as nothing, since they
+// fail before decoding anyway.
+pub fn estimate(path: &Path) -> u64 {
+    match image::image_dimensions(path) {
+        Ok((width, height)) => width as u64 * height as u64 * BYTES_PER_PIXEL,
+        Err(_) => 0,
+    }
+}
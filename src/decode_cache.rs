@@ -0,0 +1,73 @@
+// The most recently decoded single-file input, shared between the UI, the
+// worker and the size estimator so tweaking settings doesn't re-read the source
+// each time. Entries are keyed by path, page and modification time, so an edited
+// file is decoded again. An input that isn't a file, like the CLI's stdin, is
+// handed over already decoded and has no modification time.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+use crate::{decode_input_page, DecodedInput};
+
+#[derive(Clone, Default)]
+pub struct DecodeCache(Arc<Mutex<Option<Entry>>>);
+
+struct Entry {
+    path: PathBuf,
+    page: usize,
+    // None for an input handed over already decoded, which is always current.
+    modified: Option<SystemTime>,
+    decoded: DecodedInput,
+    // Warnings from the original decode, repeated on every hit.
+    notes: Vec<String>,
+}
+
+impl DecodeCache {
+    // A cache already holding `decoded` as `page` of `path`, for an input that
+    // can't be read again from disk.
+    pub fn holding(path: &Path, page: usize, decoded: DecodedInput, notes: Vec<String>) -> Self {
+        let entry = Entry { path: path.to_path_buf(), page, modified: None, decoded, notes };
+        DecodeCache(Arc::new(Mutex::new(Some(entry))))
+    }
+
+    // `decode_input_page`, reusing the last result when the file hasn't changed.
+    pub fn decode(&self, path: &Path, page: usize, notes: &mut Vec<String>) -> Result<DecodedInput, String> {
+        let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        if let Some(entry) = self.lock().as_ref()
+            && entry.path == path
+            && entry.page == page
+            && (entry.modified.is_none() || entry.modified == modified)
+        {
+            notes.extend(entry.notes.iter().cloned());
+            return Ok(entry.decoded.clone());
+        }
+
+        // Without a modification time there is no telling whether the file changed.
+        let Some(modified) = modified else {
+            return decode_input_page(path, page, notes);
+        };
+
+        // Decoded without holding the lock so other threads aren't held up.
+        let mut decode_notes = Vec::new();
+        let decoded = decode_input_page(path, page, &mut decode_notes)?;
+        notes.extend(decode_notes.iter().cloned());
+        *self.lock() = Some(Entry {
+            path: path.to_path_buf(),
+            page,
+            modified: Some(modified),
+            decoded: decoded.clone(),
+            notes: decode_notes,
+        });
+        Ok(decoded)
+    }
+
+    // Drops the cached image, e.g. when another input is picked.
+    pub fn clear(&self) {
+        *self.lock() = None;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Entry>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
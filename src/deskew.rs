@@ -0,0 +1,96 @@
+// Straightens scans that went through the scanner at a slight angle. Text
+// lines and page edges show up as long straight edges; a Hough transform finds
+// their angle to the nearest degree, and a projection profile narrows it down
+// to a tenth of one.
+
+use image::{DynamicImage, Rgba, imageops::FilterType};
+use imageproc::edges::canny;
+use imageproc::geometric_transformations::{Border, Interpolation, rotate_about_center};
+use imageproc::hough::{LineDetectionOptions, detect_lines};
+
+// Lines are looked for in a copy no larger than this, which keeps the Hough
+// transform quick and doesn't change any angles.
+const DETECT_SIZE: u32 = 1024;
+// Anything tilted further than this is more likely a deliberate angle, or a
+// diagonal in the picture, than a skewed scan.
+const MAX_SKEW: f32 = 15.0;
+// Smallest correction worth resampling the image for.
+pub const MIN_SKEW: f32 = 0.1;
+// Canny thresholds on the Sobel gradient, which peaks around 1140.
+const EDGE_LOW: f32 = 50.0;
+const EDGE_HIGH: f32 = 150.0;
+
+// The skew in degrees, positive when the content is turned clockwise, or None
+// when the image has no long straight edges to measure it by.
+pub fn detect_angle(img: &DynamicImage) -> Option<f32> {
+    let small = if img.width() > DETECT_SIZE || img.height() > DETECT_SIZE {
+        img.resize(DETECT_SIZE, DETECT_SIZE, FilterType::Triangle)
+    } else {
+        img.clone()
+    };
+    let edges = canny(&small.to_luma8(), EDGE_LOW, EDGE_HIGH);
+    let (width, height) = edges.dimensions();
+
+    // A line whose angle falls between two of the Hough transform's
+    // one-degree steps spreads its votes, peaking at around 115 however long
+    // it is, so the threshold stays below that.
+    let options =
+        LineDetectionOptions { vote_threshold: (width.min(height) / 8).clamp(16, 80), suppression_radius: 8 };
+    // The Hough angle is the line's normal: 90° for a level line, 0° for an
+    // upright one. Either way, what's left over is the skew.
+    let mut votes = [0u32; 2 * MAX_SKEW as usize + 1];
+    for line in detect_lines(&edges, options) {
+        let skew = (line.angle_in_degrees % 90) as i32;
+        let skew = if skew > 45 { skew - 90 } else { skew };
+        if skew.unsigned_abs() <= MAX_SKEW as u32 {
+            votes[(skew + MAX_SKEW as i32) as usize] += 1;
+        }
+    }
+    let (index, &count) = votes.iter().enumerate().max_by_key(|&(index, &count)| (count, usize::MAX - index))?;
+    if count == 0 {
+        return None;
+    }
+    let coarse = index as f32 - MAX_SKEW;
+
+    let points: Vec<(f32, f32)> = edges
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0[0] > 0)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    let fine = (-10..=10)
+        .map(|step| coarse + step as f32 / 10.0)
+        .max_by(|a, b| sharpness(&points, *a).total_cmp(&sharpness(&points, *b)))?;
+    Some(fine)
+}
+
+// How tightly the edge points bunch into lines tilted by `skew` degrees, level
+// and upright together: the more points share a row or column once the tilt
+// is taken out, the higher the score.
+fn sharpness(points: &[(f32, f32)], skew: f32) -> f64 {
+    let (sin, cos) = skew.to_radians().sin_cos();
+    let mut rows = std::collections::HashMap::<i32, u64>::new();
+    let mut columns = std::collections::HashMap::<i32, u64>::new();
+    for &(x, y) in points {
+        *rows.entry((y * cos - x * sin).round() as i32).or_default() += 1;
+        *columns.entry((x * cos + y * sin).round() as i32).or_default() += 1;
+    }
+    rows.values().chain(columns.values()).map(|&count| (count * count) as f64).sum()
+}
+
+// Turns the image back by `skew` degrees, keeping its size. Corners uncovered
+// by the turn are filled with `background`, or left transparent when the image
+// has alpha.
+pub fn straighten(img: &DynamicImage, skew: f32, background: [u8; 3]) -> DynamicImage {
+    let fill = if img.color().has_alpha() {
+        Rgba([0, 0, 0, 0])
+    } else {
+        Rgba([background[0], background[1], background[2], 255])
+    };
+    let rotated = DynamicImage::ImageRgba8(rotate_about_center(
+        &img.to_rgba8(),
+        -skew.to_radians(),
+        Interpolation::Bicubic,
+        Border::Constant(fill),
+    ));
+    if img.color().has_alpha() { rotated } else { DynamicImage::ImageRgb8(rotated.to_rgb8()) }
+}
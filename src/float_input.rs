@@ -0,0 +1,48 @@
+// Floating-point inputs, such as 32-bit TIFFs and OpenEXR files, are brought
+// down to 8 bits here, right after decoding, so everything after works on an
+// ordinary image. Samples above 1.0 are high dynamic range and are tone mapped
+// rather than clipped; EXR stores linear light, which gets the sRGB curve.
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+pub fn is_float(img: &DynamicImage) -> bool {
+    matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_))
+}
+
+// `linear` says whether the samples are linear light rather than already
+// gamma encoded.
+pub fn to_8bit(img: &DynamicImage, linear: bool, notes: &mut Vec<String>) -> DynamicImage {
+    let has_alpha = img.color().has_alpha();
+    let float = img.to_rgba32f();
+    // Infinite samples are left out, or they would squash everything else to black.
+    let peak = float
+        .pixels()
+        .flat_map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2]])
+        .filter(|sample| sample.is_finite())
+        .fold(0.0f32, f32::max);
+    let hdr = peak > 1.0;
+    notes.push(if hdr {
+        format!("tone mapped high dynamic range input (peak {peak:.1}) to 8 bits")
+    } else {
+        "converted 32-bit float input to 8 bits".to_string()
+    });
+
+    let channel = |sample: f32| {
+        let sample = if sample.is_nan() { 0.0 } else { sample.clamp(0.0, peak.max(1.0)) };
+        // Extended Reinhard, which brings the peak to exactly 1.0.
+        let sample = if hdr { sample * (1.0 + sample / (peak * peak)) / (1.0 + sample) } else { sample };
+        let sample = if linear { linear_to_srgb(sample) } else { sample };
+        (sample * 255.0).round() as u8
+    };
+    let rgba = RgbaImage::from_fn(float.width(), float.height(), |x, y| {
+        let [r, g, b, a] = float.get_pixel(x, y).0;
+        let alpha = if a.is_nan() { 1.0 } else { a.clamp(0.0, 1.0) };
+        Rgba([channel(r), channel(g), channel(b), (alpha * 255.0).round() as u8])
+    });
+    let rgba = DynamicImage::ImageRgba8(rgba);
+    if has_alpha { rgba } else { DynamicImage::ImageRgb8(rgba.to_rgb8()) }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
@@ -0,0 +1,89 @@
+// Remembers the settings last used to compress each input folder, so picking
+// the folder again restores them. Stored as JSON in the user's config folder:
+//
+//     {
+//       "version": 1,
+//       "folders": {
+//         "/home/me/photos": { "format": "jpg", "settings": { "quality": 70 } }
+//       }
+//     }
+//
+// `settings` is a task with empty paths; missing fields fall back to defaults.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::CompressionTask;
+
+const PROFILES_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FolderProfile {
+    // Extension of the batch output format, e.g. "jpg".
+    pub format: String,
+    pub settings: CompressionTask,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProfilesFile {
+    version: u32,
+    folders: HashMap<PathBuf, FolderProfile>,
+}
+
+#[derive(Default)]
+pub struct FolderProfiles {
+    folders: HashMap<PathBuf, FolderProfile>,
+}
+
+impl FolderProfiles {
+    // Reads the stored profiles; a missing or unreadable file means none yet.
+    pub fn load() -> Self {
+        let Some(path) = profiles_path() else {
+            return Self::default();
+        };
+        let Ok(json) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<ProfilesFile>(&json) {
+            Ok(file) if file.version <= PROFILES_VERSION => Self { folders: file.folders },
+            _ => Self::default(),
+        }
+    }
+
+    pub fn get(&self, folder: &Path) -> Option<&FolderProfile> {
+        self.folders.get(folder)
+    }
+
+    // Records the profile for `folder` and writes the file straight away.
+    pub fn set(&mut self, folder: PathBuf, profile: FolderProfile) -> Result<(), String> {
+        self.folders.insert(folder, profile);
+
+        let Some(path) = profiles_path() else {
+            return Err("Error: no config folder to save folder settings in".to_string());
+        };
+        let file = ProfilesFile {
+            version: PROFILES_VERSION,
+            folders: self.folders.clone(),
+        };
+        let json = match serde_json::to_string_pretty(&file) {
+            Ok(json) => json,
+            Err(e) => return Err(format!("Error serializing folder settings: {e}")),
+        };
+        if let Some(dir) = path.parent()
+            && let Err(e) = std::fs::create_dir_all(dir)
+        {
+            return Err(format!("Error creating config folder: {e}"));
+        }
+        match std::fs::write(path, json) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Error writing folder settings: {e}")),
+        }
+    }
+}
+
+// e.g. ~/.config/image-compressor/folder_profiles.json on Linux.
+fn profiles_path() -> Option<PathBuf> {
+    Some(crate::config::config_dir()?.join("folder_profiles.json"))
+}
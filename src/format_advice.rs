@@ -0,0 +1,96 @@
+// A suggested output format for an input, from a quick look at its pixels:
+// graphics with few colors suit PNG's palette, flat artwork and screenshots
+// lossless WebP, and photos (with or without transparency) lossy WebP.
+
+use image::{DynamicImage, GenericImageView, RgbaImage, imageops::FilterType};
+
+use crate::OutputFormat;
+
+// Pixels are sampled from a copy no larger than this. Nearest-neighbor
+// sampling keeps the colors exact, so a palette image still counts as one.
+const SAMPLE_SIZE: u32 = 512;
+// PNG can store up to this many colors as a palette.
+const PALETTE_COLORS: usize = 256;
+// Share of pixels exactly matching their right-hand neighbor above which an
+// image is mostly flat areas, like a screenshot or a diagram. Camera noise
+// keeps photos far below it.
+const FLAT_SHARE: f64 = 0.5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Suggestion {
+    Png,
+    WebpLossless,
+    WebpLossy,
+}
+
+impl Suggestion {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Png => "PNG",
+            Self::WebpLossless => "WebP lossless",
+            Self::WebpLossy => "WebP lossy",
+        }
+    }
+
+    pub fn format(self) -> OutputFormat {
+        match self {
+            Self::Png => OutputFormat::Png,
+            Self::WebpLossless | Self::WebpLossy => OutputFormat::WebP,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::WebpLossless | Self::WebpLossy => "webp",
+        }
+    }
+}
+
+// The suggestion and a short reason for it, e.g. "few colors".
+pub fn suggest(img: &DynamicImage) -> (Suggestion, &'static str) {
+    let sample = if img.width() > SAMPLE_SIZE || img.height() > SAMPLE_SIZE {
+        let (width, height) = fit(img.dimensions());
+        img.resize_exact(width, height, FilterType::Nearest).to_rgba8()
+    } else {
+        img.to_rgba8()
+    };
+
+    let mut colors = std::collections::HashSet::new();
+    for pixel in sample.pixels() {
+        colors.insert(pixel.0);
+        if colors.len() > PALETTE_COLORS {
+            break;
+        }
+    }
+    if colors.len() <= PALETTE_COLORS {
+        return (Suggestion::Png, "few colors");
+    }
+    if flat_share(&sample) > FLAT_SHARE {
+        return (Suggestion::WebpLossless, "flat graphics");
+    }
+    let has_alpha = sample.pixels().any(|pixel| pixel.0[3] < 255);
+    (Suggestion::WebpLossy, if has_alpha { "photo with transparency" } else { "photo" })
+}
+
+// `(width, height)` scaled down to fit within the sample size.
+fn fit((width, height): (u32, u32)) -> (u32, u32) {
+    let scale = SAMPLE_SIZE as f64 / width.max(height) as f64;
+    (((width as f64 * scale).round() as u32).max(1), ((height as f64 * scale).round() as u32).max(1))
+}
+
+fn flat_share(img: &RgbaImage) -> f64 {
+    let (width, height) = img.dimensions();
+    if width < 2 {
+        return 1.0;
+    }
+    let mut same = 0u64;
+    for y in 0..height {
+        for x in 0..width - 1 {
+            if img.get_pixel(x, y) == img.get_pixel(x + 1, y) {
+                same += 1;
+            }
+        }
+    }
+    same as f64 / ((width - 1) as u64 * height as u64) as f64
+}
@@ -0,0 +1,86 @@
+// A system-wide shortcut that captures the screen and compresses it, even while
+// the window is in the background. Needs the `hotkey` feature, and works on
+// Windows, macOS and X11; Wayland doesn't let applications grab keys globally,
+// so registering fails there.
+
+#[cfg(all(feature = "hotkey", any(windows, target_os = "macos", target_os = "linux")))]
+mod imp {
+    use std::sync::mpsc::{Receiver, channel};
+
+    use global_hotkey::hotkey::HotKey;
+    use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+    pub struct GlobalShortcut {
+        manager: GlobalHotKeyManager,
+        current: Option<HotKey>,
+        pressed: Receiver<u32>,
+    }
+
+    impl GlobalShortcut {
+        // Only one is ever made: the library takes a single event handler for
+        // the whole process. Presses wake the UI through `ctx`.
+        pub fn new(ctx: &egui::Context) -> Result<Self, String> {
+            let manager = match GlobalHotKeyManager::new() {
+                Ok(manager) => manager,
+                Err(e) => return Err(format!("Error setting up global hotkeys: {e}")),
+            };
+            let (tx, pressed) = channel();
+            let ctx = ctx.clone();
+            GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+                if event.state == HotKeyState::Pressed {
+                    let _ = tx.send(event.id);
+                    ctx.request_repaint();
+                }
+            }));
+            Ok(Self { manager, current: None, pressed })
+        }
+
+        // Swaps the registered shortcut, e.g. "Ctrl+Shift+S", for `shortcut`;
+        // None just removes it.
+        pub fn set(&mut self, shortcut: Option<&str>) -> Result<(), String> {
+            if let Some(current) = self.current.take() {
+                let _ = self.manager.unregister(current);
+            }
+            let Some(shortcut) = shortcut else {
+                return Ok(());
+            };
+            let hotkey: HotKey = match shortcut.parse() {
+                Ok(hotkey) => hotkey,
+                Err(e) => return Err(format!("Error in hotkey {shortcut}: {e}")),
+            };
+            if let Err(e) = self.manager.register(hotkey) {
+                return Err(format!("Error registering hotkey {shortcut}: {e}"));
+            }
+            self.current = Some(hotkey);
+            Ok(())
+        }
+
+        // Whether the shortcut was pressed since the last call. Every queued
+        // press is taken, so a burst of them runs a single capture.
+        pub fn pressed(&self) -> bool {
+            let id = self.current.map(|hotkey| hotkey.id());
+            self.pressed.try_iter().filter(|&event| Some(event) == id).count() > 0
+        }
+    }
+}
+
+#[cfg(not(all(feature = "hotkey", any(windows, target_os = "macos", target_os = "linux"))))]
+mod imp {
+    pub struct GlobalShortcut;
+
+    impl GlobalShortcut {
+        pub fn new(_ctx: &egui::Context) -> Result<Self, String> {
+            Err("Error: global hotkeys are not available in this build".to_string())
+        }
+
+        pub fn set(&mut self, _shortcut: Option<&str>) -> Result<(), String> {
+            Ok(())
+        }
+
+        pub fn pressed(&self) -> bool {
+            false
+        }
+    }
+}
+
+pub use imp::GlobalShortcut;
@@ -0,0 +1,80 @@
+// What the input file says about itself, for the Metadata panel: its format,
+// dimensions and color type, whether it carries a color profile, and its EXIF
+// tags. Read straight from the file's headers, without decoding the pixels.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{ImageDecoder, ImageReader};
+
+// Longer values (maker notes, embedded thumbnails) are cut short for display.
+const MAX_VALUE_LEN: usize = 80;
+
+pub struct ImageMetadata {
+    // (label, value) pairs, in display order.
+    pub summary: Vec<(String, String)>,
+    pub exif: Vec<(String, String)>,
+}
+
+pub fn read(path: &Path) -> Result<ImageMetadata, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(format!("Error reading input file: {e}")),
+    };
+    let mut reader = ImageReader::new(Cursor::new(&bytes));
+    let format = image::guess_format(&bytes).ok();
+    if let Some(format) = format {
+        reader.set_format(format);
+    }
+    let mut decoder = match reader.into_decoder() {
+        Ok(decoder) => decoder,
+        Err(e) => return Err(format!("Error reading image metadata: {e}")),
+    };
+
+    let (width, height) = decoder.dimensions();
+    let icc_profile = decoder.icc_profile().ok().flatten();
+    let mut summary = vec![
+        ("Format".to_string(), format.map_or("unknown".to_string(), |format| format!("{format:?}"))),
+        ("Dimensions".to_string(), format!("{width} × {height}")),
+        ("Color type".to_string(), format!("{:?}", decoder.original_color_type())),
+        (
+            "Color profile".to_string(),
+            icc_profile.map_or("none".to_string(), |profile| format!("embedded, {} bytes", profile.len())),
+        ),
+    ];
+
+    let exif = match decoder.exif_metadata().ok().flatten() {
+        Some(raw) => exif_fields(raw),
+        None => Vec::new(),
+    };
+    summary.push((
+        "EXIF".to_string(),
+        match exif.len() {
+            0 => "none".to_string(),
+            1 => "1 tag".to_string(),
+            count => format!("{count} tags"),
+        },
+    ));
+    Ok(ImageMetadata { summary, exif })
+}
+
+// Tags of the main image; the thumbnail's own IFD mostly repeats them.
+fn exif_fields(mut raw: Vec<u8>) -> Vec<(String, String)> {
+    // Some decoders hand over the APP1 payload with its "Exif" header still on.
+    if raw.starts_with(b"Exif\0\0") {
+        raw.drain(..6);
+    }
+    let Ok(exif) = exif::Reader::new().read_raw(raw) else {
+        return Vec::new();
+    };
+    exif.fields()
+        .filter(|field| field.ifd_num == exif::In::PRIMARY)
+        .map(|field| {
+            let mut value = field.display_value().with_unit(&exif).to_string();
+            if value.chars().count() > MAX_VALUE_LEN {
+                value = value.chars().take(MAX_VALUE_LEN).collect::<String>() + "…";
+            }
+            (field.tag.to_string(), value)
+        })
+        .collect()
+}
@@ -0,0 +1,62 @@
+// Saving and loading queued compression jobs as a small JSON file, so that a
+// set of input/output/quality entries can be re-run later.
+//
+// Format:
+//
+//     {
+//       "version": 1,
+//       "jobs": [
+//         { "input_path": "in/photo.png", "output_path": "out/photo.jpg", "quality": 80 }
+//       ]
+//     }
+//
+// Any task field may be omitted from an entry and falls back to its default.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CompressionTask;
+
+const JOB_FILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct JobFile {
+    version: u32,
+    jobs: Vec<CompressionTask>,
+}
+
+pub fn save_job_file(path: &Path, jobs: &[CompressionTask]) -> Result<(), String> {
+    let file = JobFile {
+        version: JOB_FILE_VERSION,
+        jobs: jobs.to_vec(),
+    };
+    let json = match serde_json::to_string_pretty(&file) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Error serializing queue: {e}")),
+    };
+
+    match std::fs::write(path, json) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!("Error writing job file: {e}")),
+    }
+}
+
+pub fn load_job_file(path: &Path) -> Result<Vec<CompressionTask>, String> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Error reading job file: {e}")),
+    };
+    let file: JobFile = match serde_json::from_str(&json) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Error parsing job file: {e}")),
+    };
+
+    if file.version > JOB_FILE_VERSION {
+        return Err(format!(
+            "Error: job file version {} is newer than this app supports",
+            file.version
+        ));
+    }
+    Ok(file.jobs)
+}
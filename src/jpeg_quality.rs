@@ -0,0 +1,52 @@
+// Guesses the quality a JPEG was saved at from its luminance quantization
+// table, by comparing it with the standard libjpeg table that encoders scale
+// by quality. Recompressing above that quality can't bring back lost detail.
+
+use crate::jpeg_segments;
+
+// The example luminance table from the JPEG spec (Annex K), which libjpeg and
+// most other encoders scale. Only its sum is used, so the order doesn't matter.
+const STANDARD_LUMINANCE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69,
+    56, 14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104,
+    113, 92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+// Estimated quality (1-100), or None when `bytes` has no luminance table.
+pub fn estimate(bytes: &[u8]) -> Option<u8> {
+    let table = luminance_table(bytes)?;
+    // Quality 100 scales every entry to zero, which encoders round up to 1.
+    if table.iter().all(|&v| v == 1) {
+        return Some(100);
+    }
+    let standard: u32 = STANDARD_LUMINANCE.iter().map(|&v| v as u32).sum();
+    // The percentage libjpeg scaled the standard table by.
+    let scale = table.iter().map(|&v| v as f64).sum::<f64>() * 100.0 / standard as f64;
+    let quality = if scale <= 100.0 { (200.0 - scale) / 2.0 } else { 5000.0 / scale };
+    Some(quality.round().clamp(1.0, 100.0) as u8)
+}
+
+// DQT, the segment holding quantization tables.
+const DEFINE_QUANTIZATION: u8 = 0xDB;
+
+// Table 0 from the DQT segments before the image data.
+fn luminance_table(bytes: &[u8]) -> Option<Vec<u16>> {
+    for segment in jpeg_segments::segments(bytes).filter(|segment| segment.marker == DEFINE_QUANTIZATION) {
+        // A DQT segment can hold several tables, each prefixed by precision and id.
+        let mut tables = segment.data;
+        while !tables.is_empty() {
+            let (precision, id) = (tables[0] >> 4, tables[0] & 0x0F);
+            let size = if precision == 0 { 64 } else { 128 };
+            let values = tables.get(1..1 + size)?;
+            if id == 0 {
+                return Some(if precision == 0 {
+                    values.iter().map(|&v| v as u16).collect()
+                } else {
+                    values.chunks_exact(2).map(|v| u16::from_be_bytes([v[0], v[1]])).collect()
+                });
+            }
+            tables = &tables[1 + size..];
+        }
+    }
+    None
+}
@@ -0,0 +1,75 @@
+// Walks the segments at the start of a JPEG, up to and including the first
+// start of scan. Each one carries its length, so the walk steps over anything
+// inside them, such as an EXIF thumbnail with markers of its own.
+
+// Start of scan: the headers are over and the image data follows.
+pub const START_OF_SCAN: u8 = 0xDA;
+
+pub struct Segment<'a> {
+    pub marker: u8,
+    // What follows the length field; for the start of scan, everything after
+    // the marker, image data included.
+    pub data: &'a [u8],
+}
+
+pub struct Segments<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    done: bool,
+    cut_short: bool,
+}
+
+pub fn segments(bytes: &[u8]) -> Segments<'_> {
+    // Past the start of image marker.
+    Segments { bytes, pos: 2, done: false, cut_short: false }
+}
+
+impl Segments<'_> {
+    // Whether the walk stopped because the file ended partway through the headers.
+    pub fn cut_short(&self) -> bool {
+        self.cut_short
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Segment<'a>> {
+        while !self.done {
+            let marker = match self.bytes.get(self.pos..self.pos + 2) {
+                Some(&[0xFF, marker]) => marker,
+                // Not laid out as expected, which the decoder will report.
+                Some(_) => break,
+                None => {
+                    self.cut_short = true;
+                    break;
+                }
+            };
+            match marker {
+                // Padding before a marker.
+                0xFF => self.pos += 1,
+                // Markers without a length.
+                0x01 | 0xD0..=0xD8 => self.pos += 2,
+                START_OF_SCAN => {
+                    self.done = true;
+                    return Some(Segment { marker, data: &self.bytes[self.pos + 2..] });
+                }
+                _ => {
+                    let Some(&[high, low]) = self.bytes.get(self.pos + 2..self.pos + 4) else {
+                        self.cut_short = true;
+                        break;
+                    };
+                    let end = self.pos + 2 + u16::from_be_bytes([high, low]) as usize;
+                    let Some(data) = self.bytes.get(self.pos + 4..end) else {
+                        self.cut_short = true;
+                        break;
+                    };
+                    self.pos = end;
+                    return Some(Segment { marker, data });
+                }
+            }
+        }
+        self.done = true;
+        None
+    }
+}
@@ -1,300 +1,6461 @@
 #![windows_subsystem = "windows"]
 
-use std::path::PathBuf;
+mod animation;
+mod batch_report;
+mod capture;
+mod cli;
+mod cmyk;
+mod config;
+mod contact_sheet;
+mod decode_budget;
+mod decode_cache;
+mod deskew;
+mod float_input;
+mod folder_profiles;
+mod format_advice;
+mod hotkey;
+mod image_metadata;
+mod job_file;
+mod jpeg_quality;
+mod jpeg_segments;
+mod memory_usage;
+mod metrics;
+mod output_metadata;
+mod pdf_images;
+mod size_estimate;
+mod smart_crop;
+mod taskbar;
+mod tiff_pages;
+mod truncated_input;
+mod upload;
+mod watermark;
+mod zip_archive;
+
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use eframe::egui;
 use image::{ImageFormat, DynamicImage};
 use rfd::FileDialog;
+use serde::{Deserialize, Serialize};
+
+// Extensions picked up when scanning a folder for batch compression.
+#[cfg(not(feature = "exr"))]
+const INPUT_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "tif", "tiff", "gif"];
+#[cfg(feature = "exr")]
+const INPUT_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "tif", "tiff", "gif", "exr"];
+
+// Formats offered as the batch output format.
+const OUTPUT_FORMATS: &[ImageFormat] = &[ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP];
+
+// Maximum edge length of the downscaled copy shown in the preview pane.
+const PREVIEW_MAX_SIZE: u32 = 1024;
+
+// Edge length of the per-file thumbnails shown for a batch folder.
+const BATCH_THUMBNAIL_SIZE: u32 = 96;
+// Longest edge of the reference image shown next to its scores.
+const REFERENCE_THUMBNAIL_SIZE: u32 = 160;
+// Lossy qualities below this get a warning; results are rarely usable.
+const LOW_QUALITY_WARNING: u8 = 15;
+// Resizing to less than this fraction of the source's width or height gets a
+// warning on the compress buttons; fine detail and text won't survive it.
+const HEAVY_DOWNSCALE_WARNING: f32 = 0.25;
+// One-click qualities shown under the slider.
+const QUALITY_PRESETS: &[(&str, u8)] = &[("Max", 95), ("High", 85), ("Balanced", 75), ("Small", 50)];
+// What "Optimize for web" writes: WebP no larger than full HD, plus a thumbnail.
+const WEB_MAX_SIDE: u32 = 1920;
+const WEB_QUALITY: u8 = 80;
+const WEB_THUMBNAIL_SIZE: u32 = 400;
+// Number of finished batch files to average over before showing an ETA.
+const ETA_MIN_SAMPLES: usize = 2;
+// Time for the minimized window to get out of the way before the screen is captured.
+const CAPTURE_DELAY: Duration = Duration::from_millis(400);
+
+// Height of the preview viewport in points; "Fit" scales the image into it.
+const PREVIEW_FIT_HEIGHT: f32 = 300.0;
+// Tallest each image of the pinned-quality comparison is drawn.
+const COMPARISON_HEIGHT: f32 = 240.0;
+
+// Zoom limits and step for the preview, in physical pixels per preview texture pixel.
+const PREVIEW_MIN_ZOOM: f32 = 0.05;
+const PREVIEW_MAX_ZOOM: f32 = 8.0;
+const PREVIEW_ZOOM_STEP: f32 = 1.25;
+
+fn main() -> Result<(), eframe::Error> {
+    // Any arguments mean command-line mode: compress one file and exit.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([500.0, 560.0])
+            .with_resizable(true),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "Image Compressor",
+        options,
+        Box::new(|_cc| Ok(Box::new(ImageCompressorApp::new()))),
+    )
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct CompressionTask {
+    input_path: PathBuf,
+    output_path: PathBuf,
+    quality: u8,
+    // Region of the source image to keep, in source pixel coordinates.
+    crop: Option<CropRect>,
+    // Then crop to a fixed width:height ratio, as large as fits.
+    aspect_crop: Option<AspectCrop>,
+    // Convert from the embedded ICC profile to sRGB before encoding.
+    convert_to_srgb: bool,
+    // Extra rotation applied after the EXIF orientation has been honored.
+    rotation: Rotation,
+    // Detect how far a scan is tilted and rotate it level.
+    deskew: bool,
+    resize: Option<ResizeTarget>,
+    // Used for the resize and the thumbnail.
+    resize_filter: ResizeFilter,
+    // Region (in source coordinates) encoded at `protect_quality`; JPEG output only.
+    protect_region: Option<CropRect>,
+    protect_quality: u8,
+    // Zero-based page to decode from multi-page TIFFs, or frame from animations.
+    page: usize,
+    // Tile every frame of an animation into one image, this many to a row.
+    sprite_columns: Option<u32>,
+    // MCUs between JPEG restart markers; 0 writes none.
+    jpeg_restart_interval: u16,
+    // Give up on inputs that take longer than this many seconds to decode; 0 waits forever.
+    decode_timeout_secs: u32,
+    // Compress the part of a truncated input that decoded instead of failing.
+    salvage_truncated: bool,
+    // Log how long each file spent decoding, transforming, encoding and writing.
+    log_timing: bool,
+    // Shell command run on each written output, with `{file}` standing for its path.
+    post_command: String,
+    // Folder outputs are written to before being moved into place; None uses the
+    // output's own folder.
+    temp_dir: Option<PathBuf>,
+    // Rename an existing output to `<name>.orig` instead of replacing it.
+    keep_original: bool,
+    webp_mode: WebpMode,
+    // libwebp near-lossless preprocessing level: 100 is lossless, lower trades
+    // exactness for smaller files. Only used in `WebpMode::NearLossless`.
+    webp_near_lossless: u8,
+    // Color that transparent areas are flattened onto for formats without alpha.
+    background: [u8; 3],
+    // Flatten onto `background` for every format, so all outputs are opaque.
+    flatten: bool,
+    // Resize in premultiplied alpha so edges don't pick up the (often black)
+    // color hiding under fully transparent pixels.
+    defringe: bool,
+    // Stretch each channel's histogram to the full range, e.g. for faded scans.
+    auto_levels: bool,
+    // Tone tweaks applied before encoding; 0 leaves the image unchanged.
+    brightness: i32,
+    contrast: f32,
+    // Round each color channel to this many levels, for a banded look and smaller PNGs.
+    posterize_levels: Option<u8>,
+    // Pixel layout of PNG output.
+    png_depth: PngDepth,
+    // Deflate level of PNG output, from 0 (stored) to 9 (smallest).
+    png_level: u8,
+    // Where to send the output after writing it. Never saved to job files, since
+    // it can carry credentials.
+    #[serde(skip)]
+    upload: Option<upload::UploadTarget>,
+    // Tells queued jobs apart, duplicates included, for the queue's selection.
+    // 0 outside the queue, and not saved to job files.
+    #[serde(skip)]
+    queue_id: u64,
+    // Free-text note written to a sidecar next to the output; empty for none.
+    note: String,
+    // Author, copyright and description written into the output's EXIF.
+    metadata: output_metadata::OutputMetadata,
+    // Blended on after resizing, so it is sized relative to the final image.
+    watermark: Option<watermark::Watermark>,
+    // Also write `<name>_thumb.<ext>`, fitted within this many pixels per side.
+    thumbnail_size: Option<u32>,
+    // Compute a BlurHash placeholder of the output: copied to the clipboard for a
+    // single file, added to the report for a batch.
+    blurhash: bool,
+}
+
+// Resampling used when resizing: sharp-edged Nearest suits pixel art, and the
+// smoother filters photos, from fast Triangle to Lanczos3 which keeps the most
+// detail.
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    #[default]
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    const ALL: [ResizeFilter; 4] =
+        [ResizeFilter::Nearest, ResizeFilter::Triangle, ResizeFilter::CatmullRom, ResizeFilter::Lanczos3];
+
+    fn label(self) -> &'static str {
+        match self {
+            ResizeFilter::Nearest => "Nearest (pixel art)",
+            ResizeFilter::Triangle => "Triangle",
+            ResizeFilter::CatmullRom => "Catmull-Rom",
+            ResizeFilter::Lanczos3 => "Lanczos3 (photos)",
+        }
+    }
+
+    fn filter_type(self) -> image::imageops::FilterType {
+        use image::imageops::FilterType;
+
+        match self {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+enum PngDepth {
+    // Keep the layout of the processed image (floats become 16-bit).
+    #[default]
+    Auto,
+    L8,
+    La8,
+    Rgb8,
+    Rgba8,
+    Rgb16,
+    Rgba16,
+}
+
+impl PngDepth {
+    const ALL: [PngDepth; 7] = [
+        PngDepth::Auto,
+        PngDepth::L8,
+        PngDepth::La8,
+        PngDepth::Rgb8,
+        PngDepth::Rgba8,
+        PngDepth::Rgb16,
+        PngDepth::Rgba16,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PngDepth::Auto => "Auto (match source)",
+            PngDepth::L8 => "Gray, 8-bit",
+            PngDepth::La8 => "Gray + alpha, 8-bit",
+            PngDepth::Rgb8 => "RGB, 8-bit",
+            PngDepth::Rgba8 => "RGBA, 8-bit",
+            PngDepth::Rgb16 => "RGB, 16-bit",
+            PngDepth::Rgba16 => "RGBA, 16-bit",
+        }
+    }
+
+    // Converts `img` to this layout.
+    fn convert(self, img: &DynamicImage) -> DynamicImage {
+        match self {
+            PngDepth::Auto => match img {
+                DynamicImage::ImageRgb32F(_) => DynamicImage::ImageRgb16(img.to_rgb16()),
+                DynamicImage::ImageRgba32F(_) => DynamicImage::ImageRgba16(img.to_rgba16()),
+                _ => img.clone(),
+            },
+            PngDepth::L8 => DynamicImage::ImageLuma8(img.to_luma8()),
+            PngDepth::La8 => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+            PngDepth::Rgb8 => DynamicImage::ImageRgb8(img.to_rgb8()),
+            PngDepth::Rgba8 => DynamicImage::ImageRgba8(img.to_rgba8()),
+            PngDepth::Rgb16 => DynamicImage::ImageRgb16(img.to_rgb16()),
+            PngDepth::Rgba16 => DynamicImage::ImageRgba16(img.to_rgba16()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+enum WebpMode {
+    #[default]
+    Lossless,
+    NearLossless,
+    // Uses the main quality value.
+    Lossy,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct ResizeTarget {
+    width: u32,
+    height: u32,
+    // Fit inside width×height keeping the image's own aspect ratio, rather than stretching.
+    keep_aspect: bool,
+    // Leave images that already fit at their own size instead of enlarging them.
+    #[serde(default)]
+    shrink_only: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct AspectCrop {
+    width: u32,
+    height: u32,
+    // Keep the most detailed part of the image rather than its center.
+    smart: bool,
+}
+
+impl AspectCrop {
+    // Largest size with this ratio that fits in `size`.
+    fn fit(self, [width, height]: [u32; 2]) -> [u32; 2] {
+        let (ratio_w, ratio_h) = (self.width.max(1) as u64, self.height.max(1) as u64);
+        let (width, height) = (width as u64, height as u64);
+        if width * ratio_h > height * ratio_w {
+            [(height * ratio_w / ratio_h).max(1) as u32, height as u32]
+        } else {
+            [width as u32, (width * ratio_h / ratio_w).max(1) as u32]
+        }
+    }
+
+    fn region(self, img: &DynamicImage) -> CropRect {
+        let [width, height] = self.fit([img.width(), img.height()]);
+        let (x, y) = if self.smart {
+            smart_crop::most_detailed(img, width, height)
+        } else {
+            ((img.width() - width) / 2, (img.height() - height) / 2)
+        };
+        CropRect { x, y, width, height }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    fn clockwise(self) -> Self {
+        match self {
+            Rotation::None => Rotation::Cw90,
+            Rotation::Cw90 => Rotation::Cw180,
+            Rotation::Cw180 => Rotation::Cw270,
+            Rotation::Cw270 => Rotation::None,
+        }
+    }
+
+    fn counter_clockwise(self) -> Self {
+        self.clockwise().clockwise().clockwise()
+    }
+
+    fn apply(self, img: DynamicImage) -> DynamicImage {
+        match self {
+            Rotation::None => img,
+            Rotation::Cw90 => img.rotate90(),
+            Rotation::Cw180 => img.rotate180(),
+            Rotation::Cw270 => img.rotate270(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct CropRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Default for CompressionTask {
+    fn default() -> Self {
+        Self {
+            input_path: PathBuf::new(),
+            output_path: PathBuf::new(),
+            quality: 80,
+            crop: None,
+            aspect_crop: None,
+            convert_to_srgb: false,
+            rotation: Rotation::None,
+            deskew: false,
+            resize: None,
+            resize_filter: ResizeFilter::Lanczos3,
+            protect_region: None,
+            protect_quality: 95,
+            page: 0,
+            webp_mode: WebpMode::Lossless,
+            webp_near_lossless: 60,
+            background: [255, 255, 255],
+            flatten: false,
+            defringe: true,
+            auto_levels: false,
+            brightness: 0,
+            contrast: 0.0,
+            posterize_levels: None,
+            png_depth: PngDepth::Auto,
+            png_level: 6,
+            upload: None,
+            queue_id: 0,
+            note: String::new(),
+            metadata: output_metadata::OutputMetadata::default(),
+            watermark: None,
+            thumbnail_size: None,
+            blurhash: false,
+            sprite_columns: None,
+            jpeg_restart_interval: 0,
+            decode_timeout_secs: 120,
+            salvage_truncated: false,
+            log_timing: false,
+            post_command: String::new(),
+            temp_dir: None,
+            keep_original: false,
+        }
+    }
+}
+
+// Still images joined into one animated WebP, in the order given.
+struct AnimationTask {
+    frames: Vec<PathBuf>,
+    output_path: PathBuf,
+    // How long each frame is shown.
+    delay_ms: u32,
+    quality: u8,
+    lossless: bool,
+    temp_dir: Option<PathBuf>,
+}
+
+struct ContactSheetTask {
+    images: Vec<PathBuf>,
+    columns: u32,
+    // Each picture is shrunk to fit a square this many pixels wide.
+    thumb_size: u32,
+    // Encoder settings, with the sheet's own path as the output.
+    encoding: CompressionTask,
+}
+
+#[derive(Default)]
+struct BatchTask {
+    tasks: Vec<CompressionTask>,
+    filters: BatchFilters,
+    // Lower qualities until all outputs together fit in this many bytes.
+    size_budget: Option<u64>,
+    // Folder to write the batch report to, when one is wanted.
+    report_dir: Option<PathBuf>,
+    // Files in the input folder that aren't images the batch can compress.
+    unsupported: Vec<PathBuf>,
+    // Where `unsupported` files are copied unchanged; None only logs them.
+    copy_unsupported_to: Option<PathBuf>,
+    // Compress several files at once; None does them one after another.
+    parallel: Option<ParallelBatch>,
+}
+
+impl BatchTask {
+    // The settings a retry of this batch's failed files runs with: the same
+    // filters, report and parallelism, without the size budget, which was for
+    // the whole batch, or the unsupported files, which were handled already.
+    fn retry_template(&self) -> BatchTask {
+        BatchTask {
+            filters: self.filters,
+            report_dir: self.report_dir.clone(),
+            parallel: self.parallel,
+            ..BatchTask::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ParallelBatch {
+    workers: usize,
+    // Bytes the files being compressed may take between them, estimated from
+    // their dimensions. None leaves it to the number of workers.
+    memory_budget: Option<u64>,
+}
+
+// Per-file conditions under which a batch leaves an output alone.
+#[derive(Clone, Copy, Default)]
+struct BatchFilters {
+    // Outputs that shrink by less than this percentage are not written.
+    // `None` always writes, which is what queued jobs use.
+    min_savings_percent: Option<f32>,
+    existing_outputs: ExistingOutputs,
+    // Inputs of at most this many bytes keep their dimensions; larger ones are resized.
+    resize_min_input_size: Option<u64>,
+}
+
+impl BatchFilters {
+    // Drops the resize from tasks whose input is under the size threshold.
+    fn apply_resize_threshold(&self, task: &mut CompressionTask, input_size: u64) {
+        if self.resize_min_input_size.is_some_and(|min| input_size <= min) {
+            task.resize = None;
+        }
+    }
+
+    // Points the task at a free name when its output exists and the policy says
+    // to rename.
+    fn apply_rename(&self, task: &mut CompressionTask) {
+        if self.existing_outputs == ExistingOutputs::Rename {
+            task.output_path = free_output_path(&task.output_path);
+        }
+    }
+}
+
+// What happens when an output file is already there. The GUI's batches and
+// the command line use the same policies.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum ExistingOutputs {
+    #[default]
+    Overwrite,
+    // Skip whenever the output file is already there.
+    Skip,
+    // Skip only when the output is at least as new as its source.
+    SkipIfNewer,
+    // Write photo_1.jpg, photo_2.jpg and so on beside it instead.
+    Rename,
+}
+
+// What a batch does with files in the input folder that aren't supported images,
+// e.g. PDFs or text files kept alongside the photos.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum UnsupportedFiles {
+    #[default]
+    Ignore,
+    // Skip them, with a line in the log for each.
+    Log,
+    // Copy them into the output folder as they are.
+    Copy,
+}
+
+// Order in which a batch works through the input folder.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum BatchOrder {
+    #[default]
+    Name,
+    // Smallest first.
+    Size,
+    // Oldest first, by modification time.
+    Modified,
+}
+
+impl BatchOrder {
+    const ALL: [BatchOrder; 3] = [BatchOrder::Name, BatchOrder::Size, BatchOrder::Modified];
+
+    fn label(self) -> &'static str {
+        match self {
+            BatchOrder::Name => "Name",
+            BatchOrder::Size => "Size",
+            BatchOrder::Modified => "Date modified",
+        }
+    }
+
+    // `inputs` come from `collect_batch_inputs`, already in name order.
+    fn sort(self, inputs: &mut [PathBuf]) {
+        // Files whose metadata can't be read go last, still in name order.
+        match self {
+            BatchOrder::Name => {}
+            BatchOrder::Size => inputs.sort_by_cached_key(|path| {
+                let size = std::fs::metadata(path).map(|meta| meta.len()).ok();
+                (size.is_none(), size)
+            }),
+            BatchOrder::Modified => inputs.sort_by_cached_key(|path| {
+                let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+                (modified.is_none(), modified)
+            }),
+        }
+    }
+}
+
+enum BatchOutcome {
+    Written { input_size: u64, output_size: u64, blurhash: Option<String> },
+    InsufficientGain { input_size: u64, output_size: u64 },
+    AlreadyExists { input_size: u64 },
+}
+
+enum Job {
+    Single(CompressionTask),
+    Batch(BatchTask),
+    // Encode in memory and hand the result back as a `data:` URI; nothing is written.
+    DataUri(CompressionTask),
+    // Work through the shared queue until it is empty.
+    Queue,
+    // Write each RGBA channel as its own grayscale PNG next to the output path.
+    SplitChannels(CompressionTask),
+    // Look at the input's content and suggest a starting quality.
+    Analyze { path: PathBuf, page: usize },
+    BuildAnimation(AnimationTask),
+    ContactSheet(ContactSheetTask),
+    // Screenshot the screen and compress it with `task`'s settings and output.
+    CaptureCompress(CompressionTask),
+    // Decode and process once, then write one output per quality.
+    QualityLadder { task: CompressionTask, qualities: Vec<u8> },
+    // Decode the input and shrink it for the preview pane.
+    Preview { path: PathBuf, page: usize, timeout_secs: u32 },
+    // Decode the reference and score it against the input.
+    Reference(ReferenceKey),
+}
+
+// Queued jobs, shared with the worker so they can still be reordered while it runs.
+// The worker always takes the front entry next.
+type SharedQueue = Arc<Mutex<Vec<CompressionTask>>>;
+
+// Lets the UI hold a running batch between files, e.g. to free up a shared
+// machine for a while. The worker blocks on `resumed` while `paused` is set.
+#[derive(Default)]
+struct PauseControl {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl PauseControl {
+    fn set(&self, paused: bool) {
+        *self.paused.lock().unwrap_or_else(PoisonError::into_inner) = paused;
+        self.resumed.notify_all();
+    }
+
+    fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Blocks while paused and returns for how long, so the ETA can leave it out.
+    fn wait(&self) -> Duration {
+        let started = Instant::now();
+        let paused = self.paused.lock().unwrap_or_else(PoisonError::into_inner);
+        let _resumed = self.resumed.wait_while(paused, |paused| *paused).unwrap_or_else(PoisonError::into_inner);
+        started.elapsed()
+    }
+}
+
+fn lock_queue(queue: &SharedQueue) -> MutexGuard<'_, Vec<CompressionTask>> {
+    // A panic elsewhere can't leave a Vec half-updated, so a poisoned lock is still usable.
+    queue.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Messages sent from the worker thread back to the UI.
+enum WorkerEvent {
+    // `elapsed` is the time spent on the batch so far, used for the ETA.
+    Progress { done: usize, total: usize, elapsed: Duration },
+    Log(String),
+    // A batch file that failed, kept so it can be retried.
+    Failed(Box<CompressionTask>),
+    // The step a single-file job has reached.
+    Phase(Phase),
+    // Input and output bytes of a finished job, summed over a batch's written files.
+    Sizes { original: u64, compressed: u64 },
+    // A single file's BlurHash, copied to the clipboard once the job succeeds.
+    BlurHash(String),
+    Finished(Result<String, String>),
+    DataUri(Result<String, String>),
+    SuggestedQuality(Result<u8, String>),
+    // `path` and `page` say which input it is, as another may have been picked since.
+    Preview { path: PathBuf, page: usize, result: Result<PreviewImage, String> },
+    Reference { key: ReferenceKey, result: Result<ReferenceImage, String> },
+}
+
+// Steps of a single-file job, reported so a large image doesn't look stuck.
+#[derive(Clone, Copy)]
+enum Phase {
+    Decoding,
+    Processing,
+    // `written` is the encoded size so far, for encoders that write as they go.
+    Encoding { written: usize },
+    Writing,
+    Uploading,
+}
+
+impl Phase {
+    // Rough share of the job done once this phase starts.
+    fn fraction(self) -> f32 {
+        match self {
+            Phase::Decoding => 0.0,
+            Phase::Processing => 0.3,
+            Phase::Encoding { .. } => 0.5,
+            Phase::Writing => 0.9,
+            Phase::Uploading => 0.95,
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Phase::Decoding => "Decoding…".to_string(),
+            Phase::Processing => "Processing…".to_string(),
+            Phase::Encoding { written: 0 } => "Encoding…".to_string(),
+            Phase::Encoding { written } => format!("Encoding… {} written", format_size(written as u64)),
+            Phase::Writing => "Writing…".to_string(),
+            Phase::Uploading => "Uploading…".to_string(),
+        }
+    }
+}
+
+// Time spent in each phase of one file, fed from the same reports as the
+// progress display, for the per-file timing lines in the log.
+struct PhaseTimer {
+    // The phase running now, as an index into `totals`, and when it started.
+    current: Cell<Option<(usize, Instant)>>,
+    totals: Cell<[Option<Duration>; 5]>,
+}
+
+impl PhaseTimer {
+    const NAMES: [&str; 5] = ["decode", "transform", "encode", "write", "upload"];
+
+    fn new() -> Self {
+        Self { current: Cell::new(None), totals: Cell::new([None; 5]) }
+    }
+
+    fn mark(&self, phase: Phase) {
+        let index = match phase {
+            Phase::Decoding => 0,
+            Phase::Processing => 1,
+            // Reported again as output accumulates; only the first one starts the phase.
+            Phase::Encoding { .. } => 2,
+            Phase::Writing => 3,
+            Phase::Uploading => 4,
+        };
+        if self.current.get().is_some_and(|(current, _)| current == index) {
+            return;
+        }
+        self.stop();
+        self.current.set(Some((index, Instant::now())));
+    }
+
+    fn stop(&self) {
+        if let Some((index, started)) = self.current.take() {
+            let mut totals = self.totals.get();
+            totals[index] = Some(totals[index].unwrap_or_default() + started.elapsed());
+            self.totals.set(totals);
+        }
+    }
+
+    // e.g. "decode 412 ms, transform 38 ms, encode 1.20 s, write 6 ms"; None
+    // when nothing was timed, as for a skipped file.
+    fn summary(&self) -> Option<String> {
+        self.stop();
+        let parts: Vec<String> = Self::NAMES
+            .iter()
+            .zip(self.totals.get())
+            .filter_map(|(name, total)| {
+                let total = total?;
+                Some(match total.as_millis() {
+                    ms @ 0..1000 => format!("{name} {ms} ms"),
+                    _ => format!("{name} {:.2} s", total.as_secs_f64()),
+                })
+            })
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+// Collects encoder output, reporting the size every `PROGRESS_CHUNK` bytes.
+struct ProgressWriter<'a> {
+    buf: Vec<u8>,
+    report: &'a dyn Fn(Phase),
+    reported: usize,
+}
+
+const PROGRESS_CHUNK: usize = 256 * 1024;
+
+impl std::io::Write for ProgressWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() - self.reported >= PROGRESS_CHUNK {
+            self.reported = self.buf.len();
+            (self.report)(Phase::Encoding { written: self.buf.len() });
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// A decoded input together with the metadata needed by later processing steps.
+#[derive(Clone)]
+struct DecodedInput {
+    image: DynamicImage,
+    icc_profile: Option<Vec<u8>>,
+    // Estimated quality the input was saved at, for JPEG inputs.
+    source_quality: Option<u8>,
+    // Why the input looks cut short, when only part of it decoded.
+    truncated: Option<&'static str>,
+}
+
+// Result of encoding a task into memory, before anything is written to disk.
+struct EncodedImage {
+    bytes: Vec<u8>,
+    format: OutputFormat,
+    notes: Vec<String>,
+    // Smaller copy in the same format, when the task asks for one.
+    thumbnail: Option<Vec<u8>>,
+    blurhash: Option<String>,
+    deskew_angle: Option<f32>,
+}
+
+// What `encode_task_with_progress` can write, picked from the output file's extension.
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Jpeg,
+    Png,
+    WebP,
+    // Lossless, and much faster to encode and decode than PNG.
+    Qoi,
+    // Needs the `jpegxl` feature; quality maps to the encoder's distance.
+    Jxl,
+    // Uncompressed: binary PPM (RGB), PAM (RGBA), or headerless RGBA bytes.
+    Ppm,
+    Pam,
+    Raw,
+}
+
+impl OutputFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "png" => Some(Self::Png),
+            "webp" => Some(Self::WebP),
+            "qoi" => Some(Self::Qoi),
+            "jxl" => Some(Self::Jxl),
+            "ppm" => Some(Self::Ppm),
+            "pam" => Some(Self::Pam),
+            "raw" => Some(Self::Raw),
+            _ => None,
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::WebP => "image/webp",
+            Self::Qoi => "image/qoi",
+            Self::Jxl => "image/jxl",
+            Self::Ppm => "image/x-portable-pixmap",
+            Self::Pam => "image/x-portable-arbitrarymap",
+            Self::Raw => "application/octet-stream",
+        }
+    }
+
+    // PNG has its own compression level instead, QOI has no settings, and the
+    // uncompressed formats ignore quality entirely.
+    fn uses_quality(self) -> bool {
+        !matches!(self, Self::Png | Self::Qoi | Self::Ppm | Self::Pam | Self::Raw)
+    }
+}
+
+// Whether changing `task`'s quality changes its output. Lossless and
+// near-lossless WebP ignore it too.
+fn task_uses_quality(task: &CompressionTask) -> bool {
+    match OutputFormat::from_path(&task.output_path) {
+        Some(OutputFormat::WebP) => task.webp_mode == WebpMode::Lossy,
+        format => format.is_some_and(OutputFormat::uses_quality),
+    }
+}
+
+// One target format of a combined export.
+struct FormatExport {
+    format: ImageFormat,
+    enabled: bool,
+    quality: u8,
+    // Deflate level, used in place of the quality by PNG.
+    png_level: u8,
+}
+
+// Thumbnail of one file in the batch folder, already honoring EXIF orientation.
+struct BatchThumbnail {
+    path: PathBuf,
+    image: DynamicImage,
+    texture: egui::TextureHandle,
+}
+
+// What dragging over the preview does.
+#[derive(Clone, Copy, PartialEq)]
+enum PreviewTool {
+    View,
+    Crop,
+    Protect,
+}
+
+// The images in a ZIP, each with whether it is ticked for compressing.
+struct ZipSelection {
+    path: PathBuf,
+    entries: Vec<(zip_archive::Entry, bool)>,
+}
+
+// Clipboard content saved before copying over it.
+enum ClipboardBackup {
+    Text(String),
+    Image(arboard::ImageData<'static>),
+}
+
+// Downscaled copy of the selected input, uploaded as a texture for display.
+struct Preview {
+    texture: egui::TextureHandle,
+    // Unadjusted thumbnail, kept so tone changes can be re-applied to it.
+    thumbnail: DynamicImage,
+    source_size: [u32; 2],
+}
+
+// What the worker hands back for the preview; the texture is made from it on the UI thread.
+struct PreviewImage {
+    thumbnail: DynamicImage,
+    source_size: [u32; 2],
+    source_quality: Option<u8>,
+    suggestion: (format_advice::Suggestion, &'static str),
+    truncated: Option<&'static str>,
+    notes: Vec<String>,
+    // Read along with the image, since both mean going through the whole file.
+    page_count: usize,
+    metadata: Result<image_metadata::ImageMetadata, String>,
+}
+
+// A job held back until the user agrees to replace the outputs in `existing`.
+struct PendingOverwrite {
+    job: Job,
+    existing: Vec<PathBuf>,
+    keep_original: bool,
+}
+
+// A preview the worker is still decoding.
+struct PendingPreview {
+    path: PathBuf,
+    page: usize,
+    // Status shown once it arrives, e.g. "Input reloaded".
+    message: &'static str,
+}
+
+// A second image judged against the original input, e.g. another encoder's output.
+#[derive(Clone)]
+struct Reference {
+    texture: egui::TextureHandle,
+    metrics: Result<metrics::Metrics, String>,
+    // Whether the reference had to be scaled to the original's size first.
+    resized: bool,
+}
+
+// What the worker hands back for the reference; the texture is made on the UI thread.
+struct ReferenceImage {
+    thumbnail: DynamicImage,
+    metrics: Result<metrics::Metrics, String>,
+    resized: bool,
+}
+
+// Which comparison a reference result belongs to. The modification times make
+// an edited input or reference count as a different one.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ReferenceKey {
+    input: Option<PathBuf>,
+    page: usize,
+    reference: PathBuf,
+    modified: [Option<SystemTime>; 2],
+}
+
+struct ImageCompressorApp {
+    input_path: Option<PathBuf>,
+    output_path: Option<PathBuf>,
+    quality: u8,
+    status_message: String,
+    compress_tx: Sender<Job>,
+    result_rx: Receiver<WorkerEvent>,
+    is_compressing: bool,
+    batch_mode: bool,
+    batch_input_dir: Option<PathBuf>,
+    // The PDF or ZIP whose images were extracted into `batch_input_dir`, if any.
+    batch_archive: Option<PathBuf>,
+    // A ZIP opened for input, while its images are being picked.
+    zip_selection: Option<ZipSelection>,
+    batch_output_dir: Option<PathBuf>,
+    batch_format: ImageFormat,
+    // Write each output in its input's format, where that format can be
+    // written; batch inputs that can't fall back to `batch_format`.
+    match_input_format: bool,
+    // Batch output file names, with `{name}` standing for the input's.
+    name_template: String,
+    min_savings_percent: f32,
+    existing_outputs: ExistingOutputs,
+    unsupported_files: UnsupportedFiles,
+    // Only resize batch inputs larger than `resize_min_input_kb`.
+    resize_threshold_enabled: bool,
+    resize_min_input_kb: u32,
+    batch_order: BatchOrder,
+    size_budget_enabled: bool,
+    size_budget_mb: f32,
+    // Compress batch files on several threads, holding the estimated memory of
+    // the files in progress under `memory_limit_mb`.
+    parallel_batch: bool,
+    parallel_workers: usize,
+    memory_limit_enabled: bool,
+    memory_limit_mb: u32,
+    // Write compression_report.csv/.json to the output folder after a batch.
+    write_report: bool,
+    // Time each frame is shown when the input folder is built into an animation.
+    animation_delay_ms: u32,
+    contact_sheet_columns: u32,
+    contact_sheet_thumb_size: u32,
+    taskbar_progress: taskbar::TaskbarProgress,
+    batch_progress: Option<(usize, usize)>,
+    // Step reached by a running single-file job.
+    single_phase: Option<Phase>,
+    // Sizes reported by the running job, and by the last one that succeeded,
+    // for the ratio gauge.
+    job_sizes: Option<(u64, u64)>,
+    last_sizes: Option<(u64, u64)>,
+    job_blurhash: Option<String>,
+    batch_eta: Option<Duration>,
+    batch_log: Vec<String>,
+    // Tasks that failed in the last batch or queue run.
+    failed_tasks: Vec<CompressionTask>,
+    // How to retry them, from the batch they failed in; None after a queue run.
+    failed_batch: Option<BatchTask>,
+    batch_thumbnails: Vec<BatchThumbnail>,
+    // Thumbnails are decoded on a helper thread and arrive here as they finish.
+    thumbnail_rx: Option<Receiver<(PathBuf, DynamicImage)>>,
+    // Per-file rotation chosen from the batch thumbnails.
+    rotation_overrides: HashMap<PathBuf, Rotation>,
+    convert_to_srgb: bool,
+    aspect_crop_enabled: bool,
+    aspect_crop_width: u32,
+    aspect_crop_height: u32,
+    aspect_crop_smart: bool,
+    resize_enabled: bool,
+    resize_width: u32,
+    resize_height: u32,
+    // Links the width and height fields through the source aspect ratio.
+    aspect_locked: bool,
+    resize_filter: ResizeFilter,
+    // Warn when a resize would leave a side shorter than this, e.g. from a
+    // mistyped dimension, and optionally refuse to compress.
+    min_resize_side: u32,
+    block_tiny_resize: bool,
+    thumbnail_enabled: bool,
+    thumbnail_size: u32,
+    blurhash: bool,
+    jpeg_restart_interval: u16,
+    decode_timeout_secs: u32,
+    salvage_truncated: bool,
+    log_timing: bool,
+    post_command: String,
+    temp_dir: Option<PathBuf>,
+    keep_original: bool,
+    // Jobs added with "Add to queue", run in order by "Run queue".
+    queue: SharedQueue,
+    // Queued jobs picked with click, ctrl-click and shift-click, by `queue_id`,
+    // since indexes shift as the queue runs. Shift-click selects the range from
+    // `queue_anchor`, the last job clicked.
+    queue_selection: HashSet<u64>,
+    queue_anchor: Option<u64>,
+    // The `queue_id` the next queued or loaded job gets.
+    next_queue_id: u64,
+    // Quality "Set quality" gives the selected jobs.
+    queue_bulk_quality: u8,
+    // Holds batches and the queue between files.
+    pause: Arc<PauseControl>,
+    preview: Option<Preview>,
+    preview_pending: Option<PendingPreview>,
+    // Output format suggested for the current input, with the reason.
+    format_suggestion: Option<(format_advice::Suggestion, &'static str)>,
+    // On-disk size of the selected input, for the size readout.
+    input_size: Option<u64>,
+    // Estimated quality of the selected JPEG input, for the over-quality warning.
+    source_quality: Option<u8>,
+    // Why the selected input looks cut short, for the warning under it.
+    input_truncated: Option<&'static str>,
+    // Headers and EXIF tags of the selected input, for the Metadata panel.
+    input_metadata: Option<Result<image_metadata::ImageMetadata, String>>,
+    size_estimator: size_estimate::SizeEstimator,
+    // Quality pinned for comparison, encoded alongside the current settings.
+    pinned_quality: Option<u8>,
+    pinned_estimator: size_estimate::SizeEstimator,
+    // Decoded thumbnails of the pinned and current encodes.
+    comparison_textures: [Option<egui::TextureHandle>; 2],
+    memory: memory_usage::MemoryMonitor,
+    reference_path: Option<PathBuf>,
+    reference: Option<Reference>,
+    // The comparison the worker is still working on.
+    reference_pending: Option<ReferenceKey>,
+    // Every comparison made so far, so going back to an input or page doesn't redo it.
+    reference_cache: HashMap<ReferenceKey, Reference>,
+    // Pages in the selected input; more than one only for multi-page TIFFs and
+    // animated GIFs and WebPs, where they are frames.
+    page_count: usize,
+    page: usize,
+    sprite_sheet: bool,
+    sprite_columns: u32,
+    webp_mode: WebpMode,
+    webp_near_lossless: u8,
+    background: [u8; 3],
+    flatten: bool,
+    defringe: bool,
+    deskew: bool,
+    auto_levels: bool,
+    brightness: i32,
+    contrast: f32,
+    posterize_enabled: bool,
+    posterize_levels: u8,
+    png_depth: PngDepth,
+    png_level: u8,
+    upload_enabled: bool,
+    upload_url: String,
+    upload_method: upload::UploadMethod,
+    upload_authorization: String,
+    note: String,
+    metadata: output_metadata::OutputMetadata,
+    // Suggest a quality from the content whenever an input is loaded.
+    auto_quality: bool,
+    analyzing: bool,
+    watermark_enabled: bool,
+    watermark: watermark::Watermark,
+    preview_tool: PreviewTool,
+    // A screen capture in progress, delivering the saved screenshot.
+    capture_rx: Option<Receiver<Result<PathBuf, String>>>,
+    // Global shortcut that captures the screen and compresses it into
+    // `hotkey_folder`, cropped to `hotkey_region` when set.
+    hotkey_enabled: bool,
+    hotkey_shortcut: String,
+    hotkey_folder: Option<PathBuf>,
+    hotkey_format: ImageFormat,
+    hotkey_region: Option<CropRect>,
+    // Made on first use; the shortcut last registered with it (or tried) and
+    // why that failed.
+    global_shortcut: Option<hotkey::GlobalShortcut>,
+    hotkey_registered: Option<String>,
+    hotkey_error: Option<String>,
+    crop: Option<CropRect>,
+    // Area kept at `protect_quality` while the rest uses the main quality.
+    protect_region: Option<CropRect>,
+    protect_quality: u8,
+    // Source-space point where the current selection drag started.
+    drag_start: Option<[f32; 2]>,
+    // `None` fits the preview to the pane; otherwise physical pixels per texture pixel.
+    preview_zoom: Option<f32>,
+    // Kept alive for the whole session; on Linux the copied data is lost once it drops.
+    clipboard: Option<arboard::Clipboard>,
+    // What the clipboard held before the app last replaced it, for "Restore clipboard".
+    clipboard_backup: Option<ClipboardBackup>,
+    // The text the app itself last copied, so copying twice keeps the original backup.
+    clipboard_copied: Option<String>,
+    // A single-file job or export waiting for the user to confirm overwriting its outputs.
+    pending_overwrite: Option<PendingOverwrite>,
+    skip_overwrite_confirm: bool,
+    // Single mode: write the image to several formats at once, each at its own quality.
+    combined_export: bool,
+    export_formats: Vec<FormatExport>,
+    // Single mode: write the image once per quality in `ladder_qualities`, a
+    // list such as "40, 60, 80".
+    quality_ladder: bool,
+    ladder_qualities: String,
+    // Settings last used for each batch input folder.
+    folder_profiles: folder_profiles::FolderProfiles,
+    decode_cache: decode_cache::DecodeCache,
+}
+
+impl Default for ImageCompressorApp {
+    fn default() -> Self {
+        let (compress_tx, compress_rx) = channel::<Job>();
+        let (result_tx, result_rx) = channel::<WorkerEvent>();
+        let queue = SharedQueue::default();
+        let worker_queue = Arc::clone(&queue);
+        let pause = Arc::new(PauseControl::default());
+        let worker_pause = Arc::clone(&pause);
+        let decode_cache = decode_cache::DecodeCache::default();
+        let worker_cache = decode_cache.clone();
+
+        // Spawn worker thread for compression
+        thread::spawn(move || {
+            while let Ok(job) = compress_rx.recv() {
+                let event = match job {
+                    Job::Single(task) => {
+                        WorkerEvent::Finished(perform_compression(task, &result_tx, Some(&worker_cache)))
+                    }
+                    Job::Batch(batch) => {
+                        let result = match batch.size_budget {
+                            Some(budget) => perform_budget_batch(batch, budget, &worker_pause, &result_tx),
+                            None => perform_batch(batch, &worker_pause, &result_tx),
+                        };
+                        WorkerEvent::Finished(result)
+                    }
+                    Job::DataUri(task) => WorkerEvent::DataUri(encode_data_uri(&task, &worker_cache)),
+                    Job::Queue => WorkerEvent::Finished(perform_queue(&worker_queue, &worker_pause, &result_tx)),
+                    Job::SplitChannels(task) => {
+                        WorkerEvent::Finished(perform_split_channels(&task, &worker_cache))
+                    }
+                    Job::Analyze { path, page } => {
+                        let suggestion = worker_cache
+                            .decode(&path, page, &mut Vec::new())
+                            .map(|decoded| suggest_quality(&decoded.image));
+                        WorkerEvent::SuggestedQuality(suggestion)
+                    }
+                    Job::BuildAnimation(task) => {
+                        WorkerEvent::Finished(perform_build_animation(&task, &result_tx))
+                    }
+                    Job::ContactSheet(task) => {
+                        WorkerEvent::Finished(perform_contact_sheet(&task, &result_tx))
+                    }
+                    Job::CaptureCompress(task) => WorkerEvent::Finished(capture::capture_screen().and_then(|path| {
+                        perform_compression(CompressionTask { input_path: path, ..task }, &result_tx, None)
+                    })),
+                    Job::QualityLadder { task, qualities } => {
+                        WorkerEvent::Finished(perform_quality_ladder(&task, &qualities, &worker_cache, &result_tx))
+                    }
+                    Job::Preview { path, page, timeout_secs } => {
+                        let result = decode_preview(&path, page, timeout_secs, &worker_cache);
+                        WorkerEvent::Preview { path, page, result }
+                    }
+                    Job::Reference(key) => {
+                        let result = compare_reference(&key, &worker_cache);
+                        WorkerEvent::Reference { key, result }
+                    }
+                };
+                let _ = result_tx.send(event);
+            }
+        });
+
+        Self {
+            input_path: None,
+            output_path: None,
+            quality: 80,
+            status_message: "Ready".to_string(),
+            compress_tx,
+            result_rx,
+            is_compressing: false,
+            batch_mode: false,
+            batch_input_dir: None,
+            batch_archive: None,
+            zip_selection: None,
+            batch_output_dir: None,
+            batch_format: ImageFormat::Jpeg,
+            match_input_format: false,
+            name_template: "{name}".to_string(),
+            min_savings_percent: 0.0,
+            resize_threshold_enabled: false,
+            resize_min_input_kb: 500,
+            existing_outputs: ExistingOutputs::Overwrite,
+            unsupported_files: UnsupportedFiles::Ignore,
+            batch_order: BatchOrder::Name,
+            size_budget_enabled: false,
+            parallel_batch: false,
+            parallel_workers: thread::available_parallelism().map_or(2, |threads| threads.get()),
+            memory_limit_enabled: true,
+            memory_limit_mb: 1024,
+            size_budget_mb: 10.0,
+            write_report: false,
+            animation_delay_ms: 100,
+            contact_sheet_columns: 6,
+            contact_sheet_thumb_size: 200,
+            taskbar_progress: taskbar::TaskbarProgress::default(),
+            batch_progress: None,
+            single_phase: None,
+            job_sizes: None,
+            last_sizes: None,
+            job_blurhash: None,
+            batch_eta: None,
+            batch_log: Vec::new(),
+            failed_tasks: Vec::new(),
+            failed_batch: None,
+            batch_thumbnails: Vec::new(),
+            thumbnail_rx: None,
+            rotation_overrides: HashMap::new(),
+            convert_to_srgb: false,
+            aspect_crop_enabled: false,
+            aspect_crop_width: 1,
+            aspect_crop_height: 1,
+            aspect_crop_smart: false,
+            resize_enabled: false,
+            resize_width: 1920,
+            resize_height: 1080,
+            aspect_locked: true,
+            resize_filter: ResizeFilter::Lanczos3,
+            min_resize_side: 16,
+            block_tiny_resize: false,
+            thumbnail_enabled: false,
+            thumbnail_size: 300,
+            blurhash: false,
+            jpeg_restart_interval: 0,
+            decode_timeout_secs: 120,
+            salvage_truncated: false,
+            log_timing: false,
+            post_command: String::new(),
+            temp_dir: None,
+            keep_original: false,
+            queue,
+            queue_selection: HashSet::new(),
+            queue_anchor: None,
+            next_queue_id: 1,
+            queue_bulk_quality: 80,
+            pause,
+            preview: None,
+            preview_pending: None,
+            format_suggestion: None,
+            input_size: None,
+            source_quality: None,
+            input_truncated: None,
+            input_metadata: None,
+            size_estimator: size_estimate::SizeEstimator::new(decode_cache.clone()),
+            pinned_quality: None,
+            pinned_estimator: size_estimate::SizeEstimator::new(decode_cache.clone()),
+            comparison_textures: [None, None],
+            memory: memory_usage::MemoryMonitor::new(),
+            reference_path: None,
+            reference: None,
+            reference_pending: None,
+            reference_cache: HashMap::new(),
+            page_count: 1,
+            page: 0,
+            sprite_sheet: false,
+            sprite_columns: 8,
+            webp_mode: WebpMode::Lossless,
+            webp_near_lossless: 60,
+            background: [255, 255, 255],
+            flatten: false,
+            defringe: true,
+            brightness: 0,
+            contrast: 0.0,
+            deskew: false,
+            auto_levels: false,
+            posterize_enabled: false,
+            posterize_levels: 8,
+            png_depth: PngDepth::Auto,
+            png_level: 6,
+            upload_enabled: false,
+            upload_url: String::new(),
+            upload_method: upload::UploadMethod::Put,
+            upload_authorization: String::new(),
+            note: String::new(),
+            metadata: output_metadata::OutputMetadata::default(),
+            auto_quality: false,
+            analyzing: false,
+            watermark_enabled: false,
+            watermark: watermark::Watermark::default(),
+            preview_tool: PreviewTool::View,
+            capture_rx: None,
+            hotkey_enabled: false,
+            hotkey_shortcut: "Ctrl+Shift+S".to_string(),
+            hotkey_folder: None,
+            hotkey_format: ImageFormat::WebP,
+            hotkey_region: None,
+            global_shortcut: None,
+            hotkey_registered: None,
+            hotkey_error: None,
+            crop: None,
+            protect_region: None,
+            protect_quality: 95,
+            drag_start: None,
+            preview_zoom: None,
+            clipboard: arboard::Clipboard::new().ok(),
+            clipboard_backup: None,
+            clipboard_copied: None,
+            pending_overwrite: None,
+            skip_overwrite_confirm: false,
+            combined_export: false,
+            export_formats: OUTPUT_FORMATS
+                .iter()
+                .map(|&format| FormatExport {
+                    format,
+                    enabled: format != ImageFormat::Png,
+                    quality: 80,
+                    png_level: 6,
+                })
+                .collect(),
+            quality_ladder: false,
+            ladder_qualities: "40, 60, 80".to_string(),
+            folder_profiles: folder_profiles::FolderProfiles::load(),
+            decode_cache,
+        }
+    }
+}
+
+impl eframe::App for ImageCompressorApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Drain worker events without blocking
+        while let Ok(event) = self.result_rx.try_recv() {
+            match event {
+                WorkerEvent::Progress { done, total, elapsed } => {
+                    self.batch_progress = Some((done, total));
+                    // Wait for a few finished files before trusting the average.
+                    self.batch_eta = (done >= ETA_MIN_SAMPLES)
+                        .then(|| elapsed.div_f64(done as f64).mul_f64((total - done) as f64));
+                    self.status_message = format!("Compressing {done}/{total}...");
+                }
+                WorkerEvent::Log(line) => self.batch_log.push(line),
+                WorkerEvent::Failed(task) => self.failed_tasks.push(*task),
+                WorkerEvent::Phase(phase) => self.single_phase = Some(phase),
+                WorkerEvent::Sizes { original, compressed } => self.job_sizes = Some((original, compressed)),
+                WorkerEvent::BlurHash(hash) => self.job_blurhash = Some(hash),
+                WorkerEvent::Finished(result) => {
+                    self.is_compressing = false;
+                    // A pause pressed during the last file would otherwise hold the next job.
+                    self.pause.set(false);
+                    self.batch_progress = None;
+                    self.single_phase = None;
+                    self.batch_eta = None;
+                    self.last_sizes = self.job_sizes.take().filter(|_| result.is_ok());
+                    let blurhash = self.job_blurhash.take();
+                    self.status_message = match result {
+                        Ok(mut msg) => {
+                            if let Some(hash) = blurhash {
+                                let copied = self.copy_to_clipboard(hash, "BlurHash");
+                                if copied.starts_with("Error") {
+                                    msg.push_str(&format!(" ({copied})"));
+                                } else {
+                                    msg.push_str(", copied to clipboard");
+                                }
+                            }
+                            msg
+                        }
+                        Err(err) => err,
+                    };
+                }
+                WorkerEvent::DataUri(result) => {
+                    self.is_compressing = false;
+                    self.status_message = match result {
+                        Ok(uri) => {
+                            let description = format!("data URI ({} KB)", uri.len().div_ceil(1024));
+                            self.copy_to_clipboard(uri, &description)
+                        }
+                        Err(err) => err,
+                    };
+                }
+                WorkerEvent::SuggestedQuality(result) => {
+                    self.analyzing = false;
+                    match result {
+                        Ok(quality) => self.quality = quality,
+                        Err(err) => self.status_message = err,
+                    }
+                }
+                WorkerEvent::Preview { path, page, result } => self.finish_preview(ctx, &path, page, result),
+                WorkerEvent::Reference { key, result } => self.finish_reference(ctx, key, result),
+            }
+        }
+
+        // The same fraction as the progress bars below.
+        let progress = match (self.batch_progress, self.single_phase) {
+            (Some((done, total)), _) => Some(done as f32 / total.max(1) as f32),
+            (None, Some(phase)) => Some(phase.fraction()),
+            (None, None) => None,
+        };
+        self.taskbar_progress.set(frame, progress);
+
+        self.receive_thumbnails(ctx);
+        self.receive_capture(ctx);
+        self.update_hotkey(ctx);
+        self.overwrite_confirm_ui(ctx);
+        self.update_size_estimate();
+        self.status_bar_ui(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading("Image Compressor");
+                ui.add_space(10.0);
+
+                // Settings can't affect a job that is already running, so lock them meanwhile.
+                ui.add_enabled_ui(!self.is_compressing, |ui| self.settings_ui(ui));
+
+                ui.add_space(20.0);
+                ui.separator();
+                ui.add_space(10.0);
+
+                // Compress button
+                if self.batch_mode {
+                    self.batch_compress_ui(ui);
+                } else {
+                    self.single_compress_ui(ui);
+                }
+
+                ui.add_space(10.0);
+
+                // Status
+                ui.horizontal(|ui| {
+                    ui.label("Status:");
+                    let color = if self.status_message.starts_with("Error") {
+                        egui::Color32::RED
+                    } else if self.status_message.starts_with("Success") {
+                        egui::Color32::GREEN
+                    } else {
+                        egui::Color32::GRAY
+                    };
+                    ui.colored_label(color, &self.status_message);
+                });
+                if !self.is_compressing
+                    && let Some((original, compressed)) = self.last_sizes
+                {
+                    ratio_gauge(ui, original, compressed);
+                }
+
+                if let Some((done, total)) = self.batch_progress {
+                    ui.add(egui::ProgressBar::new(done as f32 / total.max(1) as f32).show_percentage());
+                    ui.horizontal(|ui| {
+                        let eta = match self.batch_eta {
+                            Some(remaining) => format!("About {} remaining", format_duration(remaining)),
+                            None => "Estimating time remaining…".to_string(),
+                        };
+                        ui.label(eta);
+                        if self.pause.is_paused() {
+                            if ui.button("Resume").clicked() {
+                                self.pause.set(false);
+                                self.status_message = "Resuming...".to_string();
+                            }
+                        } else if ui
+                            .button("Pause")
+                            .on_hover_text("Stops after the file being compressed now, until resumed.")
+                            .clicked()
+                        {
+                            self.pause.set(true);
+                            self.status_message = "Paused after the current file".to_string();
+                        }
+                    });
+                }
+                if let Some(phase) = self.single_phase {
+                    ui.add(egui::ProgressBar::new(phase.fraction()).text(phase.label()));
+                }
+
+                if !self.batch_mode {
+                    ui.add_space(10.0);
+                    ui.separator();
+                    self.queue_ui(ui);
+                }
+
+                if !self.batch_log.is_empty() {
+                    ui.add_space(10.0);
+                    ui.label(if self.batch_mode { "Batch log:" } else { "Log:" });
+                    egui::ScrollArea::vertical()
+                        .id_salt("batch_log")
+                        .max_height(150.0)
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            for line in &self.batch_log {
+                                ui.label(line);
+                            }
+                        });
+                }
+
+                if !self.failed_tasks.is_empty() {
+                    let label = format!("Retry failed ({})", self.failed_tasks.len());
+                    if ui.add_enabled(!self.is_compressing, egui::Button::new(label)).clicked() {
+                        self.retry_failed();
+                    }
+                }
+            });
+        });
+
+        // Request repaint to check for results
+        if self.is_compressing
+            || self.analyzing
+            || self.preview_pending.is_some()
+            || self.reference_pending.is_some()
+            || self.thumbnail_rx.is_some()
+            || self.size_estimator.is_pending()
+            || self.pinned_estimator.is_pending()
+        {
+            ctx.request_repaint();
+        }
+        if self.is_compressing {
+            ctx.set_cursor_icon(egui::CursorIcon::Progress);
+        }
+    }
+}
+
+impl ImageCompressorApp {
+    // The built-in defaults with config.toml, and its environment overrides, on top.
+    fn new() -> Self {
+        let mut app = Self::default();
+        match config::load() {
+            Ok(config) => app.apply_config(&config),
+            Err(err) => app.status_message = err,
+        }
+        app
+    }
+
+    fn apply_config(&mut self, config: &config::Config) {
+        let mut task = self.build_task(PathBuf::new(), PathBuf::new());
+        config.apply_to_task(&mut task);
+        self.apply_settings(&task);
+        if let Some(template) = &config.name_template {
+            self.name_template = template.clone();
+        }
+        if let Some(extension) = &config.format {
+            match ImageFormat::from_extension(extension) {
+                Some(format) if OUTPUT_FORMATS.contains(&format) => self.batch_format = format,
+                _ => self.status_message = format!("Error in config.toml: unknown format {extension}"),
+            }
+        }
+        if let Some(shortcut) = &config.hotkey {
+            self.hotkey_enabled = true;
+            self.hotkey_shortcut = shortcut.clone();
+        }
+        if let Some(folder) = &config.hotkey_folder {
+            self.hotkey_folder = Some(folder.clone());
+        }
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.batch_mode, false, "Single file");
+            ui.radio_value(&mut self.batch_mode, true, "Folder batch");
+        });
+        ui.add_space(10.0);
+
+        if self.batch_mode {
+            self.batch_selection_ui(ui);
+        } else {
+            self.single_selection_ui(ui);
+        }
+
+        // Quality slider
+        ui.separator();
+        ui.add_space(10.0);
+
+        if self.writes_only_png() {
+            self.png_level_ui(ui);
+        } else {
+            self.quality_ui(ui);
+        }
+
+        ui.add_space(10.0);
+        ui.checkbox(&mut self.convert_to_srgb, "Convert to sRGB")
+            .on_hover_text("Uses the embedded ICC profile, e.g. Adobe RGB or Display P3.");
+
+        ui.horizontal(|ui| {
+            ui.label("Background for transparency:");
+            ui.color_edit_button_srgb(&mut self.background)
+                .on_hover_text("Used by JPEG and PPM, which can't store alpha, and by Flatten.");
+            ui.checkbox(&mut self.flatten, "Flatten all formats")
+                .on_hover_text("Composites transparent images onto this color for every format, giving opaque output.");
+        });
+        ui.checkbox(&mut self.defringe, "Remove alpha fringing")
+            .on_hover_text("Keeps dark halos from appearing around transparent edges when resizing.");
+
+        ui.add_space(10.0);
+        ui.checkbox(&mut self.deskew, "Straighten tilted scans")
+            .on_hover_text("Measures the tilt from text lines and page edges and rotates the image level.");
+        let auto_levels = ui
+            .checkbox(&mut self.auto_levels, "Auto levels")
+            .on_hover_text("Stretches each channel to the full range, brightening faded scans.");
+        let brightness = ui.add(egui::Slider::new(&mut self.brightness, -100..=100).text("Brightness"));
+        let contrast = ui.add(egui::Slider::new(&mut self.contrast, -100.0..=100.0).text("Contrast"));
+        let posterize = ui.horizontal(|ui| {
+            let enabled = ui
+                .checkbox(&mut self.posterize_enabled, "Posterize to")
+                .on_hover_text("Fewer shades per channel: a flat, banded look that also shrinks PNGs.");
+            let levels = ui.add_enabled(
+                self.posterize_enabled,
+                egui::DragValue::new(&mut self.posterize_levels)
+                    .range(2..=64)
+                    .suffix(" levels per channel"),
+            );
+            enabled.changed() || levels.changed()
+        });
+
+        ui.add_space(10.0);
+        let watermark_changed = self.watermark_ui(ui);
+
+        if auto_levels.changed() || brightness.changed() || contrast.changed() || posterize.inner || watermark_changed
+        {
+            self.refresh_preview_texture(ui.ctx());
+        }
+
+        ui.add_space(10.0);
+        ui.label(if self.batch_mode { "Note (shared by every file):" } else { "Note:" });
+        ui.add(
+            egui::TextEdit::multiline(&mut self.note)
+                .desired_rows(2)
+                .hint_text("optional, saved as a .txt next to the output"),
+        );
+        self.metadata_fields_ui(ui);
+
+        if !self.batch_mode {
+            ui.add_space(10.0);
+            self.combined_export_ui(ui);
+            self.quality_ladder_ui(ui);
+            ui.add_space(10.0);
+            self.upload_ui(ui);
+        }
+
+        self.advanced_options_ui(ui);
+
+        ui.add_space(10.0);
+        self.aspect_crop_ui(ui);
+        self.resize_ui(ui);
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.thumbnail_enabled, "Also write a thumbnail, up to")
+                .on_hover_text("Saved as <name>_thumb next to the output, in the same format.");
+            ui.add_enabled(
+                self.thumbnail_enabled,
+                egui::DragValue::new(&mut self.thumbnail_size)
+                    .range(16..=4096)
+                    .suffix(" px"),
+            );
+        });
+        ui.checkbox(&mut self.blurhash, "Compute a BlurHash placeholder").on_hover_text(
+            "A short string web pages can show blurred while the image loads. \
+             Copied to the clipboard, or added to the batch report.",
+        );
+
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            ui.label("Post-process command:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.post_command)
+                    .hint_text("e.g. jpegoptim --strip-all {file}"),
+            )
+            .on_hover_text("Runs after each output is written. {file} is replaced with its path.");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Give up decoding after");
+            ui.add(
+                egui::DragValue::new(&mut self.decode_timeout_secs)
+                    .range(0..=3600)
+                    .suffix(" s"),
+            )
+            .on_hover_text("Stops malformed or enormous inputs from hanging a job. 0 waits forever.");
+        });
+        ui.checkbox(&mut self.salvage_truncated, "Save what decodes of truncated files").on_hover_text(
+            "Files cut short, e.g. by an interrupted download, fail by default. \
+             With this on, the part that arrived is compressed instead.",
+        );
+        ui.checkbox(&mut self.log_timing, "Log timings per file")
+            .on_hover_text("Adds how long each file spent decoding, transforming, encoding and writing to the log.");
+        ui.horizontal(|ui| {
+            ui.label("Temporary files:");
+            match &self.temp_dir {
+                Some(dir) => ui.label(dir.display().to_string()),
+                None => ui.label("next to the output"),
+            }
+            .on_hover_text(
+                "Outputs are written here first, then moved into place. A local folder avoids \
+                 writing twice over a slow network share.",
+            );
+            if ui.button("Browse…").clicked()
+                && let Some(dir) = FileDialog::new().pick_folder()
+            {
+                self.temp_dir = Some(dir);
+            }
+            if self.temp_dir.is_some() && ui.button("Reset").clicked() {
+                self.temp_dir = None;
+            }
+        });
+        ui.checkbox(&mut self.keep_original, "Keep overwritten files as .orig").on_hover_text(
+            "Renames a file about to be overwritten to <name>.orig instead of deleting it. \
+             An existing .orig is left alone, so it always holds the first original.",
+        );
+        if cfg!(feature = "hotkey") {
+            self.hotkey_ui(ui);
+        }
+
+        if self.batch_mode {
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.label("Minimum savings:");
+                ui.add(
+                    egui::DragValue::new(&mut self.min_savings_percent)
+                        .range(0.0..=99.0)
+                        .speed(0.5)
+                        .suffix("%"),
+                );
+            });
+            ui.label("Files that would shrink by less than this are skipped.");
+
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.label("Existing outputs:");
+                ui.radio_value(&mut self.existing_outputs, ExistingOutputs::Overwrite, "Overwrite");
+                ui.radio_value(&mut self.existing_outputs, ExistingOutputs::Skip, "Skip");
+                ui.radio_value(&mut self.existing_outputs, ExistingOutputs::SkipIfNewer, "Skip if newer")
+                    .on_hover_text("Skip only outputs modified after their source file.");
+                ui.radio_value(&mut self.existing_outputs, ExistingOutputs::Rename, "Rename")
+                    .on_hover_text("Write photo_1.jpg and so on next to the existing output.");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Other files:");
+                ui.radio_value(&mut self.unsupported_files, UnsupportedFiles::Ignore, "Ignore");
+                ui.radio_value(&mut self.unsupported_files, UnsupportedFiles::Log, "Log");
+                ui.radio_value(&mut self.unsupported_files, UnsupportedFiles::Copy, "Copy unchanged")
+                    .on_hover_text("Copies files that aren't supported images, such as PDFs, into the output folder.");
+            });
+
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.label("Process files by:");
+                egui::ComboBox::from_id_salt("batch_order")
+                    .selected_text(self.batch_order.label())
+                    .show_ui(ui, |ui| {
+                        for order in BatchOrder::ALL {
+                            ui.selectable_value(&mut self.batch_order, order, order.label());
+                        }
+                    });
+            });
+
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.size_budget_enabled, "Fit total size budget:");
+                ui.add_enabled(
+                    self.size_budget_enabled,
+                    egui::DragValue::new(&mut self.size_budget_mb)
+                        .range(0.1..=100_000.0)
+                        .speed(0.1)
+                        .suffix(" MB"),
+                );
+            });
+            if self.size_budget_enabled {
+                ui.label("Quality is lowered on the largest outputs until the batch fits.");
+            }
+
+            ui.add_space(10.0);
+            ui.add_enabled_ui(!self.size_budget_enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.parallel_batch, "Compress in parallel:")
+                        .on_disabled_hover_text("A size budget compresses files one at a time.");
+                    ui.add_enabled(
+                        self.parallel_batch,
+                        egui::DragValue::new(&mut self.parallel_workers).range(1..=64).suffix(" threads"),
+                    );
+                });
+                if self.parallel_batch {
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.memory_limit_enabled, "Limit memory to:");
+                        ui.add_enabled(
+                            self.memory_limit_enabled,
+                            egui::DragValue::new(&mut self.memory_limit_mb)
+                                .range(64..=1_048_576)
+                                .speed(16)
+                                .suffix(" MB"),
+                        );
+                    })
+                    .response
+                    .on_hover_text("Large images wait for others to finish rather than being decoded all at once.");
+                }
+            });
+
+            ui.add_space(10.0);
+            ui.checkbox(&mut self.write_report, "Write a report to the output folder")
+                .on_hover_text("compression_report.csv and .json: sizes, savings, quality and result per file.");
+        }
+    }
+
+    fn single_selection_ui(&mut self, ui: &mut egui::Ui) {
+        // Input file
+        ui.horizontal(|ui| {
+            ui.label("Input file:");
+            if ui.button("Browse…").clicked()
+                && let Some(path) = FileDialog::new()
+                    .add_filter("Images", INPUT_EXTENSIONS)
+                    .add_filter("ZIP archives", &["zip"])
+                    .pick_file()
+            {
+                // A ZIP holds several images, so it is compressed as a batch.
+                if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) {
+                    self.batch_mode = true;
+                    self.open_zip(path);
+                } else {
+                    self.select_input(path);
+                }
+            }
+            if cfg!(feature = "capture")
+                && ui
+                    .add_enabled(self.capture_rx.is_none(), egui::Button::new("Capture screen"))
+                    .on_hover_text("Screenshot the screen as the input, then crop it to the region you want.")
+                    .clicked()
+            {
+                self.start_capture(ui.ctx());
+            }
+        });
+        if let Some(reason) = self.input_truncated {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("⚠ This file appears truncated ({reason}); only part of it decoded."),
+            );
+            ui.checkbox(&mut self.salvage_truncated, "Save what decoded anyway")
+                .on_hover_text("Compresses the part that arrived. Without this, compressing the file fails.");
+        }
+
+        if self.page_count > 1
+            && let Some(path) = self.input_path.clone()
+        {
+            let animated = is_animation(&path);
+            let unit = if animated { "Frame" } else { "Page" };
+            ui.horizontal(|ui| {
+                ui.label(format!("{unit}:"));
+                let previous = self.page;
+                ui.add_enabled_ui(!(animated && self.sprite_sheet), |ui| {
+                    egui::ComboBox::from_id_salt("page")
+                        .selected_text(format!("{} of {}", self.page + 1, self.page_count))
+                        .show_ui(ui, |ui| {
+                            for page in 0..self.page_count {
+                                ui.selectable_value(&mut self.page, page, format!("{unit} {}", page + 1));
+                            }
+                        });
+                });
+                if self.page != previous {
+                    self.load_preview(&path, "Input file selected");
+                }
+            });
+            if animated {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.sprite_sheet, "Export all frames as a sprite sheet")
+                        .on_hover_text(
+                            "Tiles every frame into one image. Crop and protected regions are ignored.",
+                        );
+                    ui.add_enabled(
+                        self.sprite_sheet,
+                        egui::DragValue::new(&mut self.sprite_columns)
+                            .range(1..=64)
+                            .suffix(" columns"),
+                    );
+                });
+            }
+        }
+
+        ui.label(
+            self.input_path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "No file selected".to_string()),
+        );
+
+        // Round-trip through an external editor: open the file, then reload it
+        // once the changes have been saved.
+        if let Some(path) = self.input_path.clone() {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Edit externally")
+                    .on_hover_text("Open the input in the system's default image editor.")
+                    .clicked()
+                {
+                    self.status_message = match opener::open(&path) {
+                        Ok(()) => "Opened in external editor; press Reload after saving".to_string(),
+                        Err(e) => format!("Error opening external editor: {e}"),
+                    };
+                }
+                if ui
+                    .button("Reload")
+                    .on_hover_text("Re-read the input file from disk.")
+                    .clicked()
+                {
+                    // The file may have been saved within the same mtime tick.
+                    self.decode_cache.clear();
+                    self.reference_cache.clear();
+                    self.load_preview(&path, "Input reloaded");
+                }
+            });
+        }
+        self.metadata_ui(ui);
+        ui.add_space(10.0);
+
+        self.preview_ui(ui);
+
+        // Output file
+        ui.horizontal(|ui| {
+            ui.label("Output file:");
+            if ui.button("Browse…").clicked()
+                && let Some(path) = FileDialog::new()
+                    .add_filter("JPEG", &["jpg", "jpeg"])
+                    .add_filter("PNG", &["png"])
+                    .add_filter("WebP", &["webp"])
+                    .add_filter("QOI", &["qoi"])
+                    .add_filter("JPEG XL", &["jxl"])
+                    .add_filter("PPM / PAM", &["ppm", "pam"])
+                    .add_filter("Raw RGBA", &["raw"])
+                    .save_file()
+            {
+                self.output_path = Some(path);
+                self.status_message = "Output file selected".to_string();
+            }
+        });
+
+        ui.label(
+            self.output_path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "No file selected".to_string()),
+        );
+        if ui
+            .checkbox(&mut self.match_input_format, "Match input format")
+            .on_hover_text("Keeps the input's format, so only the quality and other settings change.")
+            .changed()
+        {
+            self.apply_input_format();
+        }
+        self.format_suggestion_ui(ui);
+        ui.add_space(10.0);
+
+        self.reference_ui(ui);
+    }
+
+    fn format_suggestion_ui(&mut self, ui: &mut egui::Ui) {
+        let (Some((suggestion, reason)), Some(input)) = (self.format_suggestion, &self.input_path) else {
+            return;
+        };
+        let webp_mode = match suggestion {
+            format_advice::Suggestion::WebpLossless => Some(WebpMode::Lossless),
+            format_advice::Suggestion::WebpLossy => Some(WebpMode::Lossy),
+            format_advice::Suggestion::Png => None,
+        };
+        let output_format = self.output_path.as_deref().and_then(OutputFormat::from_path);
+        let applied = output_format == Some(suggestion.format())
+            && webp_mode.is_none_or(|mode| mode == self.webp_mode);
+
+        ui.horizontal(|ui| {
+            ui.weak(format!("Suggested: {} ({reason})", suggestion.label()));
+            if !applied
+                && ui
+                    .small_button("Use it")
+                    .on_hover_text("Changes the output file's extension, next to the input if none is chosen")
+                    .clicked()
+            {
+                let output = output_with_extension(self.output_path.as_deref(), input, suggestion.extension());
+                self.output_path = Some(output);
+                if let Some(mode) = webp_mode {
+                    self.webp_mode = mode;
+                }
+                self.status_message = format!("Output set to {}", suggestion.label());
+            }
+        });
+    }
+
+    // With "Match input format" on, points the output at the input's format.
+    fn apply_input_format(&mut self) {
+        if !self.match_input_format || self.batch_mode {
+            return;
+        }
+        let Some(input) = self.input_path.clone() else {
+            return;
+        };
+        match writable_input_format(&input) {
+            Some(format) => {
+                let output = output_with_extension(self.output_path.as_deref(), &input, format.extensions_str()[0]);
+                self.output_path = Some(output);
+            }
+            None => {
+                self.status_message = "Input format can't be written; choose an output format instead".to_string();
+            }
+        }
+    }
+
+    // Credits written into the output; the Metadata panel shows what the input has.
+    fn metadata_fields_ui(&mut self, ui: &mut egui::Ui) {
+        let title = if self.metadata.is_empty() { "Author and copyright" } else { "Author and copyright (set)" };
+        egui::CollapsingHeader::new(title)
+            .id_salt("output_metadata")
+            .show(ui, |ui| {
+                egui::Grid::new("output_metadata_grid").num_columns(2).show(ui, |ui| {
+                    for (label, value, hint) in [
+                        ("Artist:", &mut self.metadata.artist, "e.g. Jane Doe"),
+                        ("Copyright:", &mut self.metadata.copyright, "e.g. © 2026 Jane Doe"),
+                        ("Description:", &mut self.metadata.description, "what the image shows"),
+                    ] {
+                        ui.label(label);
+                        ui.add(egui::TextEdit::singleline(value).hint_text(hint));
+                        ui.end_row();
+                    }
+                });
+                ui.label("Written as EXIF into JPEG, PNG and WebP output.");
+            });
+    }
+
+    // Read-only listing of what the input file carries, collapsed by default.
+    fn metadata_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(metadata) = &self.input_metadata else {
+            return;
+        };
+        egui::CollapsingHeader::new("Metadata")
+            .id_salt("input_metadata")
+            .show(ui, |ui| {
+                let metadata = match metadata {
+                    Ok(metadata) => metadata,
+                    Err(err) => {
+                        ui.colored_label(egui::Color32::RED, err);
+                        return;
+                    }
+                };
+                egui::Grid::new("metadata_summary").num_columns(2).striped(true).show(ui, |ui| {
+                    for (label, value) in &metadata.summary {
+                        ui.label(label);
+                        ui.label(value);
+                        ui.end_row();
+                    }
+                });
+                if !metadata.exif.is_empty() {
+                    ui.add_space(6.0);
+                    ui.label("EXIF tags:");
+                    egui::ScrollArea::vertical()
+                        .id_salt("metadata_exif")
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("metadata_exif_grid").num_columns(2).striped(true).show(ui, |ui| {
+                                for (tag, value) in &metadata.exif {
+                                    ui.label(tag);
+                                    ui.label(value);
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                }
+            });
+    }
+
+    // Optional reference image, scored against the original with PSNR and SSIM.
+    fn reference_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Reference file:");
+            if ui.button("Browse…").clicked()
+                && let Some(path) = FileDialog::new()
+                    .add_filter("Images", INPUT_EXTENSIONS)
+                    .pick_file()
+            {
+                self.reference_path = Some(path);
+                self.load_reference();
+            }
+            if self.reference_path.is_some() && ui.button("Clear").clicked() {
+                self.reference_path = None;
+                self.reference = None;
+                self.reference_pending = None;
+            }
+        });
+
+        let Some(path) = &self.reference_path else {
+            return;
+        };
+        let Some(reference) = &self.reference else {
+            if self.reference_pending.is_some() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Comparing with the reference…");
+                });
+            }
+            return;
+        };
+        ui.label(path.display().to_string());
+
+        ui.horizontal(|ui| {
+            let size = reference.texture.size_vec2();
+            let scale = (REFERENCE_THUMBNAIL_SIZE as f32 / size.x.max(size.y)).min(1.0);
+            ui.image((reference.texture.id(), size * scale));
+
+            ui.vertical(|ui| match &reference.metrics {
+                Ok(metrics) => {
+                    let psnr = if metrics.psnr.is_finite() {
+                        format!("{:.2} dB", metrics.psnr)
+                    } else {
+                        "∞ (identical)".to_string()
+                    };
+                    ui.label(format!("PSNR: {psnr}"));
+                    ui.label(format!("SSIM: {:.4}", metrics.ssim));
+                    if reference.resized {
+                        ui.label("Reference was scaled to the original's size.");
+                    }
+                }
+                Err(err) => {
+                    ui.colored_label(egui::Color32::RED, err);
+                }
+            });
+        });
+        ui.add_space(10.0);
+    }
+
+    // Scores the reference against the current input, on the worker unless this
+    // pair has been compared already; `finish_reference` picks the result up.
+    fn load_reference(&mut self) {
+        let Some(reference) = self.reference_path.clone() else {
+            return;
+        };
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        let key = ReferenceKey {
+            modified: [self.input_path.as_deref().and_then(modified), modified(&reference)],
+            input: self.input_path.clone(),
+            page: self.page,
+            reference,
+        };
+        if let Some(cached) = self.reference_cache.get(&key) {
+            self.reference = Some(cached.clone());
+            self.reference_pending = None;
+            return;
+        }
+
+        self.reference = None;
+        if self.compress_tx.send(Job::Reference(key.clone())).is_err() {
+            self.status_message = "Error: the worker thread has stopped".to_string();
+            return;
+        }
+        self.reference_pending = Some(key);
+    }
+
+    fn finish_reference(&mut self, ctx: &egui::Context, key: ReferenceKey, result: Result<ReferenceImage, String>) {
+        let pending = self.reference_pending.as_ref() == Some(&key);
+        let reference = match result {
+            Ok(ReferenceImage { thumbnail, metrics, resized }) => {
+                let texture = load_texture(ctx, "reference", &thumbnail);
+                Reference { texture, metrics, resized }
+            }
+            Err(err) => {
+                if pending {
+                    self.reference_pending = None;
+                    self.status_message = err;
+                }
+                return;
+            }
+        };
+        // Kept even when another comparison has been asked for since, as it may be wanted again.
+        self.reference_cache.insert(key, reference.clone());
+        if pending {
+            self.reference_pending = None;
+            self.reference = Some(reference);
+        }
+    }
+
+    fn select_input(&mut self, path: PathBuf) {
+        self.decode_cache.clear();
+        self.page = 0;
+        // Both arrive with the preview.
+        self.page_count = 1;
+        self.input_metadata = None;
+        self.load_preview(&path, "Input file selected");
+        self.input_path = Some(path);
+        self.apply_input_format();
+    }
+
+    fn hotkey_ui(&mut self, ui: &mut egui::Ui) {
+        let title = if self.hotkey_enabled { "Capture hotkey (on)" } else { "Capture hotkey" };
+        egui::CollapsingHeader::new(title).id_salt("capture_hotkey").show(ui, |ui| {
+            ui.checkbox(&mut self.hotkey_enabled, "Capture and compress from anywhere")
+                .on_hover_text("Works while the window is in the background, using the current settings.");
+            ui.horizontal(|ui| {
+                ui.label("Shortcut:");
+                ui.add(egui::TextEdit::singleline(&mut self.hotkey_shortcut).desired_width(140.0))
+                    .on_hover_text("Modifiers first, then one key, e.g. Ctrl+Shift+S or Alt+F9.");
+            });
+            if let Some(err) = &self.hotkey_error {
+                ui.colored_label(egui::Color32::RED, err);
+            }
+            ui.horizontal(|ui| {
+                ui.label("Save to:");
+                match &self.hotkey_folder {
+                    Some(dir) => ui.label(dir.display().to_string()),
+                    None => ui.label("no folder chosen"),
+                };
+                if ui.button("Browse…").clicked()
+                    && let Some(dir) = FileDialog::new().pick_folder()
+                {
+                    self.hotkey_folder = Some(dir);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Format:");
+                egui::ComboBox::from_id_salt("hotkey_format")
+                    .selected_text(format!("{:?}", self.hotkey_format))
+                    .show_ui(ui, |ui| {
+                        for format in OUTPUT_FORMATS {
+                            ui.selectable_value(&mut self.hotkey_format, *format, format!("{format:?}"));
+                        }
+                    });
+            });
+
+            let mut region_enabled = self.hotkey_region.is_some();
+            ui.checkbox(&mut region_enabled, "Only part of the screen");
+            match (region_enabled, &mut self.hotkey_region) {
+                (true, Some(region)) => {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut region.x).prefix("x "));
+                        ui.add(egui::DragValue::new(&mut region.y).prefix("y "));
+                        ui.add(egui::DragValue::new(&mut region.width).range(1..=65535).prefix("w "));
+                        ui.add(egui::DragValue::new(&mut region.height).range(1..=65535).prefix("h "));
+                    });
+                }
+                (true, None) => self.hotkey_region = Some(CropRect { x: 0, y: 0, width: 800, height: 600 }),
+                (false, _) => self.hotkey_region = None,
+            }
+        });
+    }
+
+    // Keeps the registered shortcut in step with the settings, and runs a
+    // capture when it is pressed.
+    fn update_hotkey(&mut self, ctx: &egui::Context) {
+        let wanted = self.hotkey_enabled.then(|| self.hotkey_shortcut.trim().to_string());
+        if wanted != self.hotkey_registered {
+            self.hotkey_registered = wanted.clone();
+            if self.global_shortcut.is_none() && wanted.is_some() {
+                match hotkey::GlobalShortcut::new(ctx) {
+                    Ok(shortcut) => self.global_shortcut = Some(shortcut),
+                    Err(err) => self.hotkey_error = Some(err),
+                }
+            }
+            if let Some(shortcut) = &mut self.global_shortcut {
+                self.hotkey_error = shortcut.set(wanted.as_deref()).err();
+            }
+        }
+
+        if self.global_shortcut.as_ref().is_some_and(|shortcut| shortcut.pressed()) {
+            self.start_hotkey_capture();
+        }
+    }
+
+    fn start_hotkey_capture(&mut self) {
+        let Some(folder) = &self.hotkey_folder else {
+            self.status_message = "Error: choose a folder for hotkey captures first".to_string();
+            return;
+        };
+        if self.is_compressing {
+            self.status_message = "Error: hotkey capture skipped while another job runs".to_string();
+            return;
+        }
+        // Named by time like the screenshot itself, e.g. screenshot-1760400000.webp.
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let extension = self.hotkey_format.extensions_str()[0];
+        let output = free_output_path(&folder.join(format!("screenshot-{seconds}.{extension}")));
+        let task = CompressionTask { crop: self.hotkey_region, ..self.build_task(PathBuf::new(), output) };
+        let _ = self.compress_tx.send(Job::CaptureCompress(task));
+        self.is_compressing = true;
+        self.status_message = "Capturing screen...".to_string();
+    }
+
+    // Minimizes the window so it isn't in the shot, then captures on another thread.
+    fn start_capture(&mut self, ctx: &egui::Context) {
+        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+        let (tx, rx) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            thread::sleep(CAPTURE_DELAY);
+            let _ = tx.send(capture::capture_screen());
+            ctx.request_repaint();
+        });
+        self.capture_rx = Some(rx);
+        self.status_message = "Capturing screen...".to_string();
+    }
+
+    fn receive_capture(&mut self, ctx: &egui::Context) {
+        let Some(result) = self.capture_rx.as_ref().and_then(|rx| rx.try_recv().ok()) else {
+            return;
+        };
+        self.capture_rx = None;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        match result {
+            Ok(path) => {
+                self.select_input(path);
+                if let Some(pending) = &mut self.preview_pending {
+                    self.preview_tool = PreviewTool::Crop;
+                    pending.message = "Screen captured; drag on the preview to crop it to a region";
+                }
+            }
+            Err(err) => self.status_message = err,
+        }
+    }
+
+    // Large inputs take a while to decode, so that happens on the worker;
+    // `finish_preview` picks the result up. `message` is the status shown then.
+    fn load_preview(&mut self, path: &Path, message: &'static str) {
+        self.input_size = std::fs::metadata(path).ok().map(|meta| meta.len());
+        self.crop = None;
+        self.protect_region = None;
+        self.drag_start = None;
+        self.preview = None;
+        self.source_quality = None;
+        self.input_truncated = None;
+        self.format_suggestion = None;
+
+        let (path, page) = (path.to_path_buf(), self.page);
+        let job = Job::Preview { path: path.clone(), page, timeout_secs: self.decode_timeout_secs };
+        if self.compress_tx.send(job).is_err() {
+            self.status_message = "Error: the worker thread has stopped".to_string();
+            return;
+        }
+        self.preview_pending = Some(PendingPreview { path, page, message });
+        self.status_message = "Loading preview...".to_string();
+    }
+
+    fn finish_preview(
+        &mut self,
+        ctx: &egui::Context,
+        path: &Path,
+        page: usize,
+        result: Result<PreviewImage, String>,
+    ) {
+        // Results for an input or page that has since been replaced are dropped.
+        let Some(pending) = self.preview_pending.take_if(|pending| pending.path == path && pending.page == page)
+        else {
+            return;
+        };
+        let decoded = match result {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                self.status_message = err;
+                return;
+            }
+        };
+
+        let PreviewImage { thumbnail, source_size, source_quality, suggestion, truncated, notes, page_count, metadata } =
+            decoded;
+        // A reloaded file may have lost pages; the worker decoded the last one then.
+        self.page_count = page_count;
+        self.page = self.page.min(page_count - 1);
+        self.input_metadata = Some(metadata);
+        let texture = load_texture(ctx, "preview", &thumbnail);
+        self.source_quality = source_quality;
+        self.input_truncated = truncated;
+        self.format_suggestion = Some(suggestion);
+        self.preview = Some(Preview { texture, thumbnail, source_size });
+        self.refresh_preview_texture(ctx);
+        if !self.resize_enabled {
+            [self.resize_width, self.resize_height] = source_size;
+        }
+        self.status_message = pending.message.to_string();
+        // e.g. a CMYK conversion, which is worth knowing about before compressing.
+        for note in notes {
+            self.status_message.push_str(&format!(" ({note})"));
+        }
+        if let Some(source) = self.source_quality {
+            self.status_message.push_str(&format!(" (saved at about quality {source})"));
+        }
+
+        // Scores are relative to the input, so they need redoing for the new one.
+        self.load_reference();
+        if self.auto_quality {
+            self.request_auto_quality();
+        }
+    }
+
+    // Keeps the background size estimates in step with the current single-file settings.
+    fn update_size_estimate(&mut self) {
+        // New encodes need their comparison thumbnails uploading again.
+        if self.pinned_estimator.poll() {
+            self.comparison_textures[0] = None;
+        }
+        if self.size_estimator.poll() {
+            self.comparison_textures[1] = None;
+        }
+        match (&self.input_path, self.batch_mode) {
+            (Some(input), false) => {
+                // Same fallback as "Copy as data URI": JPEG until an output is chosen.
+                let output = self
+                    .output_path
+                    .clone()
+                    .unwrap_or_else(|| input.with_extension("jpg"));
+                // The note doesn't change the size, so typing it shouldn't re-encode,
+                // and the thumbnail and BlurHash aren't part of the estimate.
+                let task = CompressionTask {
+                    note: String::new(),
+                    thumbnail_size: None,
+                    blurhash: false,
+                    ..self.build_single_task(input.clone(), output)
+                };
+                // Remembered estimates arrive here rather than through `poll`.
+                match self.pinned_quality {
+                    Some(quality) => {
+                        let pinned = CompressionTask { quality, ..task.clone() };
+                        if self.pinned_estimator.request(pinned) {
+                            self.comparison_textures[0] = None;
+                        }
+                    }
+                    None => self.pinned_estimator.clear(),
+                }
+                if self.size_estimator.request(task) {
+                    self.comparison_textures[1] = None;
+                }
+            }
+            _ => {
+                self.size_estimator.clear();
+                self.pinned_estimator.clear();
+            }
+        }
+    }
+
+    // Always-visible bar: in single mode the original size against the estimated
+    // output, and the app's memory use in the corner.
+    fn status_bar_ui(&mut self, ctx: &egui::Context) {
+        let memory = self.memory.resident();
+        // Memory climbs while a job runs, so keep sampling even without input.
+        if self.is_compressing {
+            ctx.request_repaint_after(memory_usage::SAMPLE_INTERVAL);
+        }
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if !self.batch_mode
+                    && let Some(original) = self.input_size
+                {
+                    let (estimated, saved) = match self.size_estimator.estimate() {
+                        Some(Ok(estimate)) => (
+                            format_size(estimate.size as u64),
+                            format!("{:.1}%", savings_percent(original, estimate.size as u64)),
+                        ),
+                        Some(Err(_)) => ("unavailable".to_string(), "–".to_string()),
+                        None => ("estimating…".to_string(), "–".to_string()),
+                    };
+                    ui.label(format!(
+                        "Original: {}  •  Estimated: {estimated}  •  Saved: {saved}",
+                        format_size(original)
+                    ));
+                }
+                if let Some(bytes) = memory {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.weak(format!("Memory: {}", format_size(bytes)))
+                            .on_hover_text("Resident memory used by the app");
+                    });
+                }
+            });
+        });
+    }
+
+    fn request_auto_quality(&mut self) {
+        if let Some(path) = &self.input_path {
+            let _ = self.compress_tx.send(Job::Analyze { path: path.clone(), page: self.page });
+            self.analyzing = true;
+        }
+    }
+
+    // Re-applies levels, tone, posterize and watermark settings to the preview thumbnail.
+    fn refresh_preview_texture(&mut self, ctx: &egui::Context) {
+        let Some(preview) = &mut self.preview else {
+            return;
+        };
+        let mut adjusted = preview.thumbnail.clone();
+        if self.auto_levels {
+            adjusted = auto_levels(adjusted);
+        }
+        adjusted = adjust_tone(adjusted, self.brightness, self.contrast);
+        if self.posterize_enabled {
+            adjusted = posterize(adjusted, self.posterize_levels);
+        }
+        if self.watermark_enabled {
+            match watermark::apply(adjusted.clone(), &self.watermark) {
+                Ok(marked) => adjusted = marked,
+                Err(err) => self.status_message = err,
+            }
+        }
+        preview.texture = load_texture(ctx, "preview", &adjusted);
+    }
+
+    // Returns whether a setting changed, so the preview can be redrawn.
+    fn watermark_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui.checkbox(&mut self.watermark_enabled, "Add watermark").changed();
+        if !self.watermark_enabled {
+            return changed;
+        }
+
+        let mark = &mut self.watermark;
+        ui.horizontal(|ui| {
+            if ui.radio(mark.image.is_none(), "Text").clicked() && mark.image.is_some() {
+                mark.image = None;
+                changed = true;
+            }
+            if ui.radio(mark.image.is_some(), "Image…").clicked()
+                && let Some(path) = FileDialog::new()
+                    .add_filter("Images", INPUT_EXTENSIONS)
+                    .pick_file()
+            {
+                mark.image = Some(path);
+                changed = true;
+            }
+        });
+
+        match &mark.image {
+            Some(path) => {
+                ui.label(path.display().to_string());
+            }
+            None => {
+                ui.horizontal(|ui| {
+                    changed |= ui.text_edit_singleline(&mut mark.text).changed();
+                    changed |= ui.color_edit_button_srgb(&mut mark.color).changed();
+                });
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Position:");
+            egui::ComboBox::from_id_salt("watermark_position")
+                .selected_text(mark.position.label())
+                .show_ui(ui, |ui| {
+                    for position in watermark::WatermarkPosition::ALL {
+                        changed |= ui
+                            .selectable_value(&mut mark.position, position, position.label())
+                            .changed();
+                    }
+                });
+        });
+        changed |= ui
+            .add(egui::Slider::new(&mut mark.opacity, 0.0..=1.0).text("Opacity"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut mark.size, 1.0..=50.0).suffix("%").text("Size"))
+            .on_hover_text("Relative to the image's shorter side.")
+            .changed();
+        changed
+    }
+
+    fn preview_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(preview) = &self.preview else {
+            if self.preview_pending.is_some() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Loading preview…");
+                });
+            }
+            return;
+        };
+        let [source_w, source_h] = preview.source_size;
+        let texture_id = preview.texture.id();
+        // Texture pixels per source pixel; below 1 when the source was shrunk to
+        // PREVIEW_MAX_SIZE for the preview.
+        let texture_ratio = preview.texture.size()[0] as f32 / source_w.max(1) as f32;
+
+        ui.horizontal(|ui| {
+            ui.label("Tool:");
+            ui.radio_value(&mut self.preview_tool, PreviewTool::View, "View");
+            ui.radio_value(&mut self.preview_tool, PreviewTool::Crop, "Crop");
+            ui.radio_value(&mut self.preview_tool, PreviewTool::Protect, "Protect region");
+
+            // Fidelity of the background estimate's encode, so it follows the settings.
+            if let Some(Ok(estimate)) = self.size_estimator.estimate()
+                && let Some(ssim) = estimate.ssim
+            {
+                let (label, color) = fidelity_badge(ssim);
+                ui.separator();
+                ui.colored_label(color, format!("● {label}"))
+                    .on_hover_text(format!("SSIM {ssim:.3} against the uncompressed image"));
+            }
+        });
+
+        // Typed coordinates, kept within the source, for crops too exact to drag.
+        match self.crop {
+            Some(mut crop) => {
+                ui.horizontal(|ui| {
+                    ui.label("Crop:");
+                    ui.add(egui::DragValue::new(&mut crop.x).range(0..=source_w - 1).prefix("x "));
+                    ui.add(egui::DragValue::new(&mut crop.y).range(0..=source_h - 1).prefix("y "));
+                    ui.add(egui::DragValue::new(&mut crop.width).range(1..=source_w - crop.x).prefix("w "));
+                    ui.add(egui::DragValue::new(&mut crop.height).range(1..=source_h - crop.y).prefix("h "));
+                    crop.width = crop.width.min(source_w - crop.x);
+                    crop.height = crop.height.min(source_h - crop.y);
+                    self.crop = Some(crop);
+                    if ui.button("Clear crop").clicked() {
+                        self.crop = None;
+                    }
+                });
+            }
+            None => {
+                if ui
+                    .small_button("Crop by coordinates")
+                    .on_hover_text("Starts from the whole image; type the region's position and size.")
+                    .clicked()
+                {
+                    self.crop = Some(CropRect { x: 0, y: 0, width: source_w, height: source_h });
+                }
+            }
+        }
+        if let Some(region) = self.protect_region {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Protected: {}×{} at ({}, {})",
+                    region.width, region.height, region.x, region.y
+                ));
+                if ui.button("Clear region").clicked() {
+                    self.protect_region = None;
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut self.protect_quality, 1..=100)
+                    .text("Protected region quality"),
+            );
+            ui.label("The rest of the image uses the main quality. JPEG output only.");
+        }
+        if self.preview_tool != PreviewTool::View {
+            ui.label("Drag over the image to select a region.");
+        }
+
+        // `scale` is in points per source pixel; zoom levels are expressed in physical
+        // pixels per texture pixel so that 100% maps one pixel of the preview to one
+        // screen pixel on any display. For a source larger than the preview that is
+        // the downscaled copy, not the source itself, and the label says so.
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let fit_scale = (ui.available_width() / source_w as f32)
+            .min(PREVIEW_FIT_HEIGHT / source_h as f32);
+        let scale = match self.preview_zoom {
+            Some(zoom) => zoom * texture_ratio / pixels_per_point,
+            None => fit_scale,
+        };
+        let zoom = scale * pixels_per_point / texture_ratio;
+
+        ui.horizontal(|ui| {
+            ui.label("Zoom:");
+            if ui.selectable_label(self.preview_zoom.is_none(), "Fit").clicked() {
+                self.preview_zoom = None;
+            }
+            if ui.selectable_label(self.preview_zoom == Some(1.0), "100%").clicked() {
+                self.preview_zoom = Some(1.0);
+            }
+            if ui.button("−").clicked() {
+                self.preview_zoom = Some((zoom / PREVIEW_ZOOM_STEP).max(PREVIEW_MIN_ZOOM));
+            }
+            if ui.button("+").clicked() {
+                self.preview_zoom = Some((zoom * PREVIEW_ZOOM_STEP).min(PREVIEW_MAX_ZOOM));
+            }
+            ui.label(format!("{:.0}%", zoom * 100.0));
+            if texture_ratio < 1.0 {
+                let of_source = zoom * texture_ratio * 100.0;
+                ui.label(format!("of the {PREVIEW_MAX_SIZE} px preview ({of_source:.0}% of the source)"))
+                    .on_hover_text("The preview is a downscaled copy; the compressed output keeps full resolution.");
+            }
+        });
+
+        egui::ScrollArea::both()
+            .id_salt("preview")
+            .max_height(PREVIEW_FIT_HEIGHT)
+            .drag_to_scroll(self.preview_tool == PreviewTool::View)
+            .show(ui, |ui| {
+                self.preview_canvas(ui, texture_id, [source_w, source_h], scale);
+            });
+
+        ui.add_space(10.0);
+        self.comparison_ui(ui);
+    }
+
+    // A pinned quality beside the current one, both as they come out of the encoder.
+    fn comparison_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| match self.pinned_quality {
+            None => {
+                if ui
+                    .button(format!("Pin quality {} as reference", self.quality))
+                    .on_hover_text("Then move the slider to compare another quality side by side.")
+                    .clicked()
+                {
+                    self.pinned_quality = Some(self.quality);
+                }
+            }
+            Some(pinned) => {
+                ui.label(format!("Comparing against quality {pinned}"));
+                if ui.button("Unpin").clicked() {
+                    self.pinned_quality = None;
+                }
+            }
+        });
+        let Some(pinned) = self.pinned_quality else {
+            return;
+        };
+
+        let estimators = [&self.pinned_estimator, &self.size_estimator];
+        for (slot, estimator) in estimators.into_iter().enumerate() {
+            if self.comparison_textures[slot].is_none()
+                && let Some(Ok(estimate)) = estimator.estimate()
+                && let Some(thumbnail) = &estimate.thumbnail
+            {
+                let name = ["comparison_pinned", "comparison_current"][slot];
+                self.comparison_textures[slot] = Some(load_texture(ui.ctx(), name, thumbnail));
+            }
+        }
+
+        let sizes = estimators.map(|estimator| match estimator.estimate() {
+            Some(Ok(estimate)) => Some(estimate.size as u64),
+            _ => None,
+        });
+        let captions = [("Reference", pinned), ("Current", self.quality)]
+            .into_iter()
+            .zip(estimators)
+            .map(|((name, quality), estimator)| {
+                let size = match estimator.estimate() {
+                    Some(Ok(estimate)) => format_size(estimate.size as u64),
+                    Some(Err(_)) => "encode failed".to_string(),
+                    None => "encoding…".to_string(),
+                };
+                format!("{name}: quality {quality}, {size}")
+            })
+            .collect::<Vec<_>>();
+
+        let width = (ui.available_width() - ui.spacing().item_spacing.x) / 2.0;
+        ui.horizontal_top(|ui| {
+            for (slot, caption) in captions.iter().enumerate() {
+                ui.vertical(|ui| {
+                    ui.set_width(width);
+                    ui.label(caption);
+                    if slot == 1
+                        && let [Some(reference), Some(current)] = sizes
+                    {
+                        let change = -savings_percent(reference, current);
+                        ui.label(format!("{change:+.1}% against the reference"));
+                    }
+                    if let Some(texture) = &self.comparison_textures[slot] {
+                        let size = texture.size_vec2();
+                        let scale = (width / size.x).min(COMPARISON_HEIGHT / size.y);
+                        ui.image((texture.id(), size * scale));
+                    }
+                });
+            }
+        });
+        ui.add_space(10.0);
+    }
+
+    // Draws the preview image at `scale` points per source pixel and handles
+    // dragging out the crop or protected region.
+    fn preview_canvas(
+        &mut self,
+        ui: &mut egui::Ui,
+        texture_id: egui::TextureId,
+        [source_w, source_h]: [u32; 2],
+        scale: f32,
+    ) {
+        let display_size = egui::vec2(source_w as f32 * scale, source_h as f32 * scale);
+        let sense = match self.preview_tool {
+            PreviewTool::View => egui::Sense::hover(),
+            PreviewTool::Crop | PreviewTool::Protect => egui::Sense::drag(),
+        };
+        let (rect, response) = ui.allocate_exact_size(display_size, sense);
+
+        let to_source = |pos: egui::Pos2| {
+            let rel = (pos - rect.min) / rect.size();
+            [
+                rel.x.clamp(0.0, 1.0) * source_w as f32,
+                rel.y.clamp(0.0, 1.0) * source_h as f32,
+            ]
+        };
+
+        if self.preview_tool != PreviewTool::View {
+            if response.drag_started()
+                && let Some(pos) = response.interact_pointer_pos()
+            {
+                self.drag_start = Some(to_source(pos));
+            }
+            if response.dragged()
+                && let (Some(start), Some(pos)) = (self.drag_start, response.interact_pointer_pos())
+            {
+                let end = to_source(pos);
+                let x0 = start[0].min(end[0]).round() as u32;
+                let y0 = start[1].min(end[1]).round() as u32;
+                let x1 = start[0].max(end[0]).round() as u32;
+                let y1 = start[1].max(end[1]).round() as u32;
+                // Ignore accidental clicks that would produce a degenerate region.
+                let selection = (x1 - x0 >= 2 && y1 - y0 >= 2).then_some(CropRect {
+                    x: x0,
+                    y: y0,
+                    width: x1 - x0,
+                    height: y1 - y0,
+                });
+                match self.preview_tool {
+                    PreviewTool::Crop => self.crop = selection,
+                    PreviewTool::Protect => self.protect_region = selection,
+                    PreviewTool::View => {}
+                }
+            }
+            if response.drag_stopped() {
+                self.drag_start = None;
+            }
+        }
+
+        let painter = ui.painter_at(rect);
+        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        painter.image(texture_id, rect, uv, egui::Color32::WHITE);
+
+        let to_screen = |region: CropRect| {
+            egui::Rect::from_min_size(
+                rect.min + egui::vec2(region.x as f32 * scale, region.y as f32 * scale),
+                egui::vec2(region.width as f32 * scale, region.height as f32 * scale),
+            )
+        };
+
+        if let Some(crop) = self.crop {
+            let crop_rect = to_screen(crop);
+
+            // Dim everything outside the selection.
+            let shade = egui::Color32::from_black_alpha(140);
+            painter.rect_filled(
+                egui::Rect::from_min_max(rect.min, egui::pos2(rect.max.x, crop_rect.min.y)),
+                0.0,
+                shade,
+            );
+            painter.rect_filled(
+                egui::Rect::from_min_max(egui::pos2(rect.min.x, crop_rect.max.y), rect.max),
+                0.0,
+                shade,
+            );
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(rect.min.x, crop_rect.min.y),
+                    egui::pos2(crop_rect.min.x, crop_rect.max.y),
+                ),
+                0.0,
+                shade,
+            );
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(crop_rect.max.x, crop_rect.min.y),
+                    egui::pos2(rect.max.x, crop_rect.max.y),
+                ),
+                0.0,
+                shade,
+            );
+            painter.rect_stroke(crop_rect, 0.0, egui::Stroke::new(1.5, egui::Color32::YELLOW));
+        }
+
+        if let Some(region) = self.protect_region {
+            painter.rect_stroke(
+                to_screen(region),
+                0.0,
+                egui::Stroke::new(1.5, egui::Color32::LIGHT_GREEN),
+            );
+        }
+    }
+
+    fn batch_selection_ui(&mut self, ui: &mut egui::Ui) {
+        // Input folder
+        let dropped = folder_drop_zone(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Input folder:");
+                if ui.button("Browse…").clicked()
+                    && let Some(path) = FileDialog::new().pick_folder()
+                {
+                    self.select_batch_input(path);
+                }
+                if cfg!(feature = "pdf")
+                    && ui.button("From PDF…").clicked()
+                    && let Some(path) = FileDialog::new().add_filter("PDF", &["pdf"]).pick_file()
+                {
+                    self.extract_pdf_images(path);
+                }
+                if ui.button("From ZIP…").clicked()
+                    && let Some(path) = FileDialog::new().add_filter("ZIP", &["zip"]).pick_file()
+                {
+                    self.open_zip(path);
+                }
+            });
+
+            let input_label = match (&self.batch_archive, &self.batch_input_dir) {
+                (Some(pdf), Some(_)) => format!("Images from {}", pdf.display()),
+                (None, Some(dir)) => dir.display().to_string(),
+                _ => "No folder selected (browse, or drop one here)".to_string(),
+            };
+            ui.label(input_label);
+        });
+        match dropped {
+            Some(Ok(path)) => self.select_batch_input(path),
+            Some(Err(err)) => self.status_message = err,
+            None => {}
+        }
+        self.zip_selection_ui(ui);
+        ui.add_space(10.0);
+
+        self.batch_thumbnails_ui(ui);
+
+        // Output folder
+        let dropped = folder_drop_zone(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Output folder:");
+                if ui.button("Browse…").clicked()
+                    && let Some(path) = FileDialog::new().pick_folder()
+                {
+                    self.batch_output_dir = Some(path);
+                    self.status_message = "Output folder selected".to_string();
+                }
+            });
+
+            ui.label(
+                self.batch_output_dir
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| "No folder selected (browse, or drop one here)".to_string()),
+            );
+        });
+        match dropped {
+            Some(Ok(path)) => {
+                self.batch_output_dir = Some(path);
+                self.status_message = "Output folder set from drop".to_string();
+            }
+            Some(Err(err)) => self.status_message = err,
+            None => {}
+        }
+        ui.add_space(10.0);
+
+        ui.horizontal(|ui| {
+            ui.label("Output format:");
+            egui::ComboBox::from_id_salt("batch_format")
+                .selected_text(format!("{:?}", self.batch_format))
+                .show_ui(ui, |ui| {
+                    for format in OUTPUT_FORMATS {
+                        ui.selectable_value(&mut self.batch_format, *format, format!("{format:?}"));
+                    }
+                });
+            ui.checkbox(&mut self.match_input_format, "Match input format").on_hover_text(
+                "Each file keeps its own format. Inputs in formats that can't be written, like GIF or TIFF, \
+                 use the format chosen here.",
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Output names:");
+            ui.add(egui::TextEdit::singleline(&mut self.name_template).desired_width(120.0))
+                .on_hover_text("{name} is the input's file name without its extension, e.g. {name}_web.");
+        });
+        ui.add_space(10.0);
+    }
+
+    // Uses `path` as the batch input, restoring the settings last used for it.
+    fn select_batch_input(&mut self, path: PathBuf) {
+        self.load_batch_thumbnails(&path);
+        self.status_message = match self.folder_profiles.get(&path).cloned() {
+            Some(profile) => {
+                self.apply_settings(&profile.settings);
+                if let Some(format) = ImageFormat::from_extension(&profile.format)
+                    && OUTPUT_FORMATS.contains(&format)
+                {
+                    self.batch_format = format;
+                }
+                "Input folder selected; restored the settings last used for it".to_string()
+            }
+            None => "Input folder selected".to_string(),
+        };
+        self.batch_input_dir = Some(path);
+        self.batch_archive = None;
+        self.zip_selection = None;
+    }
+
+    // Lists the images in a ZIP so the ones to compress can be ticked.
+    fn open_zip(&mut self, path: PathBuf) {
+        match zip_archive::list_images(&path) {
+            Ok(entries) if entries.is_empty() => {
+                self.status_message = "Error: no supported images in ZIP".to_string();
+            }
+            Ok(entries) => {
+                self.status_message = format!("{} images in ZIP; tick the ones to compress", entries.len());
+                self.zip_selection = Some(ZipSelection {
+                    path,
+                    entries: entries.into_iter().map(|entry| (entry, true)).collect(),
+                });
+            }
+            Err(e) => self.status_message = e,
+        }
+    }
+
+    fn zip_selection_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(selection) = &mut self.zip_selection else {
+            return;
+        };
+        let mut confirmed = false;
+        let mut cancelled = false;
+        ui.group(|ui| {
+            ui.label(format!("Images in {}:", selection.path.display()));
+            ui.horizontal(|ui| {
+                for (label, ticked) in [("All", true), ("None", false)] {
+                    if ui.small_button(label).clicked() {
+                        selection.entries.iter_mut().for_each(|(_, selected)| *selected = ticked);
+                    }
+                }
+            });
+            egui::ScrollArea::vertical()
+                .id_salt("zip_entries")
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    for (entry, selected) in &mut selection.entries {
+                        ui.checkbox(selected, &entry.name);
+                    }
+                });
+            let count = selection.entries.iter().filter(|(_, selected)| *selected).count();
+            ui.horizontal(|ui| {
+                confirmed = ui
+                    .add_enabled(count > 0, egui::Button::new(format!("Use {count} selected")))
+                    .clicked();
+                cancelled = ui.button("Cancel").clicked();
+            });
+        });
+        if cancelled {
+            self.zip_selection = None;
+        } else if confirmed && let Some(selection) = self.zip_selection.take() {
+            self.extract_zip_images(selection);
+        }
+    }
+
+    // Unpacks the ticked images to a temporary folder and uses that as the input.
+    fn extract_zip_images(&mut self, selection: ZipSelection) {
+        let stem = selection.path.file_stem().and_then(|s| s.to_str()).unwrap_or("zip");
+        let dir = std::env::temp_dir()
+            .join(format!("image-compressor-zip-{}", std::process::id()))
+            .join(stem);
+        // Images left over from opening the same ZIP earlier.
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let entries: Vec<&zip_archive::Entry> = selection
+            .entries
+            .iter()
+            .filter(|(_, selected)| *selected)
+            .map(|(entry, _)| entry)
+            .collect();
+        match zip_archive::extract(&selection.path, &entries, &dir) {
+            Ok(files) => {
+                self.status_message = format!("Extracted {} images from ZIP", files.len());
+                self.load_batch_thumbnails(&dir);
+                self.batch_input_dir = Some(dir);
+                self.batch_archive = Some(selection.path);
+            }
+            Err(e) => self.status_message = e,
+        }
+    }
+
+    // Extracts the PDF's images to a temporary folder and uses that as the input.
+    fn extract_pdf_images(&mut self, pdf: PathBuf) {
+        let stem = pdf.file_stem().and_then(|s| s.to_str()).unwrap_or("pdf");
+        let dir = std::env::temp_dir()
+            .join(format!("image-compressor-pdf-{}", std::process::id()))
+            .join(stem);
+        // Images left over from extracting the same PDF earlier.
+        let _ = std::fs::remove_dir_all(&dir);
+
+        match pdf_images::extract(&pdf, &dir) {
+            Ok(extracted) if extracted.files.is_empty() => {
+                self.status_message = format!(
+                    "Error: no supported images in PDF ({} skipped)",
+                    extracted.skipped
+                );
+            }
+            Ok(extracted) => {
+                self.status_message = format!("Extracted {} images from PDF", extracted.files.len());
+                if extracted.skipped > 0 {
+                    self.status_message += &format!(", {} in unsupported encodings skipped", extracted.skipped);
+                }
+                self.load_batch_thumbnails(&dir);
+                self.batch_input_dir = Some(dir);
+                self.batch_archive = Some(pdf);
+            }
+            Err(e) => self.status_message = e,
+        }
+    }
+
+    fn load_batch_thumbnails(&mut self, dir: &Path) {
+        self.batch_thumbnails.clear();
+        self.rotation_overrides.clear();
+
+        let Ok(inputs) = collect_batch_inputs(dir) else {
+            self.thumbnail_rx = None;
+            return;
+        };
+
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for path in inputs {
+                let Ok(decoded) = decode_input(&path, &mut Vec::new()) else {
+                    continue;
+                };
+                let thumbnail = decoded
+                    .image
+                    .thumbnail(BATCH_THUMBNAIL_SIZE, BATCH_THUMBNAIL_SIZE);
+                // Stop once the receiver is gone, e.g. another folder was picked.
+                if tx.send((path, thumbnail)).is_err() {
+                    break;
+                }
+            }
+        });
+        self.thumbnail_rx = Some(rx);
+    }
+
+    fn receive_thumbnails(&mut self, ctx: &egui::Context) {
+        let Some(rx) = &self.thumbnail_rx else {
+            return;
+        };
+
+        loop {
+            match rx.try_recv() {
+                Ok((path, image)) => {
+                    let texture = load_texture(ctx, "batch_thumbnail", &image);
+                    self.batch_thumbnails.push(BatchThumbnail { path, image, texture });
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.thumbnail_rx = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn batch_thumbnails_ui(&mut self, ui: &mut egui::Ui) {
+        if self.batch_thumbnails.is_empty() {
+            return;
+        }
+
+        ui.label("Rotate individual files before compressing:");
+        let mut changed = None;
+        egui::ScrollArea::horizontal()
+            .id_salt("batch_thumbnails")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (index, thumb) in self.batch_thumbnails.iter().enumerate() {
+                        let rotation = self
+                            .rotation_overrides
+                            .get(&thumb.path)
+                            .copied()
+                            .unwrap_or_default();
+                        ui.vertical(|ui| {
+                            ui.set_width(BATCH_THUMBNAIL_SIZE as f32);
+                            let size = thumb.texture.size_vec2();
+                            ui.add(egui::Image::new((thumb.texture.id(), size)));
+                            ui.horizontal(|ui| {
+                                if ui.small_button("⟲").clicked() {
+                                    changed = Some((index, rotation.counter_clockwise()));
+                                }
+                                if ui.small_button("⟳").clicked() {
+                                    changed = Some((index, rotation.clockwise()));
+                                }
+                            });
+                            let name = thumb
+                                .path
+                                .file_name()
+                                .map(|name| name.to_string_lossy().into_owned())
+                                .unwrap_or_default();
+                            ui.add(egui::Label::new(egui::RichText::new(name).small()).truncate());
+                        });
+                    }
+                });
+            });
+
+        if let Some((index, rotation)) = changed {
+            let thumb = &mut self.batch_thumbnails[index];
+            let rotated = rotation.apply(thumb.image.clone());
+            thumb.texture = load_texture(ui.ctx(), "batch_thumbnail", &rotated);
+            if rotation == Rotation::None {
+                self.rotation_overrides.remove(&thumb.path);
+            } else {
+                self.rotation_overrides.insert(thumb.path.clone(), rotation);
+            }
+        }
+        ui.add_space(10.0);
+    }
+
+    fn single_compress_ui(&mut self, ui: &mut egui::Ui) {
+        let can_compress = self.input_path.is_some()
+            && self.output_path.is_some()
+            && !self.is_compressing
+            && self.pending_overwrite.is_none()
+            && !(self.block_tiny_resize && self.tiny_resize().is_some());
+
+        if self.combined_export {
+            let any_enabled = self.export_formats.iter().any(|export| export.enabled);
+            if ui
+                .add_enabled(can_compress && any_enabled, egui::Button::new("Export all formats"))
+                .clicked()
+                && let (Some(input), Some(output)) = (&self.input_path, &self.output_path)
+            {
+                let tasks = self
+                    .export_formats
+                    .iter()
+                    .filter(|export| export.enabled)
+                    .map(|export| CompressionTask {
+                        quality: export.quality,
+                        png_level: export.png_level,
+                        ..self.build_single_task(
+                            input.clone(),
+                            output.with_extension(export.format.extensions_str()[0]),
+                        )
+                    })
+                    .collect();
+                let batch = BatchTask { tasks, ..BatchTask::default() };
+                let outputs = batch.tasks.iter().map(|task| task.output_path.clone()).collect();
+                let keep_original = batch.tasks.first().is_some_and(|task| task.keep_original);
+                self.confirm_and_start(Job::Batch(batch), outputs, keep_original);
+            }
+        } else if self.quality_ladder {
+            let qualities = parse_qualities(&self.ladder_qualities);
+            if ui
+                .add_enabled(can_compress && qualities.is_ok(), egui::Button::new("Export quality ladder"))
+                .clicked()
+                && let (Some(input), Some(output), Ok(qualities)) = (&self.input_path, &self.output_path, qualities)
+            {
+                let task = self.build_single_task(input.clone(), output.clone());
+                if task_uses_quality(&task) {
+                    let outputs = ladder_outputs(&task.output_path, &qualities);
+                    let keep_original = task.keep_original;
+                    self.confirm_and_start(Job::QualityLadder { task, qualities }, outputs, keep_original);
+                } else {
+                    self.status_message = "Error: this output ignores quality, so every file in the ladder \
+                        would be the same; use JPEG, lossy WebP or JPEG XL"
+                        .to_string();
+                }
+            }
+        } else if ui
+            .add_enabled(can_compress, self.compress_button("Compress image"))
+            .clicked()
+            && let (Some(input), Some(output)) = (&self.input_path, &self.output_path)
+        {
+            let task = CompressionTask {
+                upload: self.upload_target(),
+                ..self.build_single_task(input.clone(), output.clone())
+            };
+            self.confirm_and_start_single(task);
+        }
+
+        if ui
+            .add_enabled(can_compress, egui::Button::new("Optimize for web"))
+            .on_hover_text(format!(
+                "Writes an sRGB WebP at quality {WEB_QUALITY}, shrunk to at most {WEB_MAX_SIDE} px per side, \
+                 without the note or metadata fields, plus a {WEB_THUMBNAIL_SIZE} px thumbnail. \
+                 Other settings such as crop and rotation still apply."
+            ))
+            .clicked()
+            && let (Some(input), Some(output)) = (&self.input_path, &self.output_path)
+        {
+            let output = output_with_extension(Some(output), input, "webp");
+            let task = CompressionTask {
+                upload: self.upload_target(),
+                ..self.build_single_task(input.clone(), output)
+            };
+            self.confirm_and_start_single(web_task(task));
+        }
+
+        let can_copy = self.input_path.is_some() && !self.is_compressing;
+        if ui
+            .add_enabled(can_copy, egui::Button::new("Copy as data URI"))
+            .on_hover_text("Uses the output file's format, or JPEG if none is chosen. No file is written.")
+            .clicked()
+            && let Some(input) = &self.input_path
+        {
+            // Only the extension matters here, since nothing is written to disk.
+            let output = self
+                .output_path
+                .clone()
+                .unwrap_or_else(|| input.with_extension("jpg"));
+            let task = self.build_single_task(input.clone(), output);
+            let _ = self.compress_tx.send(Job::DataUri(task));
+            self.is_compressing = true;
+            self.status_message = "Encoding...".to_string();
+        }
+
+        if ui
+            .add_enabled(can_compress, egui::Button::new("Copy as CLI command"))
+            .on_hover_text("Copies a command line that compresses this file with the current settings.")
+            .clicked()
+            && let (Some(input), Some(output)) = (&self.input_path, &self.output_path)
+        {
+            let command = cli::command_line(&self.build_single_task(input.clone(), output.clone()));
+            self.status_message = self.copy_to_clipboard(command, "command");
+        }
+
+        if self.clipboard_backup.is_some()
+            && ui
+                .button("Restore clipboard")
+                .on_hover_text("Puts back what was on the clipboard before the last copy.")
+                .clicked()
+        {
+            self.status_message = self.restore_clipboard();
+        }
+
+        if ui
+            .add_enabled(can_compress, egui::Button::new("Split channels"))
+            .on_hover_text("Writes R, G, B and A as separate grayscale PNGs next to the output file.")
+            .clicked()
+            && let (Some(input), Some(output)) = (&self.input_path, &self.output_path)
+        {
+            let task = self.build_single_task(input.clone(), output.clone());
+            let _ = self.compress_tx.send(Job::SplitChannels(task));
+            self.is_compressing = true;
+            self.status_message = "Splitting channels...".to_string();
+        }
+    }
+
+    // Runs the files that failed in the last batch again, e.g. after unlocking them.
+    fn retry_failed(&mut self) {
+        // Queued jobs always write, so a queue run's failures retry with the defaults.
+        let batch = BatchTask {
+            tasks: std::mem::take(&mut self.failed_tasks),
+            ..self.failed_batch.as_ref().map(BatchTask::retry_template).unwrap_or_default()
+        };
+        let _ = self.compress_tx.send(Job::Batch(batch));
+        self.is_compressing = true;
+        self.batch_log.clear();
+        self.status_message = "Retrying failed files...".to_string();
+    }
+
+    // Starts `task`, first asking before it replaces an existing file.
+    fn confirm_and_start_single(&mut self, task: CompressionTask) {
+        let (outputs, keep_original) = (vec![task.output_path.clone()], task.keep_original);
+        self.confirm_and_start(Job::Single(task), outputs, keep_original);
+    }
+
+    // Starts `job`, first asking before it replaces any of `outputs` that exist.
+    fn confirm_and_start(&mut self, job: Job, outputs: Vec<PathBuf>, keep_original: bool) {
+        let existing: Vec<PathBuf> = outputs.into_iter().filter(|output| output.exists()).collect();
+        if existing.is_empty() || self.skip_overwrite_confirm {
+            self.start_job(job);
+        } else {
+            self.pending_overwrite = Some(PendingOverwrite { job, existing, keep_original });
+        }
+    }
+
+    // Sends a single-file job or an export of several outputs to the worker.
+    fn start_job(&mut self, job: Job) {
+        match &job {
+            Job::Single(task) => {
+                // The log is otherwise only used by batches; here it collects the timings.
+                if task.log_timing {
+                    self.batch_log.clear();
+                }
+                self.status_message = "Compressing...".to_string();
+            }
+            Job::Batch(batch) => {
+                self.failed_batch = Some(batch.retry_template());
+                self.batch_log.clear();
+                self.failed_tasks.clear();
+                self.status_message = "Exporting...".to_string();
+            }
+            _ => self.status_message = "Exporting...".to_string(),
+        }
+        let _ = self.compress_tx.send(job);
+        self.is_compressing = true;
+    }
+
+    // Asks before replacing existing output files.
+    fn overwrite_confirm_ui(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending_overwrite else {
+            return;
+        };
+        let keep_original = pending.keep_original;
+        let names: Vec<String> = pending
+            .existing
+            .iter()
+            .map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default())
+            .collect();
+        let question = match names.as_slice() {
+            [name] => format!("{name} already exists. Replace it?"),
+            names => format!("{} already exist. Replace them?", names.join(", ")),
+        };
+        let kept = match names.as_slice() {
+            [name] => format!("The current file will be kept as {name}.orig."),
+            _ => "The current files will be kept with .orig added to their names.".to_string(),
+        };
+
+        let mut decision = None;
+        egui::Window::new("Overwrite file?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(question);
+                if keep_original {
+                    ui.label(kept);
+                }
+                ui.checkbox(&mut self.skip_overwrite_confirm, "Don't ask again this session");
+                ui.horizontal(|ui| {
+                    if ui.button("Overwrite").clicked() {
+                        decision = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        decision = Some(false);
+                    }
+                });
+            });
+
+        match decision {
+            Some(true) => {
+                if let Some(pending) = self.pending_overwrite.take() {
+                    self.start_job(pending.job);
+                }
+            }
+            Some(false) => {
+                self.pending_overwrite = None;
+                // Only remember the choice when the user actually went ahead.
+                self.skip_overwrite_confirm = false;
+                self.status_message = "Cancelled".to_string();
+            }
+            None => {}
+        }
+    }
+
+    // Places `text` on the system clipboard and returns the status message to show.
+    fn copy_to_clipboard(&mut self, text: String, description: &str) -> String {
+        let Some(clipboard) = &mut self.clipboard else {
+            return "Error: clipboard is not available".to_string();
+        };
+
+        let previous = match clipboard.get_text() {
+            Ok(previous) => Some(ClipboardBackup::Text(previous)),
+            Err(_) => clipboard.get_image().ok().map(ClipboardBackup::Image),
+        };
+        match clipboard.set_text(text.clone()) {
+            Ok(()) => {
+                let ours = matches!(
+                    (&previous, &self.clipboard_copied),
+                    (Some(ClipboardBackup::Text(previous)), Some(copied)) if previous == copied
+                );
+                if !ours {
+                    self.clipboard_backup = previous;
+                }
+                self.clipboard_copied = Some(text);
+                format!("Success: copied {description} to clipboard")
+            }
+            Err(e) => format!("Error copying to clipboard: {e}"),
+        }
+    }
+
+    // Puts back what was on the clipboard before the app's last copy.
+    fn restore_clipboard(&mut self) -> String {
+        let (Some(clipboard), Some(backup)) = (&mut self.clipboard, self.clipboard_backup.take()) else {
+            return "Error: nothing to restore".to_string();
+        };
+        self.clipboard_copied = None;
+        let result = match backup {
+            ClipboardBackup::Text(text) => clipboard.set_text(text),
+            ClipboardBackup::Image(image) => clipboard.set_image(image),
+        };
+        match result {
+            Ok(()) => "Success: restored the previous clipboard content".to_string(),
+            Err(e) => format!("Error restoring clipboard: {e}"),
+        }
+    }
+
+    // A ratio every output is cut to before resizing, e.g. 1:1 for avatars.
+    fn aspect_crop_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.aspect_crop_enabled, "Crop to aspect ratio");
+            ui.add_enabled_ui(self.aspect_crop_enabled, |ui| {
+                ui.add(egui::DragValue::new(&mut self.aspect_crop_width).range(1..=100));
+                ui.label(":");
+                ui.add(egui::DragValue::new(&mut self.aspect_crop_height).range(1..=100));
+                egui::ComboBox::from_id_salt("aspect_crop_placement")
+                    .selected_text(if self.aspect_crop_smart { "most detailed part" } else { "centered" })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.aspect_crop_smart, false, "centered");
+                        ui.selectable_value(&mut self.aspect_crop_smart, true, "most detailed part");
+                    })
+                    .response
+                    .on_hover_text(
+                        "\"Most detailed part\" keeps the busiest area of the image, which is usually the \
+                         subject, instead of the middle.",
+                    );
+            });
+        });
+    }
+
+    fn resize_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.resize_enabled, "Resize");
+        if !self.resize_enabled {
+            return;
+        }
+
+        // Batch files each have their own shape, so the lock can only link the
+        // fields when a single input (or its crop) gives us a ratio.
+        let aspect = self.source_aspect();
+
+        ui.horizontal(|ui| {
+            ui.label("Width:");
+            let width_changed = ui
+                .add(egui::DragValue::new(&mut self.resize_width).range(1..=65535).suffix(" px"))
+                .changed();
+            ui.label("Height:");
+            let height_changed = ui
+                .add(egui::DragValue::new(&mut self.resize_height).range(1..=65535).suffix(" px"))
+                .changed();
+            ui.toggle_value(&mut self.aspect_locked, "🔒")
+                .on_hover_text("Preserve aspect ratio");
+
+            if self.aspect_locked
+                && let Some(aspect) = aspect
+            {
+                if width_changed {
+                    self.resize_height = ((self.resize_width as f32 / aspect).round() as u32).max(1);
+                } else if height_changed {
+                    self.resize_width = ((self.resize_height as f32 * aspect).round() as u32).max(1);
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            egui::ComboBox::from_id_salt("resize_filter")
+                .selected_text(self.resize_filter.label())
+                .show_ui(ui, |ui| {
+                    for filter in ResizeFilter::ALL {
+                        ui.selectable_value(&mut self.resize_filter, filter, filter.label());
+                    }
+                });
+        });
+
+        if self.aspect_locked && aspect.is_none() {
+            ui.label("Images will be fitted inside these dimensions, keeping their aspect ratio.");
+        }
+        ui.horizontal(|ui| {
+            ui.label("Warn below");
+            ui.add(egui::DragValue::new(&mut self.min_resize_side).range(1..=4096).suffix(" px"));
+            ui.checkbox(&mut self.block_tiny_resize, "and don't compress");
+        })
+        .response
+        .on_hover_text("Catches a mistyped dimension before it produces an unusable image.");
+        if let Some([width, height]) = self.tiny_resize() {
+            let blocked = if self.block_tiny_resize { " Compressing is blocked." } else { "" };
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("⚠ Output would be only {width}×{height} px.{blocked}"),
+            );
+        }
+        if self.batch_mode {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.resize_threshold_enabled, "Only resize files larger than");
+                ui.add_enabled(
+                    self.resize_threshold_enabled,
+                    egui::DragValue::new(&mut self.resize_min_input_kb)
+                        .range(1..=1_000_000)
+                        .suffix(" KB"),
+                );
+            })
+            .response
+            .on_hover_text("Smaller files keep their original dimensions.");
+        }
+    }
+
+    // Format-specific options, collapsed by default and limited to the formats
+    // the current settings write.
+    fn advanced_options_ui(&mut self, ui: &mut egui::Ui) {
+        let formats: Vec<ImageFormat> = [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP]
+            .into_iter()
+            .filter(|format| self.writes_format(*format))
+            .collect();
+        if formats.is_empty() {
+            return;
+        }
+
+        ui.add_space(10.0);
+        let names: Vec<String> = formats.iter().map(|format| format!("{format:?}")).collect();
+        egui::CollapsingHeader::new(format!("Advanced {} options", names.join("/")))
+            .id_salt("advanced_options")
+            .show(ui, |ui| {
+                if formats.contains(&ImageFormat::Jpeg) {
+                    ui.horizontal(|ui| {
+                        ui.label("Restart markers every");
+                        ui.add(
+                            egui::DragValue::new(&mut self.jpeg_restart_interval)
+                                .range(0..=1024)
+                                .suffix(" MCUs"),
+                        )
+                        .on_hover_text(
+                            "Lets decoders resync after corrupted bytes, at a small size cost. 0 disables them.",
+                        );
+                    });
+                }
+
+                if formats.contains(&ImageFormat::Png) {
+                    ui.horizontal(|ui| {
+                        ui.label("PNG output:");
+                        egui::ComboBox::from_id_salt("png_depth")
+                            .selected_text(self.png_depth.label())
+                            .show_ui(ui, |ui| {
+                                for depth in PngDepth::ALL {
+                                    ui.selectable_value(&mut self.png_depth, depth, depth.label());
+                                }
+                            });
+                    });
+                }
+
+                if formats.contains(&ImageFormat::WebP) {
+                    self.webp_options_ui(ui);
+                }
+            });
+    }
+
+    fn quality_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Compression quality:");
+            ui.label(format!("{}%", self.quality));
+        });
+
+        let uses_quality = self.batch_mode
+            || self
+                .output_path
+                .as_deref()
+                .and_then(OutputFormat::from_path)
+                .is_none_or(OutputFormat::uses_quality);
+        ui.add_enabled(
+            uses_quality,
+            egui::Slider::new(&mut self.quality, 1..=100)
+                .text("Quality")
+                .show_value(false),
+        );
+        ui.add_enabled_ui(uses_quality, |ui| {
+            ui.horizontal(|ui| {
+                for &(name, quality) in QUALITY_PRESETS {
+                    let label = format!("{name} ({quality})");
+                    if ui.selectable_label(self.quality == quality, label).clicked() {
+                        self.quality = quality;
+                    }
+                }
+            });
+        });
+        if !uses_quality {
+            ui.label("Not used: this output format is uncompressed.");
+        }
+        ui.label("Lower = more compression / smaller file.");
+        ui.label("Higher = less compression / better quality.");
+        if self.quality < LOW_QUALITY_WARNING && self.quality_is_lossy() {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("⚠ Quality {} is very low; expect heavy artifacts.", self.quality),
+            );
+        }
+        if !self.batch_mode
+            && let Some(source) = self.source_quality
+            && self.quality > source
+            && self.quality_is_lossy()
+        {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!(
+                    "⚠ The input was saved at about quality {source}; going higher can't recover detail and only makes the file bigger."
+                ),
+            );
+        }
+        if !self.batch_mode {
+            ui.horizontal(|ui| {
+                if ui
+                    .checkbox(&mut self.auto_quality, "Auto quality")
+                    .on_hover_text("Picks a starting quality from how detailed the image is; adjust it freely afterwards.")
+                    .changed()
+                    && self.auto_quality
+                {
+                    self.request_auto_quality();
+                }
+                if self.analyzing {
+                    ui.spinner();
+                }
+            });
+        }
+    }
+
+    // PNG is lossless, so in place of a quality it only has the deflate effort.
+    fn png_level_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("PNG compression level:");
+            ui.label(self.png_level.to_string());
+        });
+        ui.add(egui::Slider::new(&mut self.png_level, 0..=9).text("Level").show_value(false));
+        ui.label("0 = stored uncompressed, fastest / largest file.");
+        ui.label("9 = smallest file, slowest. Every level keeps the pixels exact.");
+    }
+
+    fn webp_options_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("WebP mode:");
+            ui.radio_value(&mut self.webp_mode, WebpMode::Lossless, "Lossless");
+            ui.radio_value(&mut self.webp_mode, WebpMode::NearLossless, "Near-lossless");
+            ui.radio_value(&mut self.webp_mode, WebpMode::Lossy, "Lossy");
+        });
+
+        match self.webp_mode {
+            WebpMode::Lossless => {}
+            WebpMode::NearLossless => {
+                ui.add(
+                    egui::Slider::new(&mut self.webp_near_lossless, 0..=100)
+                        .text("Near-lossless level"),
+                );
+                ui.label("100 = lossless; lower values preprocess pixels for smaller files.");
+            }
+            WebpMode::Lossy => {
+                ui.label("Uses the compression quality above.");
+            }
+        }
+    }
+
+    fn combined_export_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.combined_export, "Export to several formats at once")
+            .on_hover_text("Writes one file per format next to the output file, replacing its extension.");
+        if !self.combined_export {
+            return;
+        }
+
+        for export in &mut self.export_formats {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut export.enabled, format!("{:?}", export.format));
+                // PNG takes a compression level rather than a quality.
+                let slider = if export.format == ImageFormat::Png {
+                    egui::Slider::new(&mut export.png_level, 0..=9).text("level")
+                } else {
+                    egui::Slider::new(&mut export.quality, 1..=100).text("quality")
+                };
+                ui.add_enabled(export.enabled, slider);
+            });
+        }
+        ui.label("Each format uses its own setting instead of the slider above.");
+    }
+
+    fn quality_ladder_ui(&mut self, ui: &mut egui::Ui) {
+        // Both write several files from the output name; one at a time.
+        if self.combined_export {
+            return;
+        }
+        ui.checkbox(&mut self.quality_ladder, "Export a quality ladder")
+            .on_hover_text("Writes one file per quality next to the output file, e.g. photo_q40.jpg.");
+        if !self.quality_ladder {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Qualities:");
+            ui.add(egui::TextEdit::singleline(&mut self.ladder_qualities).desired_width(160.0))
+                .on_hover_text("Separated by commas or spaces, each from 1 to 100.");
+        });
+        if let Err(err) = parse_qualities(&self.ladder_qualities) {
+            ui.colored_label(egui::Color32::RED, err);
+        }
+        if !self.writes_format(ImageFormat::Jpeg) && !self.writes_format(ImageFormat::WebP) {
+            ui.label("Only JPEG and WebP outputs have a quality; other formats give identical files.");
+        }
+        ui.label("Used instead of the quality slider above.");
+    }
+
+    fn upload_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.upload_enabled, "Upload after compressing")
+            .on_hover_text("Sends the compressed file to an HTTP endpoint once it has been saved.");
+        if !self.upload_enabled {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("URL:");
+            ui.text_edit_singleline(&mut self.upload_url);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Method:");
+            ui.radio_value(&mut self.upload_method, upload::UploadMethod::Put, "PUT");
+            ui.radio_value(&mut self.upload_method, upload::UploadMethod::Post, "POST");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Authorization:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.upload_authorization)
+                    .password(true)
+                    .hint_text("optional, e.g. Bearer <token>"),
+            );
+        });
+    }
+
+    // The upload step for "Compress image", if one is configured.
+    fn upload_target(&self) -> Option<upload::UploadTarget> {
+        let url = self.upload_url.trim();
+        (self.upload_enabled && !url.is_empty()).then(|| upload::UploadTarget {
+            url: url.to_string(),
+            method: self.upload_method,
+            authorization: self.upload_authorization.clone(),
+        })
+    }
+
+    // Whether PNG is the one format the current settings write, so the quality
+    // slider has nothing to control.
+    fn writes_only_png(&self) -> bool {
+        if !self.batch_mode && self.combined_export {
+            return false;
+        }
+        self.selected_output_format() == Some(ImageFormat::Png)
+    }
+
+    // Whether the main quality slider feeds a lossy encoder.
+    fn quality_is_lossy(&self) -> bool {
+        if !self.batch_mode && (self.combined_export || self.quality_ladder) {
+            // Combined export and the ladder use their own qualities.
+            return false;
+        }
+        self.writes_format(ImageFormat::Jpeg)
+            || (self.writes_format(ImageFormat::WebP) && self.webp_mode == WebpMode::Lossy)
+    }
+
+    // Whether the current settings will produce output in `format`.
+    fn writes_format(&self, format: ImageFormat) -> bool {
+        if !self.batch_mode && self.combined_export {
+            return self
+                .export_formats
+                .iter()
+                .any(|export| export.enabled && export.format == format);
+        }
+        // Any of them, depending on the inputs.
+        if self.batch_mode && self.match_input_format {
+            return OUTPUT_FORMATS.contains(&format);
+        }
+        self.selected_output_format() == Some(format)
+    }
+
+    // Format the current mode will write, if it is known yet.
+    fn selected_output_format(&self) -> Option<ImageFormat> {
+        if self.batch_mode {
+            return Some(self.batch_format);
+        }
+        self.output_path
+            .as_deref()
+            .and_then(|path| ImageFormat::from_path(path).ok())
+    }
+
+    // Size of what will be encoded: the crop if one is set, else the source,
+    // either cut down to the aspect crop.
+    fn encoded_source_size(&self) -> Option<[u32; 2]> {
+        if self.batch_mode {
+            return None;
+        }
+        let size = match (self.crop, &self.preview) {
+            (Some(crop), _) => [crop.width, crop.height],
+            (None, Some(preview)) => preview.source_size,
+            (None, None) => return None,
+        };
+        Some(self.aspect_crop().map_or(size, |aspect| aspect.fit(size)))
+    }
+
+    fn aspect_crop(&self) -> Option<AspectCrop> {
+        self.aspect_crop_enabled.then_some(AspectCrop {
+            width: self.aspect_crop_width,
+            height: self.aspect_crop_height,
+            smart: self.aspect_crop_smart,
+        })
+    }
+
+    // Dimensions the resize settings give: exact for a single input, and just
+    // the entered ones for batches, whose files each have their own size.
+    fn resize_output_size(&self) -> Option<[u32; 2]> {
+        if !self.resize_enabled {
+            return None;
+        }
+        let target = [self.resize_width, self.resize_height];
+        match self.encoded_source_size() {
+            // Same rounding as `DynamicImage::resize`.
+            Some([width, height]) if self.aspect_locked => {
+                let scale = (target[0] as f64 / width as f64).min(target[1] as f64 / height as f64);
+                Some([
+                    ((width as f64 * scale).round() as u32).max(1),
+                    ((height as f64 * scale).round() as u32).max(1),
+                ])
+            }
+            _ => Some(target),
+        }
+    }
+
+    // The resize output size, when a side of it is under `min_resize_side`.
+    fn tiny_resize(&self) -> Option<[u32; 2]> {
+        self.resize_output_size().filter(|[width, height]| (*width).min(*height) < self.min_resize_side)
+    }
+
+    // Width/height ratio of what will be encoded.
+    fn source_aspect(&self) -> Option<f32> {
+        let [width, height] = self.encoded_source_size()?;
+        Some(width as f32 / height as f32)
+    }
+
+    // Why the current settings will likely show visible artifacts, if they will.
+    // Batch files each have their own size, so only their quality is judged.
+    fn quality_risk(&self) -> Option<&'static str> {
+        let low_quality = self.quality < LOW_QUALITY_WARNING && self.quality_is_lossy();
+        let heavy_downscale = self.resize_enabled
+            && self.encoded_source_size().is_some_and(|[width, height]| {
+                let scale = (self.resize_width as f32 / width as f32)
+                    .min(self.resize_height as f32 / height as f32);
+                scale < HEAVY_DOWNSCALE_WARNING
+            });
+        match (low_quality, heavy_downscale) {
+            (true, true) => Some("low quality, heavy downscale"),
+            (true, false) => Some("low quality"),
+            (false, true) => Some("heavy downscale"),
+            (false, false) => None,
+        }
+    }
+
+    // A compress button reading e.g. "Compress image (low quality!)" in the warning
+    // color when `quality_risk` finds a problem.
+    fn compress_button(&self, label: &str) -> egui::Button<'static> {
+        match self.quality_risk() {
+            Some(risk) => egui::Button::new(
+                egui::RichText::new(format!("{label} ({risk}!)")).color(egui::Color32::YELLOW),
+            ),
+            None => egui::Button::new(label.to_string()),
+        }
+    }
+
+    fn queue_ui(&mut self, ui: &mut egui::Ui) {
+        let shared = Arc::clone(&self.queue);
+        let mut queue = lock_queue(&shared);
+        ui.label(format!("Queue ({} jobs):", queue.len()));
+        // Jobs that have run or been removed drop out of the selection.
+        let ids: HashSet<u64> = queue.iter().map(|task| task.queue_id).collect();
+        self.queue_selection.retain(|id| ids.contains(id));
+
+        let mut remove = None;
+        let mut move_to_top = None;
+        let mut clicked = None;
+        for (index, task) in queue.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("✖").clicked() {
+                    remove = Some(index);
+                }
+                if ui
+                    .add_enabled(index > 0, egui::Button::new("⏶").small())
+                    .on_hover_text("Move to top: run this job next.")
+                    .clicked()
+                {
+                    move_to_top = Some(index);
+                }
+
+                // Each job keeps its own format and quality, whatever the settings say by the time it runs.
+                let format = ImageFormat::from_path(&task.output_path).ok().filter(|f| OUTPUT_FORMATS.contains(f));
+                let mut chosen = format;
+                let current = match format {
+                    Some(format) => format!("{format:?}"),
+                    None => task.output_path.extension().map_or_else(String::new, |extension| {
+                        extension.to_string_lossy().to_uppercase()
+                    }),
+                };
+                egui::ComboBox::from_id_salt(("queue_format", index))
+                    .selected_text(current)
+                    .width(64.0)
+                    .show_ui(ui, |ui| {
+                        for format in OUTPUT_FORMATS {
+                            ui.selectable_value(&mut chosen, Some(*format), format!("{format:?}"));
+                        }
+                    });
+                if chosen != format
+                    && let Some(format) = chosen
+                {
+                    task.output_path.set_extension(format.extensions_str()[0]);
+                }
+                ui.add(egui::DragValue::new(&mut task.quality).range(1..=100))
+                    .on_hover_text("Quality for this job.");
+
+                let selected = self.queue_selection.contains(&task.queue_id);
+                let label = format!("{} → {}", task.input_path.display(), task.output_path.display());
+                if ui
+                    .selectable_label(selected, label)
+                    .on_hover_text("Ctrl-click to add to the selection, shift-click to select a range.")
+                    .clicked()
+                {
+                    clicked = Some((index, ui.input(|i| i.modifiers)));
+                }
+            });
+        }
+        if let Some((index, modifiers)) = clicked {
+            self.select_queued(&queue, index, modifiers);
+        }
+        if let Some(index) = remove {
+            queue.remove(index);
+        }
+        if let Some(index) = move_to_top {
+            let task = queue.remove(index);
+            queue.insert(0, task);
+        }
+
+        ui.horizontal(|ui| {
+            let can_add = self.input_path.is_some() && self.output_path.is_some();
+            if ui.add_enabled(can_add, egui::Button::new("Add to queue")).clicked()
+                && let (Some(input), Some(output)) = (&self.input_path, &self.output_path)
+            {
+                let task = self.build_single_task(input.clone(), output.clone());
+                queue.push(CompressionTask { queue_id: self.take_queue_id(), ..task });
+            }
+
+            // Jobs added while the queue runs are picked up by the same run.
+            let can_run = !queue.is_empty() && !self.is_compressing;
+            if ui.add_enabled(can_run, egui::Button::new("Run queue")).clicked() {
+                let _ = self.compress_tx.send(Job::Queue);
+                self.is_compressing = true;
+                self.batch_log.clear();
+                self.failed_tasks.clear();
+                self.failed_batch = None;
+                self.status_message = "Compressing...".to_string();
+            }
+
+            if ui.add_enabled(!queue.is_empty(), egui::Button::new("Clear")).clicked() {
+                queue.clear();
+            }
+        });
+
+        if !self.queue_selection.is_empty() {
+            ui.horizontal(|ui| {
+                let count = self.queue_selection.len();
+                if ui.button(format!("Remove selected ({count})")).clicked() {
+                    queue.retain(|task| !self.queue_selection.contains(&task.queue_id));
+                    self.queue_selection.clear();
+                }
+                ui.separator();
+                ui.add(egui::DragValue::new(&mut self.queue_bulk_quality).range(1..=100));
+                if ui.button("Set quality").clicked() {
+                    for task in queue.iter_mut().filter(|task| self.queue_selection.contains(&task.queue_id)) {
+                        task.quality = self.queue_bulk_quality;
+                    }
+                }
+                if ui.button("Select none").clicked() {
+                    self.queue_selection.clear();
+                }
+            });
+        }
+
+        // The dialogs block until closed, so they open with the queue unlocked,
+        // or a running queue couldn't take its next job meanwhile.
+        let empty = queue.is_empty();
+        drop(queue);
+        let (save, load) = ui
+            .horizontal(|ui| {
+                let save = ui.add_enabled(!empty, egui::Button::new("Save queue…")).clicked();
+                (save, ui.button("Load queue…").clicked())
+            })
+            .inner;
+
+        if save
+            && let Some(path) = FileDialog::new()
+                .add_filter("Job file", &["json"])
+                .save_file()
+        {
+            let jobs = lock_queue(&shared).clone();
+            self.status_message = match job_file::save_job_file(&path, &jobs) {
+                Ok(()) => format!("Queue saved to {}", path.display()),
+                Err(err) => err,
+            };
+        }
+
+        if load
+            && let Some(path) = FileDialog::new()
+                .add_filter("Job file", &["json"])
+                .pick_file()
+        {
+            self.status_message = match job_file::load_job_file(&path) {
+                Ok(jobs) => {
+                    let count = jobs.len();
+                    let jobs: Vec<CompressionTask> =
+                        jobs.into_iter().map(|job| CompressionTask { queue_id: self.take_queue_id(), ..job }).collect();
+                    lock_queue(&shared).extend(jobs);
+                    format!("Loaded {count} jobs from {}", path.display())
+                }
+                Err(err) => err,
+            };
+        }
+    }
+
+    // Plain clicks select only the job, ctrl-click (cmd on macOS) toggles it,
+    // and shift-click selects everything between it and the last job clicked.
+    fn select_queued(&mut self, queue: &[CompressionTask], index: usize, modifiers: egui::Modifiers) {
+        let id = queue[index].queue_id;
+        let anchor = self.queue_anchor.and_then(|anchor| queue.iter().position(|task| task.queue_id == anchor));
+        match anchor {
+            Some(anchor) if modifiers.shift => {
+                if !modifiers.command {
+                    self.queue_selection.clear();
+                }
+                let range = anchor.min(index)..=anchor.max(index);
+                self.queue_selection.extend(queue[range].iter().map(|task| task.queue_id));
+                // The anchor stays put, so further shift-clicks resize the range.
+                return;
+            }
+            _ if modifiers.command => {
+                if !self.queue_selection.remove(&id) {
+                    self.queue_selection.insert(id);
+                }
+            }
+            _ => {
+                self.queue_selection.clear();
+                self.queue_selection.insert(id);
+            }
+        }
+        self.queue_anchor = Some(id);
+    }
+
+    fn take_queue_id(&mut self) -> u64 {
+        let id = self.next_queue_id;
+        self.next_queue_id += 1;
+        id
+    }
+
+    fn batch_compress_ui(&mut self, ui: &mut egui::Ui) {
+        let can_compress = self.batch_input_dir.is_some()
+            && self.batch_output_dir.is_some()
+            && !self.is_compressing
+            && !(self.block_tiny_resize && self.tiny_resize().is_some());
+
+        if ui
+            .add_enabled(can_compress, self.compress_button("Compress folder"))
+            .clicked()
+            && let (Some(input_dir), Some(output_dir)) =
+                (&self.batch_input_dir, &self.batch_output_dir)
+        {
+            let mut inputs = match collect_batch_inputs(input_dir) {
+                Ok(inputs) => inputs,
+                Err(e) => {
+                    self.status_message = format!("Error reading input folder: {e}");
+                    return;
+                }
+            };
+            self.batch_order.sort(&mut inputs);
+            if inputs.is_empty() {
+                self.status_message =
+                    "Error: no supported images found in the input folder".to_string();
+                return;
+            }
+            let unsupported = match self.unsupported_files {
+                UnsupportedFiles::Ignore => Vec::new(),
+                UnsupportedFiles::Log | UnsupportedFiles::Copy => {
+                    collect_unsupported_files(input_dir).unwrap_or_default()
+                }
+            };
+
+            let extension = self.batch_format.extensions_str()[0];
+            let mut used_names = HashSet::new();
+            let tasks = inputs
+                .into_iter()
+                .map(|input_path| {
+                    let stem = input_path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let stem = apply_name_template(&self.name_template, &stem);
+                    let extension = if self.match_input_format {
+                        writable_input_format(&input_path).map_or(extension, |format| format.extensions_str()[0])
+                    } else {
+                        extension
+                    };
+                    let output_path =
+                        output_dir.join(unique_output_name(&stem, extension, &mut used_names));
+                    let rotation = self
+                        .rotation_overrides
+                        .get(&input_path)
+                        .copied()
+                        .unwrap_or_default();
+                    CompressionTask {
+                        rotation,
+                        ..self.build_task(input_path, output_path)
+                    }
+                })
+                .collect();
+
+            let batch = BatchTask {
+                tasks,
+                filters: BatchFilters {
+                    min_savings_percent: Some(self.min_savings_percent),
+                    existing_outputs: self.existing_outputs,
+                    resize_min_input_size: (self.resize_enabled && self.resize_threshold_enabled)
+                        .then_some(u64::from(self.resize_min_input_kb) * 1024),
+                },
+                size_budget: self
+                    .size_budget_enabled
+                    .then_some((self.size_budget_mb * 1024.0 * 1024.0) as u64),
+                report_dir: self.write_report.then(|| output_dir.clone()),
+                unsupported,
+                copy_unsupported_to: (self.unsupported_files == UnsupportedFiles::Copy)
+                    .then(|| output_dir.clone()),
+                parallel: self.parallel_batch.then_some(ParallelBatch {
+                    workers: self.parallel_workers,
+                    memory_budget: self
+                        .memory_limit_enabled
+                        .then_some(u64::from(self.memory_limit_mb) * 1024 * 1024),
+                }),
+            };
+            self.failed_batch = Some(batch.retry_template());
+            let _ = self.compress_tx.send(Job::Batch(batch));
+            self.is_compressing = true;
+            self.batch_log.clear();
+            self.failed_tasks.clear();
+            self.status_message = "Compressing...".to_string();
+
+            // A PDF's or ZIP's images sit in a temporary folder, not worth remembering.
+            if self.batch_archive.is_none() {
+                let profile = folder_profiles::FolderProfile {
+                    format: extension.to_string(),
+                    settings: self.build_task(PathBuf::new(), PathBuf::new()),
+                };
+                if let Err(e) = self.folder_profiles.set(input_dir.clone(), profile) {
+                    self.batch_log.push(e);
+                }
+            }
+        }
+
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            let can_build = self.batch_input_dir.is_some() && !self.is_compressing;
+            if ui
+                .add_enabled(can_build, egui::Button::new("Build animated WebP…"))
+                .on_hover_text("Joins the input folder's images, in numbered order, into one animated WebP.")
+                .clicked()
+                && let Some(output) = FileDialog::new()
+                    .add_filter("WebP", &["webp"])
+                    .set_file_name("animation.webp")
+                    .save_file()
+            {
+                self.start_build_animation(output);
+            }
+            ui.label("showing each frame for");
+            ui.add(
+                egui::DragValue::new(&mut self.animation_delay_ms)
+                    .range(10..=10_000)
+                    .suffix(" ms"),
+            );
+        });
+        ui.horizontal(|ui| {
+            let can_build = self.batch_input_dir.is_some() && !self.is_compressing;
+            if ui
+                .add_enabled(can_build, egui::Button::new("Create contact sheet…"))
+                .on_hover_text("Lays out every image in the input folder as a captioned grid in one image.")
+                .clicked()
+                && let Some(output) = FileDialog::new()
+                    .add_filter("JPEG", &["jpg", "jpeg"])
+                    .add_filter("PNG", &["png"])
+                    .add_filter("WebP", &["webp"])
+                    .set_file_name("contact_sheet.jpg")
+                    .save_file()
+            {
+                self.start_contact_sheet(output);
+            }
+            ui.add(
+                egui::DragValue::new(&mut self.contact_sheet_columns)
+                    .range(1..=20)
+                    .suffix(" columns"),
+            );
+            ui.label("of");
+            ui.add(
+                egui::DragValue::new(&mut self.contact_sheet_thumb_size)
+                    .range(32..=1024)
+                    .suffix(" px"),
+            );
+        });
+    }
+
+    fn start_contact_sheet(&mut self, output_path: PathBuf) {
+        let Some(input_dir) = &self.batch_input_dir else {
+            return;
+        };
+        let mut images = match collect_batch_inputs(input_dir) {
+            Ok(images) => images,
+            Err(e) => {
+                self.status_message = format!("Error reading input folder: {e}");
+                return;
+            }
+        };
+        if images.is_empty() {
+            self.status_message = "Error: no supported images found in the input folder".to_string();
+            return;
+        }
+        self.batch_order.sort(&mut images);
+
+        let task = ContactSheetTask {
+            images,
+            columns: self.contact_sheet_columns,
+            thumb_size: self.contact_sheet_thumb_size,
+            encoding: self.build_task(PathBuf::new(), output_path),
+        };
+        let _ = self.compress_tx.send(Job::ContactSheet(task));
+        self.is_compressing = true;
+        self.batch_log.clear();
+        self.status_message = "Creating contact sheet...".to_string();
+    }
+
+    fn start_build_animation(&mut self, output_path: PathBuf) {
+        let Some(input_dir) = &self.batch_input_dir else {
+            return;
+        };
+        let mut frames = match collect_batch_inputs(input_dir) {
+            Ok(frames) => frames,
+            Err(e) => {
+                self.status_message = format!("Error reading input folder: {e}");
+                return;
+            }
+        };
+        if frames.is_empty() {
+            self.status_message = "Error: no supported images found in the input folder".to_string();
+            return;
+        }
+        animation::sort_frames(&mut frames);
+
+        let task = AnimationTask {
+            frames,
+            output_path,
+            delay_ms: self.animation_delay_ms,
+            quality: self.quality,
+            lossless: self.webp_mode == WebpMode::Lossless,
+            temp_dir: self.temp_dir.clone(),
+        };
+        let _ = self.compress_tx.send(Job::BuildAnimation(task));
+        self.is_compressing = true;
+        self.status_message = "Building animation...".to_string();
+    }
+
+    // Like `build_task`, plus the settings tied to the selected single input.
+    fn build_single_task(&self, input_path: PathBuf, output_path: PathBuf) -> CompressionTask {
+        // The selection is drawn on a single frame, so it doesn't carry over to a sheet.
+        if self.sprite_sheet && self.page_count > 1 && is_animation(&input_path) {
+            return CompressionTask {
+                sprite_columns: Some(self.sprite_columns),
+                ..self.build_task(input_path, output_path)
+            };
+        }
+        CompressionTask {
+            crop: self.crop,
+            protect_region: self.protect_region,
+            page: self.page,
+            ..self.build_task(input_path, output_path)
+        }
+    }
+
+    // The reverse of `build_task`: loads a task's settings back into the UI.
+    fn apply_settings(&mut self, task: &CompressionTask) {
+        self.quality = task.quality;
+        self.convert_to_srgb = task.convert_to_srgb;
+        self.aspect_crop_enabled = task.aspect_crop.is_some();
+        if let Some(aspect) = task.aspect_crop {
+            self.aspect_crop_width = aspect.width;
+            self.aspect_crop_height = aspect.height;
+            self.aspect_crop_smart = aspect.smart;
+        }
+        self.resize_enabled = task.resize.is_some();
+        if let Some(resize) = task.resize {
+            self.resize_width = resize.width;
+            self.resize_height = resize.height;
+            self.aspect_locked = resize.keep_aspect;
+        }
+        self.resize_filter = task.resize_filter;
+        self.protect_quality = task.protect_quality;
+        self.webp_mode = task.webp_mode;
+        self.webp_near_lossless = task.webp_near_lossless;
+        self.background = task.background;
+        self.flatten = task.flatten;
+        self.defringe = task.defringe;
+        self.deskew = task.deskew;
+        self.auto_levels = task.auto_levels;
+        self.brightness = task.brightness;
+        self.contrast = task.contrast;
+        self.posterize_enabled = task.posterize_levels.is_some();
+        if let Some(levels) = task.posterize_levels {
+            self.posterize_levels = levels;
+        }
+        self.png_depth = task.png_depth;
+        self.png_level = task.png_level;
+        self.jpeg_restart_interval = task.jpeg_restart_interval;
+        self.decode_timeout_secs = task.decode_timeout_secs;
+        self.salvage_truncated = task.salvage_truncated;
+        self.log_timing = task.log_timing;
+        self.post_command = task.post_command.clone();
+        self.temp_dir = task.temp_dir.clone();
+        self.keep_original = task.keep_original;
+        self.note = task.note.clone();
+        self.metadata = task.metadata.clone();
+        self.watermark_enabled = task.watermark.is_some();
+        if let Some(watermark) = &task.watermark {
+            self.watermark = watermark.clone();
+        }
+        self.thumbnail_enabled = task.thumbnail_size.is_some();
+        if let Some(size) = task.thumbnail_size {
+            self.thumbnail_size = size;
+        }
+        self.blurhash = task.blurhash;
+    }
+
+    // Builds a task for one input/output pair from the current settings.
+    fn build_task(&self, input_path: PathBuf, output_path: PathBuf) -> CompressionTask {
+        CompressionTask {
+            input_path,
+            output_path,
+            quality: self.quality,
+            crop: None,
+            aspect_crop: self.aspect_crop(),
+            convert_to_srgb: self.convert_to_srgb,
+            rotation: Rotation::None,
+            deskew: self.deskew,
+            resize: self.resize_enabled.then_some(ResizeTarget {
+                width: self.resize_width,
+                height: self.resize_height,
+                keep_aspect: self.aspect_locked,
+                shrink_only: false,
+            }),
+            resize_filter: self.resize_filter,
+            protect_region: None,
+            protect_quality: self.protect_quality,
+            page: 0,
+            webp_mode: self.webp_mode,
+            webp_near_lossless: self.webp_near_lossless,
+            background: self.background,
+            flatten: self.flatten,
+            defringe: self.defringe,
+            auto_levels: self.auto_levels,
+            brightness: self.brightness,
+            contrast: self.contrast,
+            posterize_levels: self.posterize_enabled.then_some(self.posterize_levels),
+            png_depth: self.png_depth,
+            png_level: self.png_level,
+            upload: None,
+            queue_id: 0,
+            note: self.note.clone(),
+            metadata: self.metadata.clone(),
+            watermark: self.watermark_enabled.then(|| self.watermark.clone()),
+            thumbnail_size: self.thumbnail_enabled.then_some(self.thumbnail_size),
+            blurhash: self.blurhash,
+            sprite_columns: None,
+            jpeg_restart_interval: self.jpeg_restart_interval,
+            decode_timeout_secs: self.decode_timeout_secs,
+            salvage_truncated: self.salvage_truncated,
+            log_timing: self.log_timing,
+            post_command: self.post_command.clone(),
+            temp_dir: self.temp_dir.clone(),
+            keep_original: self.keep_original,
+        }
+    }
+}
+
+fn load_texture(ctx: &egui::Context, name: &str, img: &DynamicImage) -> egui::TextureHandle {
+    let rgba = img.to_rgba8();
+    let color_image = egui::ColorImage::from_rgba_unmultiplied(
+        [rgba.width() as usize, rgba.height() as usize],
+        rgba.as_raw(),
+    );
+    ctx.load_texture(name, color_image, egui::TextureOptions::LINEAR)
+}
+
+// Compression logic running in background thread
+fn perform_compression(
+    task: CompressionTask,
+    events: &Sender<WorkerEvent>,
+    cache: Option<&decode_cache::DecodeCache>,
+) -> Result<String, String> {
+    let timer = PhaseTimer::new();
+    let report = |phase| {
+        timer.mark(phase);
+        let _ = events.send(WorkerEvent::Phase(phase));
+    };
+    let encoded = encode_task_with_progress(&task, &report, cache)?;
+    report(Phase::Writing);
+    write_output(&task, &encoded.bytes, encoded.thumbnail.as_deref())?;
+    if let Ok(meta) = std::fs::metadata(&task.input_path) {
+        let compressed = encoded.bytes.len() as u64;
+        let _ = events.send(WorkerEvent::Sizes { original: meta.len(), compressed });
+    }
+
+    let mut msg = format!("Success: saved to {}", task.output_path.display());
+    if !task.post_command.trim().is_empty() {
+        msg.push_str(", post-processed");
+    }
+    if let Some(target) = &task.upload {
+        report(Phase::Uploading);
+        match upload::send(target, &encoded.bytes, encoded.format.mime_type()) {
+            Ok(reply) => msg.push_str(&format!(", uploaded ({reply})")),
+            Err(err) => return Err(format!("{err} (saved to {})", task.output_path.display())),
+        }
+    }
+    if task.log_timing
+        && let Some(timing) = timer.summary()
+    {
+        let _ = events.send(WorkerEvent::Log(format!("{}: {timing}", file_name(&task.input_path))));
+    }
+    if let Some(angle) = encoded.deskew_angle {
+        if angle.abs() >= deskew::MIN_SKEW {
+            msg.push_str(&format!(", straightened by {angle:.1}°"));
+        } else {
+            msg.push_str(", already straight");
+        }
+    }
+    for note in encoded.notes {
+        msg.push_str(&format!(" (warning: {note})"));
+    }
+    // Last, so the UI can tack "copied to clipboard" on after it.
+    if let Some(hash) = encoded.blurhash {
+        msg.push_str(&format!(", BlurHash {hash}"));
+        let _ = events.send(WorkerEvent::BlurHash(hash));
+    }
+    Ok(msg)
+}
+
+// Reads every frame, reporting progress as it goes, and writes the animation.
+fn perform_build_animation(task: &AnimationTask, events: &Sender<WorkerEvent>) -> Result<String, String> {
+    let started = Instant::now();
+    let total = task.frames.len();
+    let frames = task.frames.iter().enumerate().map(|(index, path)| {
+        let _ = events.send(WorkerEvent::Progress { done: index, total, elapsed: started.elapsed() });
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        match decode_input(path, &mut Vec::new()) {
+            Ok(decoded) => Ok(decoded.image.into_rgba8()),
+            Err(err) => Err(format!("{err} ({name})")),
+        }
+    });
+    let bytes = animation::encode_webp(frames, task.delay_ms, task.quality, task.lossless)?;
+    if let Err(e) = write_atomic(&task.output_path, &bytes, task.temp_dir.as_deref()) {
+        return Err(format!("Error saving animation: {e}"));
+    }
+    Ok(format!(
+        "Success: built a {total}-frame animation ({}) at {}",
+        format_size(bytes.len() as u64),
+        task.output_path.display()
+    ))
+}
+
+// Shrinks every image, lays them out and encodes the sheet with the task's
+// settings. Images that can't be read are left off and logged.
+fn perform_contact_sheet(task: &ContactSheetTask, events: &Sender<WorkerEvent>) -> Result<String, String> {
+    let output_path = &task.encoding.output_path;
+    let Some(format) = OutputFormat::from_path(output_path) else {
+        return Err("Error: unsupported format. Use .jpg, .png, .webp, .qoi, .jxl, .ppm, .pam, or .raw".to_string());
+    };
+
+    let started = Instant::now();
+    let total = task.images.len();
+    let mut cells = Vec::new();
+    for (index, path) in task.images.iter().enumerate() {
+        let _ = events.send(WorkerEvent::Progress { done: index, total, elapsed: started.elapsed() });
+        let name = file_name(path);
+        match decode_input(path, &mut Vec::new()) {
+            Ok(decoded) => {
+                // Small images keep their size rather than being blown up.
+                let image = decoded.image;
+                let thumbnail = if image.width() > task.thumb_size || image.height() > task.thumb_size {
+                    image.thumbnail(task.thumb_size, task.thumb_size)
+                } else {
+                    image
+                };
+                cells.push(contact_sheet::Cell { name, thumbnail });
+            }
+            Err(err) => {
+                let _ = events.send(WorkerEvent::Log(format!("{name}: {err}")));
+            }
+        }
+    }
+
+    // The sheet's background is opaque, so no alpha channel is needed.
+    let sheet = contact_sheet::compose(&cells, task.columns, task.thumb_size)?;
+    let sheet = DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(sheet).to_rgb8());
+    let bytes = encode_image(&sheet, format, &task.encoding, None, &mut Vec::new(), &|_| {})?;
+    if let Err(e) = write_atomic(output_path, &bytes, task.encoding.temp_dir.as_deref()) {
+        return Err(format!("Error saving contact sheet: {e}"));
+    }
+    Ok(format!(
+        "Success: contact sheet of {} images ({}) saved to {}",
+        cells.len(),
+        format_size(bytes.len() as u64),
+        output_path.display()
+    ))
+}
+
+// Writes the processed image at each of `qualities`, named after the output
+// with the quality added, e.g. photo_q40.jpg.
+fn perform_quality_ladder(
+    task: &CompressionTask,
+    qualities: &[u8],
+    cache: &decode_cache::DecodeCache,
+    events: &Sender<WorkerEvent>,
+) -> Result<String, String> {
+    let started = Instant::now();
+    let processed = process_task(task, &|_| {}, Some(cache))?;
+
+    let mut written = Vec::new();
+    let outputs = ladder_outputs(&task.output_path, qualities);
+    for (index, (&quality, output_path)) in qualities.iter().zip(outputs).enumerate() {
+        let _ = events.send(WorkerEvent::Progress { done: index, total: qualities.len(), elapsed: started.elapsed() });
+        let level = CompressionTask { quality, output_path, ..task.clone() };
+        let encoded = encode_processed(&level, processed.clone(), &|_| {})?;
+        write_output(&level, &encoded.bytes, encoded.thumbnail.as_deref())?;
+        written.push(format!("q{quality} {}", format_size(encoded.bytes.len() as u64)));
+    }
+
+    let dir = task.output_path.parent().unwrap_or(Path::new(""));
+    Ok(format!("Success: wrote {} qualities to {} ({})", qualities.len(), dir.display(), written.join(", ")))
+}
+
+// Where the ladder from `output` writes each of `qualities`, e.g. photo_q40.jpg.
+fn ladder_outputs(output: &Path, qualities: &[u8]) -> Vec<PathBuf> {
+    let stem = output.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = output.extension().map(|ext| ext.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut used_names = HashSet::new();
+    qualities
+        .iter()
+        .map(|quality| {
+            let name = unique_output_name(&format!("{stem}_q{quality}"), &extension, &mut used_names);
+            output.with_file_name(name)
+        })
+        .collect()
+}
+
+// "40, 60 80" -> [40, 60, 80], sorted and without repeats.
+fn parse_qualities(text: &str) -> Result<Vec<u8>, String> {
+    let mut qualities = Vec::new();
+    for part in text.split([',', ' ']).filter(|part| !part.is_empty()) {
+        match part.parse::<u8>() {
+            Ok(quality) if (1..=100).contains(&quality) => qualities.push(quality),
+            _ => return Err(format!("Error: {part} is not a quality from 1 to 100")),
+        }
+    }
+    if qualities.is_empty() {
+        return Err("Error: enter at least one quality".to_string());
+    }
+    qualities.sort_unstable();
+    qualities.dedup();
+    Ok(qualities)
+}
+
+// Writes R, G, B and A as separate grayscale PNGs named after the output file,
+// e.g. photo_r.png, photo_g.png, photo_b.png and photo_a.png.
+fn perform_split_channels(task: &CompressionTask, cache: &decode_cache::DecodeCache) -> Result<String, String> {
+    let processed = process_task(task, &|_| {}, Some(cache))?;
+    let rgba = processed.image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    let stem = task
+        .output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    for (index, name) in ["r", "g", "b", "a"].into_iter().enumerate() {
+        let channel = image::GrayImage::from_fn(width, height, |x, y| {
+            image::Luma([rgba.get_pixel(x, y).0[index]])
+        });
+        let mut buf = Vec::new();
+        if let Err(e) = save_png(&DynamicImage::ImageLuma8(channel), &mut buf, task.png_level, PngDepth::L8) {
+            return Err(format!("Error encoding image: {e}"));
+        }
+        let channel_task =
+            CompressionTask { output_path: task.output_path.with_file_name(format!("{stem}_{name}.png")), ..task.clone() };
+        write_output(&channel_task, &buf, None)?;
+    }
+
+    let dir = task.output_path.parent().unwrap_or(Path::new(""));
+    Ok(format!("Success: wrote {stem}_r/g/b/a.png to {}", dir.display()))
+}
+
+fn encode_data_uri(task: &CompressionTask, cache: &decode_cache::DecodeCache) -> Result<String, String> {
+    use base64::Engine;
+
+    let encoded = encode_task_with_progress(task, &|_| {}, Some(cache))?;
+    let payload = base64::engine::general_purpose::STANDARD.encode(&encoded.bytes);
+    Ok(format!("data:{};base64,{payload}", encoded.format.mime_type()))
+}
+
+// Compresses every supported image in a folder, reporting per-file results as it goes.
+fn perform_batch(batch: BatchTask, pause: &PauseControl, events: &Sender<WorkerEvent>) -> Result<String, String> {
+    let total = batch.tasks.len();
+    let mut tally = handle_unsupported(&batch, events);
+    let started = Instant::now();
+    let mut paused = Duration::ZERO;
+
+    if let Some(parallel) = batch.parallel {
+        run_batch_parallel(batch.tasks, batch.filters, parallel, pause, &mut tally, events);
+    } else {
+        for (index, task) in batch.tasks.into_iter().enumerate() {
+            paused += pause.wait();
+            let _ = events.send(WorkerEvent::Progress {
+                done: index,
+                total,
+                elapsed: started.elapsed().saturating_sub(paused),
+            });
+
+            run_batch_file(task, batch.filters, &mut tally, events);
+        }
+    }
+
+    if let Some(dir) = &batch.report_dir
+        && let Err(e) = batch_report::write(dir, &tally.entries)
+    {
+        let _ = events.send(WorkerEvent::Log(e));
+    }
+    tally.send_sizes(events);
+    Ok(tally.summary())
+}
+
+// Compresses `tasks` on `parallel.workers` threads, each taking the next file
+// once it has reserved the file's memory from the budget.
+fn run_batch_parallel(
+    tasks: Vec<CompressionTask>,
+    filters: BatchFilters,
+    parallel: ParallelBatch,
+    pause: &PauseControl,
+    tally: &mut BatchTally,
+    events: &Sender<WorkerEvent>,
+) {
+    let total = tasks.len();
+    let budget = parallel.memory_budget.map(decode_budget::DecodeBudget::new);
+    let tasks = Mutex::new(tasks.into_iter().enumerate());
+    let started = Instant::now();
+    let done = AtomicUsize::new(0);
+    // Every worker waits out the same pauses, so the longest total is the batch's.
+    let paused_nanos = AtomicU64::new(0);
+
+    let tallies: Vec<(BatchTally, Vec<usize>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..parallel.workers.clamp(1, total.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut tally = BatchTally::default();
+                    // Where each of the tally's report entries comes in the batch.
+                    let mut indexes = Vec::new();
+                    let mut paused = Duration::ZERO;
+                    loop {
+                        paused += pause.wait();
+                        paused_nanos.fetch_max(paused.as_nanos() as u64, Ordering::Relaxed);
+                        let Some((index, task)) = tasks.lock().unwrap_or_else(PoisonError::into_inner).next() else {
+                            break;
+                        };
+                        // Held until the file is written.
+                        let _reservation = budget
+                            .as_ref()
+                            .map(|budget| budget.reserve(decode_budget::estimate(&task.input_path)));
+                        let _ = events.send(WorkerEvent::Progress {
+                            done: done.load(Ordering::Relaxed),
+                            total,
+                            elapsed: started
+                                .elapsed()
+                                .saturating_sub(Duration::from_nanos(paused_nanos.load(Ordering::Relaxed))),
+                        });
+                        run_batch_file(task, filters, &mut tally, events);
+                        indexes.push(index);
+                        done.fetch_add(1, Ordering::Relaxed);
+                    }
+                    (tally, indexes)
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+    let mut entries = Vec::new();
+    for (mut worker_tally, indexes) in tallies {
+        entries.extend(indexes.into_iter().zip(std::mem::take(&mut worker_tally.entries)));
+        tally.merge(worker_tally);
+    }
+    // In batch order, as a sequential run reports them, rather than grouped by worker.
+    entries.sort_by_key(|&(index, _)| index);
+    tally.entries.extend(entries.into_iter().map(|(_, entry)| entry));
+}
+
+// Like `perform_batch`, but takes tasks one at a time from the front of the shared
+// queue, so entries the UI moves, adds or removes meanwhile are honored.
+fn perform_queue(
+    queue: &SharedQueue,
+    pause: &PauseControl,
+    events: &Sender<WorkerEvent>,
+) -> Result<String, String> {
+    let mut tally = BatchTally::default();
+    let started = Instant::now();
+    let mut paused = Duration::ZERO;
+
+    let mut done = 0;
+    loop {
+        // Before taking the next task, so it can still be reordered meanwhile.
+        paused += pause.wait();
+        let (task, remaining) = {
+            let mut queue = lock_queue(queue);
+            if queue.is_empty() {
+                break;
+            }
+            let task = queue.remove(0);
+            (task, queue.len())
+        };
+
+        let _ = events.send(WorkerEvent::Progress {
+            done,
+            total: done + remaining + 1,
+            elapsed: started.elapsed().saturating_sub(paused),
+        });
+        run_batch_file(task, BatchFilters::default(), &mut tally, events);
+        done += 1;
+    }
+
+    tally.send_sizes(events);
+    Ok(tally.summary())
+}
+
+// How the files of a batch ended up.
+#[derive(Default)]
+struct BatchTally {
+    written: usize,
+    skipped: usize,
+    failed: usize,
+    // Input and output bytes of the written files.
+    original_bytes: u64,
+    compressed_bytes: u64,
+    // One per file, for the batch report.
+    entries: Vec<batch_report::Entry>,
+}
+
+impl BatchTally {
+    fn summary(&self) -> String {
+        format!(
+            "Success: batch finished ({} written, {} skipped, {} failed)",
+            self.written, self.skipped, self.failed
+        )
+    }
+
+    fn merge(&mut self, other: BatchTally) {
+        self.written += other.written;
+        self.skipped += other.skipped;
+        self.failed += other.failed;
+        self.original_bytes += other.original_bytes;
+        self.compressed_bytes += other.compressed_bytes;
+        self.entries.extend(other.entries);
+    }
+
+    fn send_sizes(&self, events: &Sender<WorkerEvent>) {
+        if self.compressed_bytes > 0 {
+            let (original, compressed) = (self.original_bytes, self.compressed_bytes);
+            let _ = events.send(WorkerEvent::Sizes { original, compressed });
+        }
+    }
+}
+
+// Logs or copies the files a batch can't compress. Copies count as written,
+// logged files as skipped.
+fn handle_unsupported(batch: &BatchTask, events: &Sender<WorkerEvent>) -> BatchTally {
+    let mut tally = BatchTally::default();
+    for path in &batch.unsupported {
+        let name = file_name(path);
+        let line = match &batch.copy_unsupported_to {
+            Some(dir) => match std::fs::copy(path, dir.join(&name)) {
+                Ok(_) => {
+                    tally.written += 1;
+                    format!("{name}: copied unchanged (not a supported image)")
+                }
+                Err(e) => {
+                    tally.failed += 1;
+                    format!("{name}: Error copying file: {e}")
+                }
+            },
+            None => {
+                tally.skipped += 1;
+                format!("{name}: skipped (not a supported image)")
+            }
+        };
+        let _ = events.send(WorkerEvent::Log(line));
+    }
+    tally
+}
+
+// Compresses one batch file and logs the result, counting the outcome in `tally`.
+fn run_batch_file(
+    mut task: CompressionTask,
+    filters: BatchFilters,
+    tally: &mut BatchTally,
+    events: &Sender<WorkerEvent>,
+) {
+    // Before compressing, so the report records the name actually written.
+    filters.apply_rename(&mut task);
+    let file_name = file_name(&task.input_path);
+    let log_timing = task.log_timing;
+    let timer = PhaseTimer::new();
+
+    let (line, entry) = match compress_batch_file(&task, filters, &timer) {
+        Ok(BatchOutcome::Written { input_size, output_size, blurhash }) => {
+            tally.written += 1;
+            tally.original_bytes += input_size;
+            tally.compressed_bytes += output_size;
+            let savings = savings_percent(input_size, output_size);
+            (
+                format!("{file_name}: saved ({savings:.1}% smaller)"),
+                batch_report::Entry {
+                    blurhash,
+                    ..batch_report::Entry::new(&task, Some(input_size), Some(output_size), "written")
+                },
+            )
+        }
+        Ok(BatchOutcome::InsufficientGain { input_size, output_size }) => {
+            tally.skipped += 1;
+            let result = "skipped (insufficient gain)";
+            (
+                format!("{file_name}: {result}"),
+                batch_report::Entry::new(&task, Some(input_size), Some(output_size), result),
+            )
+        }
+        Ok(BatchOutcome::AlreadyExists { input_size }) => {
+            tally.skipped += 1;
+            let result = "skipped (already exists)";
+            (
+                format!("{file_name}: {result}"),
+                batch_report::Entry::new(&task, Some(input_size), None, result),
+            )
+        }
+        Err(err) => {
+            tally.failed += 1;
+            let input_size = std::fs::metadata(&task.input_path).ok().map(|meta| meta.len());
+            let entry = batch_report::Entry::new(&task, input_size, None, err.as_str());
+            let _ = events.send(WorkerEvent::Failed(Box::new(task)));
+            (format!("{file_name}: {err}"), entry)
+        }
+    };
+    let _ = events.send(WorkerEvent::Log(line));
+    if log_timing
+        && let Some(timing) = timer.summary()
+    {
+        let _ = events.send(WorkerEvent::Log(format!("{file_name}: {timing}")));
+    }
+    tally.entries.push(entry);
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// Frames `add_contents` as a place to drop a folder, highlighted while files are
+// dragged over the window. Returns the folder dropped onto it, or an error when a
+// file was dropped there instead.
+fn folder_drop_zone(
+    ui: &mut egui::Ui,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) -> Option<Result<PathBuf, String>> {
+    let dragging = ui.ctx().input(|i| !i.raw.hovered_files.is_empty());
+    let stroke = if dragging {
+        ui.visuals().selection.stroke
+    } else {
+        ui.visuals().widgets.noninteractive.bg_stroke
+    };
+    let rect = egui::Frame::none()
+        .stroke(stroke)
+        .rounding(4.0)
+        .inner_margin(6.0)
+        .show(ui, add_contents)
+        .response
+        .rect;
+
+    // Drops land wherever the pointer was last seen, since that's all winit reports.
+    let (dropped, pointer) = ui.ctx().input(|i| {
+        (i.raw.dropped_files.first().and_then(|file| file.path.clone()), i.pointer.latest_pos())
+    });
+    let path = dropped?;
+    if !pointer.is_some_and(|pos| rect.contains(pos)) {
+        return None;
+    }
+    if path.is_dir() {
+        Some(Ok(path))
+    } else {
+        Some(Err("Error: drop a folder here, not a file".to_string()))
+    }
+}
+
+// SSIM of at least 0.97 is rarely distinguishable from the source; below 0.9
+// artifacts are usually visible without zooming in.
+fn fidelity_badge(ssim: f64) -> (&'static str, egui::Color32) {
+    if ssim >= 0.97 {
+        ("Excellent", egui::Color32::GREEN)
+    } else if ssim >= 0.9 {
+        ("Noticeable loss", egui::Color32::YELLOW)
+    } else {
+        ("Visible artifacts", egui::Color32::RED)
+    }
+}
+
+// File size in KB below a megabyte, otherwise MB.
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{} KB", bytes.div_ceil(1024))
+    } else {
+        format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+// Short human-readable duration for the batch ETA, e.g. "45s" or "3m 05s".
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{secs}s")
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+// Batch variant that fits the combined output size into `budget` bytes. Every file is
+// first encoded at the requested quality; then the largest output that can still go
+// lower is re-encoded a step down, repeatedly, until the total fits or nothing is left
+// to lower. Outputs whose format ignores quality are left as they are. Files that miss
+// the minimum savings at the requested quality are skipped and don't count towards
+// the budget. Outputs are only written once the final qualities are settled.
+fn perform_budget_batch(
+    batch: BatchTask,
+    budget: u64,
+    pause: &PauseControl,
+    events: &Sender<WorkerEvent>,
+) -> Result<String, String> {
+    // Lowest quality the budget search will go down to, and how far it steps each time.
+    const BUDGET_MIN_QUALITY: u8 = 10;
+    const BUDGET_QUALITY_STEP: u8 = 5;
+
+    struct Entry {
+        task: CompressionTask,
+        file_name: String,
+        input_size: u64,
+        bytes: Vec<u8>,
+        thumbnail: Option<Vec<u8>>,
+        // Taken from the first encode; lowering the quality barely changes it.
+        blurhash: Option<String>,
+        // Kept to re-encode from while the quality can still go lower, so the
+        // source isn't decoded again each step.
+        processed: Option<ProcessedImage>,
+    }
+
+    let total_files = batch.tasks.len();
+    let mut entries = Vec::new();
+    let mut report = Vec::new();
+    let other_files = handle_unsupported(&batch, events);
+    let mut failed = other_files.failed;
+    let mut skipped = other_files.skipped;
+    let started = Instant::now();
+    let mut paused = Duration::ZERO;
+
+    for (index, mut task) in batch.tasks.into_iter().enumerate() {
+        paused += pause.wait();
+        let _ = events.send(WorkerEvent::Progress {
+            done: index,
+            total: total_files,
+            elapsed: started.elapsed().saturating_sub(paused),
+        });
+
+        let file_name = task
+            .input_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let input_meta = match std::fs::metadata(&task.input_path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                failed += 1;
+                let err = format!("Error reading input file: {e}");
+                let _ = events.send(WorkerEvent::Log(format!("{file_name}: {err}")));
+                report.push(batch_report::Entry::new(&task, None, None, err));
+                let _ = events.send(WorkerEvent::Failed(Box::new(task)));
+                continue;
+            }
+        };
+        let input_size = input_meta.len();
+        if output_is_current(&task, &input_meta, batch.filters.existing_outputs) {
+            skipped += 1;
+            let result = "skipped (already exists)";
+            let _ = events.send(WorkerEvent::Log(format!("{file_name}: {result}")));
+            report.push(batch_report::Entry::new(&task, Some(input_size), None, result));
+            continue;
+        }
+        batch.filters.apply_rename(&mut task);
+
+        batch.filters.apply_resize_threshold(&mut task, input_size);
+        // Only the first encode is timed; writing happens once every quality is settled.
+        let timer = PhaseTimer::new();
+        let encoded = process_task(&task, &|phase| timer.mark(phase), None).and_then(|processed| {
+            let lowerable = task_uses_quality(&task) && task.quality > BUDGET_MIN_QUALITY;
+            let kept = lowerable.then(|| processed.clone());
+            Ok((encode_processed(&task, processed, &|phase| timer.mark(phase))?, kept))
+        });
+        if task.log_timing
+            && let Some(timing) = timer.summary()
+        {
+            let _ = events.send(WorkerEvent::Log(format!("{file_name}: {timing}")));
+        }
+        match encoded {
+            Ok((encoded, processed)) => {
+                let output_size = encoded.bytes.len() as u64;
+                if batch.filters.min_savings_percent.is_some_and(|min| savings_percent(input_size, output_size) < min) {
+                    skipped += 1;
+                    let result = "skipped (insufficient gain)";
+                    let _ = events.send(WorkerEvent::Log(format!("{file_name}: {result}")));
+                    report.push(batch_report::Entry::new(&task, Some(input_size), Some(output_size), result));
+                    continue;
+                }
+                entries.push(Entry {
+                    task,
+                    file_name,
+                    input_size,
+                    bytes: encoded.bytes,
+                    thumbnail: encoded.thumbnail,
+                    blurhash: encoded.blurhash,
+                    processed,
+                });
+            }
+            Err(err) => {
+                failed += 1;
+                let _ = events.send(WorkerEvent::Log(format!("{file_name}: {err}")));
+                report.push(batch_report::Entry::new(&task, Some(input_size), None, err));
+                let _ = events.send(WorkerEvent::Failed(Box::new(task)));
+            }
+        }
+    }
+
+    let mut total: u64 = entries.iter().map(|entry| entry.bytes.len() as u64).sum();
+    while total > budget {
+        pause.wait();
+        // Entries that can't go lower have no processed image and sort first.
+        let Some(entry) = entries
+            .iter_mut()
+            .max_by_key(|entry| entry.processed.as_ref().map(|_| entry.bytes.len()))
+        else {
+            break;
+        };
+        let Some(processed) = entry.processed.take() else {
+            break;
+        };
+
+        let previous = entry.task.quality;
+        entry.task.quality = entry
+            .task
+            .quality
+            .saturating_sub(BUDGET_QUALITY_STEP)
+            .max(BUDGET_MIN_QUALITY);
+        let _ = events.send(WorkerEvent::Log(format!(
+            "{}: lowering quality to {}",
+            entry.file_name, entry.task.quality
+        )));
+
+        let kept = (entry.task.quality > BUDGET_MIN_QUALITY).then(|| processed.clone());
+        match encode_processed(&entry.task, processed, &|_| {}) {
+            Ok(encoded) => {
+                total = total - entry.bytes.len() as u64 + encoded.bytes.len() as u64;
+                entry.bytes = encoded.bytes;
+                entry.thumbnail = encoded.thumbnail;
+                entry.processed = kept;
+            }
+            Err(err) => {
+                // Keep the previous encode and stop trying to lower this file.
+                let _ = events.send(WorkerEvent::Log(format!("{}: {err}", entry.file_name)));
+                entry.task.quality = previous;
+            }
+        }
+    }
+
+    let mut written = other_files.written;
+    let (mut original, mut compressed) = (0, 0);
+    for entry in &entries {
+        let output_size = Some(entry.bytes.len() as u64);
+        let line = match write_output(&entry.task, &entry.bytes, entry.thumbnail.as_deref()) {
+            Ok(()) => {
+                written += 1;
+                original += entry.input_size;
+                compressed += entry.bytes.len() as u64;
+                report.push(batch_report::Entry {
+                    blurhash: entry.blurhash.clone(),
+                    ..batch_report::Entry::new(&entry.task, Some(entry.input_size), output_size, "written")
+                });
+                format!(
+                    "{}: saved at quality {} ({} KB)",
+                    entry.file_name,
+                    entry.task.quality,
+                    entry.bytes.len().div_ceil(1024)
+                )
+            }
+            Err(err) => {
+                failed += 1;
+                let _ = events.send(WorkerEvent::Failed(Box::new(entry.task.clone())));
+                let line = format!("{}: {err}", entry.file_name);
+                report.push(batch_report::Entry::new(&entry.task, Some(entry.input_size), output_size, err));
+                line
+            }
+        };
+        let _ = events.send(WorkerEvent::Log(line));
+    }
+    if let Some(dir) = &batch.report_dir
+        && let Err(e) = batch_report::write(dir, &report)
+    {
+        let _ = events.send(WorkerEvent::Log(e));
+    }
+    if compressed > 0 {
+        let _ = events.send(WorkerEvent::Sizes { original, compressed });
+    }
+
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    if total > budget {
+        return Err(format!(
+            "Error: batch is {:.2} MB even at minimum quality, over the {:.2} MB budget \
+             ({written} written, {skipped} skipped, {failed} failed)",
+            mb(total),
+            mb(budget)
+        ));
+    }
+    Ok(format!(
+        "Success: batch fits in {:.2} of {:.2} MB ({written} written, {skipped} skipped, {failed} failed)",
+        mb(total),
+        mb(budget)
+    ))
+}
+
+// Encodes one batch file in memory and only writes it when it passes the filters.
+fn compress_batch_file(
+    task: &CompressionTask,
+    filters: BatchFilters,
+    timer: &PhaseTimer,
+) -> Result<BatchOutcome, String> {
+    let input_meta = match std::fs::metadata(&task.input_path) {
+        Ok(meta) => meta,
+        Err(e) => return Err(format!("Error reading input file: {e}")),
+    };
+
+    let input_size = input_meta.len();
+    if output_is_current(task, &input_meta, filters.existing_outputs) {
+        return Ok(BatchOutcome::AlreadyExists { input_size });
+    }
+
+    let mut task = task.clone();
+    filters.apply_resize_threshold(&mut task, input_size);
+    let encoded = encode_task_with_progress(&task, &|phase| timer.mark(phase), None)?;
+    let output_size = encoded.bytes.len() as u64;
+    let savings = savings_percent(input_size, output_size);
+    if filters.min_savings_percent.is_some_and(|min| savings < min) {
+        return Ok(BatchOutcome::InsufficientGain { input_size, output_size });
+    }
+
+    timer.mark(Phase::Writing);
+    write_output(&task, &encoded.bytes, encoded.thumbnail.as_deref())?;
+    Ok(BatchOutcome::Written { input_size, output_size, blurhash: encoded.blurhash })
+}
+
+// Writes the encoded image and its thumbnail, plus the task's note as
+// `<output>.txt` (e.g. photo.jpg.txt) when there is one, then runs the
+// post-process command.
+fn write_output(task: &CompressionTask, bytes: &[u8], thumbnail: Option<&[u8]>) -> Result<(), String> {
+    let temp_dir = task.temp_dir.as_deref();
+    let written = if task.keep_original {
+        write_keeping_original(&task.output_path, bytes, temp_dir)
+    } else {
+        write_atomic(&task.output_path, bytes, temp_dir)
+    };
+    if let Err(e) = written {
+        return Err(format!("Error saving image: {e}"));
+    }
+    if let Some(thumbnail) = thumbnail
+        && let Err(e) = write_atomic(&thumbnail_path(&task.output_path), thumbnail, temp_dir)
+    {
+        return Err(format!("Error saving thumbnail: {e}"));
+    }
+
+    let note = task.note.trim();
+    if !note.is_empty() {
+        let mut sidecar = task.output_path.clone().into_os_string();
+        sidecar.push(".txt");
+        if let Err(e) = std::fs::write(&sidecar, format!("{note}\n")) {
+            return Err(format!("Error writing note: {e}"));
+        }
+    }
+
+    run_post_command(&task.post_command, &task.output_path)
+}
+
+// Writes to a temporary file in `temp_dir` (or next to `path`) and then moves it
+// over `path`, so an interrupted write never leaves a truncated output behind.
+// A rename can't cross volumes, so a `temp_dir` on another disk is copied over
+// instead, which is no longer atomic but still only touches `path` once.
+fn write_atomic(path: &Path, bytes: &[u8], temp_dir: Option<&Path>) -> std::io::Result<()> {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let dir = temp_dir.or(path.parent()).unwrap_or(Path::new(""));
+    // e.g. .photo.jpg.4242.tmp, hidden and unique to this process.
+    let temp = dir.join(format!(".{name}.{}.tmp", std::process::id()));
+    std::fs::write(&temp, bytes)?;
+
+    let moved = match std::fs::rename(&temp, path) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            std::fs::copy(&temp, path).map(|_| ())
+        }
+        result => result,
+    };
+    // Already gone after a rename; left behind by a copy or a failed move.
+    let _ = std::fs::remove_file(&temp);
+    moved
+}
+
+// Like `write_atomic`, but an existing file at `path` is first renamed to
+// `<name>.orig`, and put back if the write fails. When a backup is already
+// there it holds the first original, so it is kept and `path` simply replaced.
+fn write_keeping_original(path: &Path, bytes: &[u8], temp_dir: Option<&Path>) -> std::io::Result<()> {
+    let mut backup = path.to_path_buf().into_os_string();
+    backup.push(".orig");
+    let backup = PathBuf::from(backup);
+    if !path.exists() || backup.exists() {
+        return write_atomic(path, bytes, temp_dir);
+    }
+    std::fs::rename(path, &backup)?;
+    let written = write_atomic(path, bytes, temp_dir);
+    if written.is_err() {
+        let _ = std::fs::rename(&backup, path);
+    }
+    written
+}
+
+// Longest stretch of a failed post-process command's stderr shown.
+const MAX_POST_COMMAND_STDERR: usize = 200;
+
+// Runs the user's post-process command through the shell. Without a `{file}`
+// placeholder the path is appended, matching how most optimizers take it.
+fn run_post_command(command: &str, output: &Path) -> Result<(), String> {
+    use std::process::Command;
+
+    let command = command.trim();
+    if command.is_empty() {
+        return Ok(());
+    }
+    let file = cli::quote(&output.display().to_string());
+    let command = if command.contains("{file}") {
+        command.replace("{file}", &file)
+    } else {
+        format!("{command} {file}")
+    };
+
+    // `args` would escape the quotes around the file for a program's own argument
+    // parsing, which cmd doesn't do, so the line goes to cmd as it is. With /S,
+    // cmd takes off exactly the outer pair of quotes and runs what's inside.
+    #[cfg(windows)]
+    let result = {
+        use std::os::windows::process::CommandExt;
+        Command::new("cmd").raw_arg(format!("/S /C \"{command}\"")).output()
+    };
+    #[cfg(not(windows))]
+    let result = Command::new("sh").args(["-c", &command]).output();
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => return Err(format!("Error running post-process command: {e}")),
+    };
+    if result.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    let stderr = stderr.trim();
+    let mut msg = format!("Error: post-process command failed ({})", result.status);
+    if !stderr.is_empty() {
+        let mut shown: String = stderr.chars().take(MAX_POST_COMMAND_STDERR).collect();
+        if shown.len() < stderr.len() {
+            shown.push('…');
+        }
+        msg.push_str(&format!(": {shown}"));
+    }
+    Err(msg)
+}
+
+// "{name}_web" and "photo" give "photo_web". A template without {name} is
+// added after the name, and an empty one keeps it as it is.
+fn apply_name_template(template: &str, stem: &str) -> String {
+    match template.trim() {
+        "" => stem.to_string(),
+        template if !template.contains("{name}") => format!("{stem}{template}"),
+        template => template.replace("{name}", stem),
+    }
+}
+
+// `stem.extension`, or `stem_1.extension`, `stem_2.extension`, ... when an
+// earlier input in the batch already took the name (photo.png and photo.jpg
+// both becoming photo.webp). Compared case-insensitively for Windows and macOS.
+fn unique_output_name(stem: &str, extension: &str, used: &mut HashSet<String>) -> String {
+    let mut name = format!("{stem}.{extension}");
+    let mut suffix = 1;
+    while !used.insert(name.to_lowercase()) {
+        name = format!("{stem}_{suffix}.{extension}");
+        suffix += 1;
+    }
+    name
+}
+
+// photo.jpg -> photo_thumb.jpg
+fn thumbnail_path(output: &Path) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}_thumb.{}", ext.to_string_lossy()),
+        None => format!("{stem}_thumb"),
+    };
+    output.with_file_name(name)
+}
+
+// `path`, or the first of path_1, path_2 and so on (keeping the extension)
+// that doesn't exist yet.
+fn free_output_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    let mut candidate = path.to_path_buf();
+    let mut suffix = 1;
+    while candidate.exists() {
+        candidate = path.with_file_name(format!("{stem}_{suffix}{extension}"));
+        suffix += 1;
+    }
+    candidate
+}
+
+fn output_is_current(task: &CompressionTask, input_meta: &std::fs::Metadata, policy: ExistingOutputs) -> bool {
+    let Ok(output_meta) = std::fs::metadata(&task.output_path) else {
+        return false;
+    };
+
+    match policy {
+        ExistingOutputs::Overwrite | ExistingOutputs::Rename => false,
+        ExistingOutputs::Skip => true,
+        ExistingOutputs::SkipIfNewer => match (output_meta.modified(), input_meta.modified()) {
+            (Ok(output_time), Ok(input_time)) => output_time >= input_time,
+            // Without timestamps we can't tell, so redo the file.
+            _ => false,
+        },
+    }
+}
+
+fn collect_batch_inputs(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    collect_files(dir, true)
+}
+
+// The files `collect_batch_inputs` leaves out.
+fn collect_unsupported_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    collect_files(dir, false)
+}
+
+fn collect_files(dir: &Path, supported: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_input = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| INPUT_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if path.is_file() && is_input == supported {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// A bar the width of the original size, filled to the compressed size and
+// labeled with both. An output larger than its input fills it in red.
+fn ratio_gauge(ui: &mut egui::Ui, original: u64, compressed: u64) -> egui::Response {
+    let size = egui::vec2(ui.available_width().min(320.0), 18.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    if ui.is_rect_visible(rect) {
+        let visuals = ui.visuals();
+        let rounding = rect.height() / 2.0;
+        let fraction = if original == 0 { 1.0 } else { (compressed as f32 / original as f32).min(1.0) };
+        let fill = if compressed > original { egui::Color32::RED } else { egui::Color32::from_rgb(60, 160, 90) };
+
+        let painter = ui.painter();
+        painter.rect_filled(rect, rounding, visuals.extreme_bg_color);
+        let filled = egui::Rect::from_min_size(rect.min, egui::vec2(rect.width() * fraction, rect.height()));
+        painter.rect_filled(filled, rounding, fill);
+        painter.rect_stroke(rect, rounding, visuals.widgets.noninteractive.bg_stroke);
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            format!(
+                "{} → {} ({:.1}% smaller)",
+                format_size(original),
+                format_size(compressed),
+                savings_percent(original, compressed)
+            ),
+            egui::FontId::proportional(12.0),
+            visuals.strong_text_color(),
+        );
+    }
+    response.on_hover_text("Compressed size against the original")
+}
+
+fn savings_percent(original_size: u64, new_size: u64) -> f32 {
+    if original_size == 0 {
+        return 0.0;
+    }
+    (1.0 - new_size as f64 / original_size as f64) as f32 * 100.0
+}
+
+// An input after all of a task's edits, ready to be encoded.
+#[derive(Clone)]
+struct ProcessedImage {
+    image: DynamicImage,
+    // The protected region, mapped into the processed image's coordinates.
+    protect: Option<CropRect>,
+    notes: Vec<String>,
+    // Degrees the image was turned to straighten it, when the task asks for it.
+    deskew_angle: Option<f32>,
+}
+
+// Decodes the input and applies the task's edits: color conversion, rotation, tone,
+// crop and resize. Everything up to, but not including, the output format.
+// `cache` is worth passing when the same input is likely to be processed again.
+fn process_task(
+    task: &CompressionTask,
+    report: &dyn Fn(Phase),
+    cache: Option<&decode_cache::DecodeCache>,
+) -> Result<ProcessedImage, String> {
+    let mut notes = Vec::new();
+    report(Phase::Decoding);
+    // Owned copies for the decode thread.
+    let (path, page, columns) = (task.input_path.clone(), task.page, task.sprite_columns);
+    let cache = cache.cloned();
+    let decoded = decode_with_timeout(task.decode_timeout_secs, &mut notes, move |notes| {
+        match (columns, cache) {
+            (Some(columns), _) => decode_sprite_sheet(&path, columns),
+            (None, Some(cache)) => cache.decode(&path, page, notes),
+            (None, None) => decode_input_page(&path, page, notes),
+        }
+    })?;
+    let mut img = decoded.image;
+    report(Phase::Processing);
+
+    if let Some(reason) = decoded.truncated {
+        if !task.salvage_truncated {
+            return Err(format!(
+                "Error: the file appears truncated ({reason}), so only part of it decoded; \
+                 turn on \"Save what decodes of truncated files\" to keep that part"
+            ));
+        }
+        notes.push(format!("the input appears truncated ({reason}); saved the part that decoded"));
+    }
+
+    let lossy = match OutputFormat::from_path(&task.output_path) {
+        Some(OutputFormat::Jpeg) => true,
+        Some(OutputFormat::WebP) => task.webp_mode == WebpMode::Lossy,
+        _ => false,
+    };
+    if lossy
+        && let Some(source) = decoded.source_quality
+        && task.quality > source
+    {
+        notes.push(format!(
+            "quality {} is above the source's estimated {source}, which only adds size",
+            task.quality
+        ));
+    }
+
+    if task.convert_to_srgb
+        && let Some(profile) = &decoded.icc_profile
+    {
+        img = convert_to_srgb(img, profile, &mut notes);
+    }
+
+    img = task.rotation.apply(img);
+    let mut deskew_angle = None;
+    if task.deskew {
+        match deskew::detect_angle(&img) {
+            Some(angle) => {
+                if angle.abs() >= deskew::MIN_SKEW {
+                    img = deskew::straighten(&img, angle, task.background);
+                }
+                deskew_angle = Some(angle);
+            }
+            None => notes.push("no straight edges found to deskew by".to_string()),
+        }
+    }
+    if task.auto_levels {
+        img = auto_levels(img);
+    }
+    img = adjust_tone(img, task.brightness, task.contrast);
+    if let Some(levels) = task.posterize_levels {
+        img = posterize(img, levels);
+    }
+
+    // The protected region follows the image through crop and resize as
+    // [x0, y0, x1, y1] in the current image's coordinates.
+    let mut protect = task.protect_region.map(|region| {
+        [
+            region.x as f32,
+            region.y as f32,
+            (region.x + region.width) as f32,
+            (region.y + region.height) as f32,
+        ]
+    });
+
+    if let Some(crop) = task.crop {
+        // `crop_imm` would quietly shrink a region running off the edge.
+        if crop.x.saturating_add(crop.width) > img.width() || crop.y.saturating_add(crop.height) > img.height() {
+            return Err(format!(
+                "Error: crop {}x{} at ({}, {}) doesn't fit in the {}x{} image",
+                crop.width,
+                crop.height,
+                crop.x,
+                crop.y,
+                img.width(),
+                img.height()
+            ));
+        }
+        img = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+        protect = protect.map(|region| crop_region(region, crop));
+    }
+    // Taken from within the manual crop, when there is one.
+    if let Some(aspect) = task.aspect_crop {
+        let crop = aspect.region(&img);
+        img = img.crop_imm(crop.x, crop.y, crop.width, crop.height);
+        protect = protect.map(|region| crop_region(region, crop));
+    }
+
+    if let Some(target) = task.resize
+        && !(target.shrink_only && img.width() <= target.width && img.height() <= target.height)
+    {
+        let filter = task.resize_filter.filter_type();
+        let (old_w, old_h) = (img.width() as f32, img.height() as f32);
+        let premultiply = task.defringe && img.color().has_alpha();
+        if premultiply {
+            img = premultiply_alpha(&img);
+        }
+        img = if target.keep_aspect {
+            img.resize(target.width, target.height, filter)
+        } else {
+            img.resize_exact(target.width, target.height, filter)
+        };
+        if premultiply {
+            img = unpremultiply_alpha(&img);
+        }
+        let (sx, sy) = (img.width() as f32 / old_w, img.height() as f32 / old_h);
+        protect = protect.map(|[x0, y0, x1, y1]| [x0 * sx, y0 * sy, x1 * sx, y1 * sy]);
+    }
+
+    if let Some(watermark) = &task.watermark {
+        img = watermark::apply(img, watermark)?;
+    }
+
+    let protect = protect.and_then(|[x0, y0, x1, y1]| {
+        let region = CropRect {
+            x: x0.floor() as u32,
+            y: y0.floor() as u32,
+            width: (x1.ceil() - x0.floor()) as u32,
+            height: (y1.ceil() - y0.floor()) as u32,
+        };
+        // A crop can leave the region entirely outside the image.
+        (region.width > 0 && region.height > 0).then_some(region)
+    });
+
+    Ok(ProcessedImage { image: img, protect, notes, deskew_angle })
+}
+
+// Moves a protected region given as [x0, y0, x1, y1] into `crop`'s coordinates,
+// clamped to its edges.
+fn crop_region([x0, y0, x1, y1]: [f32; 4], crop: CropRect) -> [f32; 4] {
+    let (cx, cy) = (crop.x as f32, crop.y as f32);
+    let (cw, ch) = (crop.width as f32, crop.height as f32);
+    [
+        (x0 - cx).clamp(0.0, cw),
+        (y0 - cy).clamp(0.0, ch),
+        (x1 - cx).clamp(0.0, cw),
+        (y1 - cy).clamp(0.0, ch),
+    ]
+}
+
+// Decodes the input and encodes it to the output format entirely in memory,
+// calling `report` as each phase starts and decoding through `cache`.
+fn encode_task_with_progress(
+    task: &CompressionTask,
+    report: &dyn Fn(Phase),
+    cache: Option<&decode_cache::DecodeCache>,
+) -> Result<EncodedImage, String> {
+    let processed = process_task(task, report, cache)?;
+    encode_processed(task, processed, report)
+}
+
+// The encoding half of `encode_task_with_progress`, for callers that also need the processed image.
+fn encode_processed(
+    task: &CompressionTask,
+    processed: ProcessedImage,
+    report: &dyn Fn(Phase),
+) -> Result<EncodedImage, String> {
+    let ProcessedImage { image: mut img, protect, mut notes, deskew_angle } = processed;
+
+    let Some(format) = OutputFormat::from_path(&task.output_path) else {
+        return Err(
+            "Error: unsupported format. Use .jpg, .png, .webp, .qoi, .jxl, .ppm, .pam, or .raw"
+                .to_string(),
+        );
+    };
+
+    if (task.flatten || matches!(format, OutputFormat::Jpeg | OutputFormat::Ppm)) && img.color().has_alpha() {
+        img = flatten_alpha(&img, task.background);
+    }
+
+    report(Phase::Encoding { written: 0 });
+    let bytes = encode_with_fallback(&img, format, task, protect, &mut notes, report)?;
+    let bytes = output_metadata::embed(bytes, format, &task.metadata, &mut notes)?;
+
+    // The thumbnail is cut from the processed image rather than decoded again.
+    let thumbnail = match task.thumbnail_size {
+        Some(size) if img.width() > size || img.height() > size => {
+            let premultiply = task.defringe && img.color().has_alpha();
+            let mut small = if premultiply { premultiply_alpha(&img) } else { img.clone() };
+            small = small.resize(size, size, task.resize_filter.filter_type());
+            if premultiply {
+                small = unpremultiply_alpha(&small);
+            }
+            Some(encode_image(&small, format, task, None, &mut Vec::new(), &|_| {})?)
+        }
+        // Already small enough, so the thumbnail is the image itself.
+        Some(_) => Some(bytes.clone()),
+        None => None,
+    };
+    let blurhash = if task.blurhash { Some(blurhash_of(&img)?) } else { None };
+
+    Ok(EncodedImage { bytes, format, notes, thumbnail, blurhash, deskew_angle })
+}
+
+// A BlurHash of `img`, with more components along its longer side. Worked out
+// on a small copy, since the hash only keeps the broadest shapes anyway.
+fn blurhash_of(img: &DynamicImage) -> Result<String, String> {
+    let small = img.thumbnail(64, 64).to_rgba8();
+    let (x, y) = if small.width() >= small.height() { (4, 3) } else { (3, 4) };
+    match blurhash::encode(x, y, small.width(), small.height(), small.as_raw()) {
+        Ok(hash) => Ok(hash),
+        Err(e) => Err(format!("Error computing BlurHash: {e}")),
+    }
+}
+
+// `encode_image`, retried once on failure the simplest way the format allows:
+// 8-bit RGB or RGBA pixels, no protected region, no restart markers and the
+// automatic PNG depth. Some encoders reject particular color types or
+// settings, and a plainer file beats none; the note says what was given up.
+fn encode_with_fallback(
+    img: &DynamicImage,
+    format: OutputFormat,
+    task: &CompressionTask,
+    protect: Option<CropRect>,
+    notes: &mut Vec<String>,
+    report: &dyn Fn(Phase),
+) -> Result<Vec<u8>, String> {
+    let err = match encode_image(img, format, task, protect, notes, report) {
+        Ok(bytes) => return Ok(bytes),
+        Err(err) => err,
+    };
+
+    let plain_color = if img.color().has_alpha() { image::ColorType::Rgba8 } else { image::ColorType::Rgb8 };
+    let mut changes = Vec::new();
+    if img.color() != plain_color {
+        changes.push(format!("converted {:?} pixels to {plain_color:?}", img.color()));
+    }
+    if protect.is_some() && format == OutputFormat::Jpeg {
+        changes.push("dropped the protected region".to_string());
+    }
+    if task.jpeg_restart_interval != 0 && format == OutputFormat::Jpeg {
+        changes.push("left out restart markers".to_string());
+    }
+    if task.png_depth != PngDepth::Auto && format == OutputFormat::Png {
+        changes.push("used the automatic PNG depth".to_string());
+    }
+    // The same encode again would only fail the same way.
+    if changes.is_empty() {
+        return Err(err);
+    }
 
-fn main() -> Result<(), eframe::Error> {
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([500.0, 400.0])
-            .with_resizable(true),
-        ..Default::default()
+    let plain = match plain_color {
+        image::ColorType::Rgba8 => DynamicImage::ImageRgba8(img.to_rgba8()),
+        _ => DynamicImage::ImageRgb8(img.to_rgb8()),
     };
+    let simple = CompressionTask { jpeg_restart_interval: 0, png_depth: PngDepth::Auto, ..task.clone() };
+    match encode_image(&plain, format, &simple, None, notes, report) {
+        Ok(bytes) => {
+            notes.push(format!("{err}; {} and saved that instead", changes.join(", ")));
+            Ok(bytes)
+        }
+        Err(_) => Err(err),
+    }
+}
 
-    eframe::run_native(
-        "Image Compressor",
-        options,
-        Box::new(|_cc| Ok(Box::new(ImageCompressorApp::default()))),
-    )
+// Runs the encoder for `format` with the task's settings.
+fn encode_image(
+    img: &DynamicImage,
+    format: OutputFormat,
+    task: &CompressionTask,
+    protect: Option<CropRect>,
+    notes: &mut Vec<String>,
+    report: &dyn Fn(Phase),
+) -> Result<Vec<u8>, String> {
+    let mut buf = ProgressWriter { buf: Vec::new(), report, reported: 0 };
+    let res = match format {
+        OutputFormat::Jpeg => match protect {
+            Some(region) => {
+                save_jpeg_protected(
+                    img,
+                    &mut buf,
+                    task.quality,
+                    region,
+                    task.protect_quality,
+                    task.jpeg_restart_interval,
+                )
+            }
+            None => save_jpeg(img, &mut buf, task.quality, task.jpeg_restart_interval),
+        },
+        OutputFormat::Png => save_png(img, &mut buf, task.png_level, task.png_depth),
+        OutputFormat::WebP => match task.webp_mode {
+            WebpMode::Lossless => save_webp_lossless(img, &mut buf),
+            WebpMode::NearLossless => {
+                save_webp_libwebp(img, &mut buf, None, Some(task.webp_near_lossless))
+            }
+            WebpMode::Lossy => save_webp_libwebp(img, &mut buf, Some(task.quality), None),
+        },
+        OutputFormat::Qoi => save_qoi(img, &mut buf),
+        OutputFormat::Jxl => save_jxl(img, &mut buf, task.quality),
+        OutputFormat::Ppm => save_ppm(img, &mut buf),
+        OutputFormat::Pam => save_pam(img, &mut buf),
+        OutputFormat::Raw => {
+            // Nothing in the file says how big it is, so report that alongside.
+            notes.push(format!("raw output is {}x{} RGBA, 8 bits per channel", img.width(), img.height()));
+            save_raw(img, &mut buf)
+        }
+    };
+
+    match res {
+        Ok(_) => Ok(buf.buf),
+        Err(e) => Err(format!("Error encoding image: {e}")),
+    }
 }
 
-struct CompressionTask {
-    input_path: PathBuf,
-    output_path: PathBuf,
-    quality: u8,
+// Starting quality for lossy output, judged from edge density: flat graphics take
+// low quality without visible loss, while busy photos need more to keep fine detail.
+// Measured on a downscaled copy for speed.
+fn suggest_quality(img: &DynamicImage) -> u8 {
+    // Mean gradient (0-255 scale) at which an image counts as fully detailed.
+    const DETAILED_GRADIENT: f32 = 24.0;
+    const MIN_QUALITY: f32 = 60.0;
+    const MAX_QUALITY: f32 = 92.0;
+
+    let luma = img.thumbnail(512, 512).to_luma8();
+    let (width, height) = luma.dimensions();
+    if width < 2 || height < 2 {
+        return MAX_QUALITY as u8;
+    }
+
+    let mut total = 0.0;
+    for y in 0..height - 1 {
+        for x in 0..width - 1 {
+            let here = luma.get_pixel(x, y).0[0] as f32;
+            let right = luma.get_pixel(x + 1, y).0[0] as f32;
+            let below = luma.get_pixel(x, y + 1).0[0] as f32;
+            total += (right - here).abs() + (below - here).abs();
+        }
+    }
+    let mean_gradient = total / ((width - 1) * (height - 1)) as f32;
+
+    let detail = (mean_gradient / DETAILED_GRADIENT).clamp(0.0, 1.0);
+    (MIN_QUALITY + (MAX_QUALITY - MIN_QUALITY) * detail).round() as u8
 }
 
-struct ImageCompressorApp {
-    input_path: Option<PathBuf>,
-    output_path: Option<PathBuf>,
-    quality: u8,
-    status_message: String,
-    compress_tx: Sender<CompressionTask>,
-    result_rx: Receiver<Result<String, String>>,
-    is_compressing: bool,
+// Brightness is added to every channel; contrast is a percentage (see
+// `DynamicImage::adjust_contrast`). Zero for both returns the image untouched.
+fn adjust_tone(mut img: DynamicImage, brightness: i32, contrast: f32) -> DynamicImage {
+    if brightness != 0 {
+        img = img.brighten(brightness);
+    }
+    if contrast != 0.0 {
+        img = img.adjust_contrast(contrast);
+    }
+    img
 }
 
-impl Default for ImageCompressorApp {
-    fn default() -> Self {
-        let (compress_tx, compress_rx) = channel::<CompressionTask>();
-        let (result_tx, result_rx) = channel::<Result<String, String>>();
+// Stretches each color channel so that its darkest and brightest values, after
+// clipping a small share of outliers at both ends, span 0 to 255. Transparent
+// pixels don't count, and alpha is left alone. Inputs deeper than 8 bits are
+// brought down to 8 first.
+fn auto_levels(img: DynamicImage) -> DynamicImage {
+    // Share of pixels at each end ignored, so specks of dust or a few blown
+    // highlights don't hold the range open.
+    const CLIP: f64 = 0.005;
 
-        // Spawn worker thread for compression
-        thread::spawn(move || {
-            while let Ok(task) = compress_rx.recv() {
-                let result = perform_compression(task);
-                let _ = result_tx.send(result);
+    let mut img = match img {
+        DynamicImage::ImageLuma8(_)
+        | DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageRgb8(_)
+        | DynamicImage::ImageRgba8(_) => img,
+        other if other.color().has_alpha() => DynamicImage::ImageRgba8(other.to_rgba8()),
+        other => DynamicImage::ImageRgb8(other.to_rgb8()),
+    };
+    let apply = |samples: &mut [u8], channels: usize, color: usize| {
+        let has_alpha = channels > color;
+        let mut histograms = vec![[0u64; 256]; color];
+        let mut counted = 0u64;
+        for pixel in samples.chunks_exact(channels) {
+            if has_alpha && pixel[color] == 0 {
+                continue;
+            }
+            counted += 1;
+            for (histogram, &sample) in histograms.iter_mut().zip(pixel) {
+                histogram[sample as usize] += 1;
             }
-        });
-
-        Self {
-            input_path: None,
-            output_path: None,
-            quality: 80,
-            status_message: "Ready".to_string(),
-            compress_tx,
-            result_rx,
-            is_compressing: false,
         }
+        let clipped = (counted as f64 * CLIP) as u64;
+        let luts: Vec<[u8; 256]> = histograms
+            .iter()
+            .map(|histogram| {
+                let mut total = 0;
+                let low = histogram.iter().position(|&n| {
+                    total += n;
+                    total > clipped
+                });
+                total = 0;
+                let high = histogram.iter().rposition(|&n| {
+                    total += n;
+                    total > clipped
+                });
+                match (low, high) {
+                    (Some(low), Some(high)) if high > low => std::array::from_fn(|v| {
+                        ((v as f32 - low as f32) * 255.0 / (high - low) as f32).round().clamp(0.0, 255.0) as u8
+                    }),
+                    // A flat channel has no range to stretch.
+                    _ => std::array::from_fn(|v| v as u8),
+                }
+            })
+            .collect();
+        for pixel in samples.chunks_exact_mut(channels) {
+            for (sample, lut) in pixel.iter_mut().zip(&luts) {
+                *sample = lut[*sample as usize];
+            }
+        }
+    };
+    match &mut img {
+        DynamicImage::ImageLuma8(buffer) => apply(buffer, 1, 1),
+        DynamicImage::ImageLumaA8(buffer) => apply(buffer, 2, 1),
+        DynamicImage::ImageRgb8(buffer) => apply(buffer, 3, 3),
+        DynamicImage::ImageRgba8(buffer) => apply(buffer, 4, 3),
+        _ => {}
     }
+    img
 }
 
-impl eframe::App for ImageCompressorApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Check for compression results without blocking
-        if let Ok(result) = self.result_rx.try_recv() {
-            self.is_compressing = false;
-            self.status_message = match result {
-                Ok(msg) => msg,
-                Err(err) => err,
-            };
+// Rounds every color channel to one of `levels` evenly spaced values; alpha is
+// left alone. Inputs deeper than 8 bits are brought down to 8 first.
+fn posterize(img: DynamicImage, levels: u8) -> DynamicImage {
+    let step = 255.0 / (levels.max(2) - 1) as f32;
+    let lut: [u8; 256] = std::array::from_fn(|v| ((v as f32 / step).round() * step).round() as u8);
+    // Alpha, when there is any, comes after the `color` channels.
+    let apply = |samples: &mut [u8], channels: usize, color: usize| {
+        for pixel in samples.chunks_exact_mut(channels) {
+            for sample in &mut pixel[..color] {
+                *sample = lut[*sample as usize];
+            }
         }
+    };
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Image Compressor");
-            ui.add_space(10.0);
-
-            // Input file
-            ui.horizontal(|ui| {
-                ui.label("Input file:");
-                if ui.button("Browse…").clicked() {
-                    if let Some(path) = FileDialog::new()
-                        .add_filter("Images", &["png", "jpg", "jpeg", "webp"])
-                        .pick_file()
-                    {
-                        self.input_path = Some(path);
-                        self.status_message = "Input file selected".to_string();
-                    }
-                }
-            });
+    let mut img = match img {
+        DynamicImage::ImageLuma8(_)
+        | DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageRgb8(_)
+        | DynamicImage::ImageRgba8(_) => img,
+        other if other.color().has_alpha() => DynamicImage::ImageRgba8(other.to_rgba8()),
+        other => DynamicImage::ImageRgb8(other.to_rgb8()),
+    };
+    match &mut img {
+        DynamicImage::ImageLuma8(buffer) => apply(buffer, 1, 1),
+        DynamicImage::ImageLumaA8(buffer) => apply(buffer, 2, 1),
+        DynamicImage::ImageRgb8(buffer) => apply(buffer, 3, 3),
+        DynamicImage::ImageRgba8(buffer) => apply(buffer, 4, 3),
+        _ => {}
+    }
+    img
+}
 
-            ui.label(
-                self.input_path
-                    .as_ref()
-                    .map(|p| p.display().to_string())
-                    .unwrap_or_else(|| "No file selected".to_string()),
-            );
-            ui.add_space(10.0);
+// Scales color by alpha so that filtering weighs each pixel by its coverage.
+fn premultiply_alpha(img: &DynamicImage) -> DynamicImage {
+    let mut rgba = img.to_rgba32f();
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        pixel.0 = [r * a, g * a, b * a, a];
+    }
+    DynamicImage::ImageRgba32F(rgba)
+}
 
-            // Output file
-            ui.horizontal(|ui| {
-                ui.label("Output file:");
-                if ui.button("Browse…").clicked() {
-                    if let Some(path) = FileDialog::new()
-                        .add_filter("JPEG", &["jpg", "jpeg"])
-                        .add_filter("PNG", &["png"])
-                        .add_filter("WebP", &["webp"])
-                        .save_file()
-                    {
-                        self.output_path = Some(path);
-                        self.status_message = "Output file selected".to_string();
-                    }
-                }
-            });
+// Inverse of `premultiply_alpha`. Lanczos can overshoot, so values are clamped.
+fn unpremultiply_alpha(img: &DynamicImage) -> DynamicImage {
+    let mut rgba = img.to_rgba32f();
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let a = a.clamp(0.0, 1.0);
+        pixel.0 = if a > 0.0 {
+            [
+                (r / a).clamp(0.0, 1.0),
+                (g / a).clamp(0.0, 1.0),
+                (b / a).clamp(0.0, 1.0),
+                a,
+            ]
+        } else {
+            [0.0, 0.0, 0.0, 0.0]
+        };
+    }
+    DynamicImage::ImageRgba8(DynamicImage::ImageRgba32F(rgba).to_rgba8())
+}
 
-            ui.label(
-                self.output_path
-                    .as_ref()
-                    .map(|p| p.display().to_string())
-                    .unwrap_or_else(|| "No file selected".to_string()),
-            );
-            ui.add_space(10.0);
+// Composites the image over a solid background color, dropping the alpha channel.
+fn flatten_alpha(img: &DynamicImage, background: [u8; 3]) -> DynamicImage {
+    let rgba = img.to_rgba32f();
+    let background = background.map(|c| c as f32 / 255.0);
+    let rgb = image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: f32, bg: f32| ((c * a + bg * (1.0 - a)) * 255.0).round().clamp(0.0, 255.0) as u8;
+        image::Rgb([blend(r, background[0]), blend(g, background[1]), blend(b, background[2])])
+    });
+    DynamicImage::ImageRgb8(rgb)
+}
 
-            // Quality slider
-            ui.separator();
-            ui.add_space(10.0);
+// Decodes from the file bytes so the real format is sniffed from the contents
+// rather than trusted from the extension.
+fn decode_input(path: &Path, notes: &mut Vec<String>) -> Result<DecodedInput, String> {
+    decode_input_page(path, 0, notes)
+}
 
-            ui.horizontal(|ui| {
-                ui.label("Compression quality:");
-                ui.label(format!("{}%", self.quality));
-            });
+// Like `decode_input`, but selects `page` of a multi-page TIFF.
+fn decode_input_page(path: &Path, page: usize, notes: &mut Vec<String>) -> Result<DecodedInput, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(format!("Error reading input file: {e}")),
+    };
+    if let Some(warning) = detect_extension_mismatch(path, image::guess_format(&bytes).ok()) {
+        notes.push(warning);
+    }
+    decode_bytes(&bytes, page, notes)
+}
 
-            ui.add(
-                egui::Slider::new(&mut self.quality, 1..=100)
-                    .text("Quality")
-                    .show_value(false),
-            );
-            ui.label("Lower = more compression / smaller file.");
-            ui.label("Higher = less compression / better quality.");
+// The decoding behind `decode_input_page`, for an input already in memory such
+// as one piped in on stdin.
+fn decode_bytes(bytes: &[u8], page: usize, notes: &mut Vec<String>) -> Result<DecodedInput, String> {
+    use image::{ImageDecoder, ImageReader};
+    use std::io::Cursor;
 
-            ui.add_space(20.0);
-            ui.separator();
-            ui.add_space(10.0);
+    let detected = image::guess_format(bytes).ok();
+    let missing_end = truncated_input::missing_end(bytes, detected);
 
-            // Compress button
-            let can_compress = self.input_path.is_some() 
-                && self.output_path.is_some() 
-                && !self.is_compressing;
-            
-            if ui
-                .add_enabled(can_compress, egui::Button::new("Compress image"))
-                .clicked()
-            {
-                if let (Some(input), Some(output)) = (&self.input_path, &self.output_path) {
-                    let task = CompressionTask {
-                        input_path: input.clone(),
-                        output_path: output.clone(),
-                        quality: self.quality,
-                    };
-                    let _ = self.compress_tx.send(task);
-                    self.is_compressing = true;
-                    self.status_message = "Compressing...".to_string();
-                }
-            }
+    if page > 0 && detected == Some(ImageFormat::Tiff) {
+        let mut image = tiff_pages::decode_page(bytes, page)?;
+        if float_input::is_float(&image) {
+            image = float_input::to_8bit(&image, false, notes);
+        }
+        return Ok(DecodedInput { image, icc_profile: None, source_quality: None, truncated: None });
+    }
+    if page > 0
+        && let Some(format @ (ImageFormat::Gif | ImageFormat::WebP)) = detected
+    {
+        let image = animation::decode_frame(bytes, format, page)?;
+        return Ok(DecodedInput { image, icc_profile: None, source_quality: None, truncated: None });
+    }
 
-            ui.add_space(10.0);
+    // Go through the decoder directly so metadata such as the ICC profile is available.
+    let mut reader = ImageReader::new(Cursor::new(bytes));
+    if let Some(format) = detected {
+        reader.set_format(format);
+    }
+    let mut decoder = match reader.into_decoder() {
+        Ok(decoder) => decoder,
+        Err(e) => return Err(truncated_input::describe_error(&e, missing_end)),
+    };
+    let mut icc_profile = decoder.icc_profile().ok().flatten();
+    let orientation = decoder.orientation().ok();
 
-            // Status
-            ui.horizontal(|ui| {
-                ui.label("Status:");
-                let color = if self.status_message.starts_with("Error") {
-                    egui::Color32::RED
-                } else if self.status_message.starts_with("Success") {
-                    egui::Color32::GREEN
+    // Only JPEG's decoder carries on past the end of the data; other formats
+    // that decode at all are whole.
+    let mut truncated = missing_end.filter(|_| detected == Some(ImageFormat::Jpeg));
+    let mut image = if detected == Some(ImageFormat::Jpeg) && cmyk::is_cmyk(bytes) {
+        // The CMYK profile has been used up getting to RGB.
+        icc_profile = None;
+        cmyk::decode(bytes, notes)?
+    } else {
+        match DynamicImage::from_decoder(decoder) {
+            Ok(image) => image,
+            Err(e) => {
+                let partial = if missing_end.is_some() && detected == Some(ImageFormat::Png) {
+                    truncated_input::png_rows(bytes)
                 } else {
-                    egui::Color32::GRAY
+                    None
                 };
-                ui.colored_label(color, &self.status_message);
-            });
-        });
+                let Some((image, height)) = partial else {
+                    return Err(truncated_input::describe_error(&e, missing_end));
+                };
+                notes.push(format!("only the first {} of {height} rows arrived", image.height()));
+                truncated = missing_end;
+                image
+            }
+        }
+    };
+    // Bake the EXIF orientation into the pixels, since the encoders don't carry it over.
+    if let Some(orientation) = orientation {
+        image.apply_orientation(orientation);
+    }
+    if float_input::is_float(&image) {
+        image = float_input::to_8bit(&image, detected == Some(ImageFormat::OpenExr), notes);
+    }
+    let source_quality = (detected == Some(ImageFormat::Jpeg))
+        .then(|| jpeg_quality::estimate(bytes))
+        .flatten();
+    Ok(DecodedInput { image, icc_profile, source_quality, truncated })
+}
 
-        // Request repaint to check for results
-        if self.is_compressing {
-            ctx.request_repaint();
+// Runs `decode` on a thread of its own, giving up after `timeout_secs` (0 waits
+// forever). A decode that overruns can't be stopped; its thread is left to finish
+// with nobody waiting for the result.
+fn decode_with_timeout(
+    timeout_secs: u32,
+    notes: &mut Vec<String>,
+    decode: impl FnOnce(&mut Vec<String>) -> Result<DecodedInput, String> + Send + 'static,
+) -> Result<DecodedInput, String> {
+    if timeout_secs == 0 {
+        return decode(notes);
+    }
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let mut notes = Vec::new();
+        let result = decode(&mut notes);
+        let _ = tx.send((result, notes));
+    });
+    match rx.recv_timeout(Duration::from_secs(timeout_secs as u64)) {
+        Ok((result, decode_notes)) => {
+            notes.extend(decode_notes);
+            result
+        }
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => Err(format!(
+            "Error: decoding took over {timeout_secs}s; the input may be malformed or too large"
+        )),
+        // The decode panicked.
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+            Err("Error loading image: the decoder crashed".to_string())
         }
     }
 }
 
-// Compression logic running in background thread
-fn perform_compression(task: CompressionTask) -> Result<String, String> {
-    let img = match image::open(&task.input_path) {
-        Ok(img) => img,
-        Err(e) => return Err(format!("Error loading image: {e}")),
+// Runs on the worker so the window stays responsive while a large input decodes.
+fn decode_preview(
+    path: &Path,
+    page: usize,
+    timeout_secs: u32,
+    cache: &decode_cache::DecodeCache,
+) -> Result<PreviewImage, String> {
+    let page_count = count_pages(path);
+    let page = page.min(page_count - 1);
+    let mut notes = Vec::new();
+    let (cache, owned_path) = (cache.clone(), path.to_path_buf());
+    let decoded = decode_with_timeout(timeout_secs, &mut notes, move |notes| cache.decode(&owned_path, page, notes))?;
+    let img = decoded.image;
+    Ok(PreviewImage {
+        thumbnail: img.thumbnail(PREVIEW_MAX_SIZE, PREVIEW_MAX_SIZE),
+        source_size: [img.width(), img.height()],
+        source_quality: decoded.source_quality,
+        suggestion: format_advice::suggest(&img),
+        truncated: decoded.truncated,
+        notes,
+        page_count,
+        metadata: image_metadata::read(path),
+    })
+}
+
+// Decodes the reference in `key` and scores it against its input, scaling it to
+// the input's size first when they differ.
+fn compare_reference(key: &ReferenceKey, cache: &decode_cache::DecodeCache) -> Result<ReferenceImage, String> {
+    let image = decode_input(&key.reference, &mut Vec::new())?.image;
+    let thumbnail = image.thumbnail(REFERENCE_THUMBNAIL_SIZE, REFERENCE_THUMBNAIL_SIZE);
+
+    let mut resized = false;
+    let metrics = match &key.input {
+        None => Err("Select an input file to compare against.".to_string()),
+        Some(input) => cache.decode(input, key.page, &mut Vec::new()).map(|original| {
+            let original = original.image;
+            let mut image = image;
+            if (image.width(), image.height()) != (original.width(), original.height()) {
+                use image::imageops::FilterType;
+                image = image.resize_exact(original.width(), original.height(), FilterType::Lanczos3);
+                resized = true;
+            }
+            metrics::compare(&original, &image)
+        }),
     };
+    Ok(ReferenceImage { thumbnail, metrics, resized })
+}
 
-    let format = match task.output_path
-        .extension()
-        .and_then(|s| s.to_str())
-        .map(|s| s.to_lowercase())
-        .as_deref()
-    {
-        Some("jpg") | Some("jpeg") => ImageFormat::Jpeg,
-        Some("png") => ImageFormat::Png,
-        Some("webp") => ImageFormat::WebP,
-        _ => {
-            return Err("Error: unsupported format. Use .jpg, .png, or .webp".to_string());
-        }
+// Every frame of the animation at `path`, tiled `columns` wide.
+fn decode_sprite_sheet(path: &Path, columns: u32) -> Result<DecodedInput, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(format!("Error reading input file: {e}")),
+    };
+    let Ok(format) = image::guess_format(&bytes) else {
+        return Err("Error: unrecognized image format".to_string());
     };
+    let image = animation::sprite_sheet(&bytes, format, columns)?;
+    Ok(DecodedInput { image, icc_profile: None, source_quality: None, truncated: None })
+}
 
-    let res = match format {
-        ImageFormat::Jpeg => save_jpeg(&img, &task.output_path, task.quality),
-        ImageFormat::Png => save_png(&img, &task.output_path, task.quality),
-        ImageFormat::WebP => save_webp_lossless(&img, &task.output_path),
-        _ => {
-            return Err("Error: unsupported format".to_string());
+// `task` with the "Optimize for web" settings on top: sRGB lossy WebP at most
+// WEB_MAX_SIDE per side, with a thumbnail, and none of the optional metadata.
+// The output path is left to the caller.
+fn web_task(task: CompressionTask) -> CompressionTask {
+    CompressionTask {
+        quality: WEB_QUALITY,
+        convert_to_srgb: true,
+        resize: Some(ResizeTarget { width: WEB_MAX_SIDE, height: WEB_MAX_SIDE, keep_aspect: true, shrink_only: true }),
+        webp_mode: WebpMode::Lossy,
+        note: String::new(),
+        metadata: output_metadata::OutputMetadata::default(),
+        thumbnail_size: Some(WEB_THUMBNAIL_SIZE),
+        ..task
+    }
+}
+
+// `output` (or, with none chosen yet, `input`) with its extension changed, but
+// never the input itself.
+fn output_with_extension(output: Option<&Path>, input: &Path, extension: &str) -> PathBuf {
+    let output = output.unwrap_or(input).with_extension(extension);
+    if output != input {
+        return output;
+    }
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    input.with_file_name(format!("{stem}_compressed.{extension}"))
+}
+
+// The format of the file at `path`, detected from its header, when it is one
+// outputs can be written in.
+fn writable_input_format(path: &Path) -> Option<ImageFormat> {
+    let format = image::ImageReader::open(path).and_then(|reader| reader.with_guessed_format()).ok()?.format()?;
+    OUTPUT_FORMATS.contains(&format).then_some(format)
+}
+
+// Whether the input's frames come from an animation rather than TIFF pages.
+fn is_animation(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif") || ext.eq_ignore_ascii_case("webp"))
+}
+
+// Number of pages in the file at `path`: TIFF pages or animation frames, otherwise 1.
+fn count_pages(path: &Path) -> usize {
+    let Ok(bytes) = std::fs::read(path) else {
+        return 1;
+    };
+    match image::guess_format(&bytes).ok() {
+        Some(ImageFormat::Tiff) => tiff_pages::page_count(&bytes),
+        Some(format @ (ImageFormat::Gif | ImageFormat::WebP)) => animation::frame_count(&bytes, format),
+        _ => 1,
+    }
+}
+
+// Converts pixels from the embedded ICC profile to sRGB.
+// Works on 8-bit RGBA, so deeper sources are reduced to 8 bits per channel.
+#[cfg(feature = "color-management")]
+fn convert_to_srgb(img: DynamicImage, profile: &[u8], notes: &mut Vec<String>) -> DynamicImage {
+    use lcms2::{Intent, PixelFormat, Profile, Transform};
+
+    let source = match Profile::new_icc(profile) {
+        Ok(source) => source,
+        Err(e) => {
+            notes.push(format!("embedded color profile is invalid, colors not converted ({e})"));
+            return img;
+        }
+    };
+    let transform = match Transform::new(
+        &source,
+        PixelFormat::RGBA_8,
+        &Profile::new_srgb(),
+        PixelFormat::RGBA_8,
+        Intent::Perceptual,
+    ) {
+        Ok(transform) => transform,
+        Err(e) => {
+            notes.push(format!("could not convert color profile to sRGB ({e})"));
+            return img;
         }
     };
 
-    match res {
-        Ok(_) => Ok(format!("Success: saved to {}", task.output_path.display())),
-        Err(e) => Err(format!("Error saving image: {e}")),
+    let mut rgba = img.to_rgba8();
+    let pixels: &mut [u8] = &mut rgba;
+    transform.transform_in_place(pixels);
+    DynamicImage::ImageRgba8(rgba)
+}
+
+#[cfg(not(feature = "color-management"))]
+fn convert_to_srgb(img: DynamicImage, _profile: &[u8], notes: &mut Vec<String>) -> DynamicImage {
+    notes.push(
+        "sRGB conversion is not available in this build, colors were left unchanged".to_string(),
+    );
+    img
+}
+
+// Compares the format sniffed from the file contents with the one implied by
+// its extension, returning a warning when they disagree.
+fn detect_extension_mismatch(path: &Path, detected: Option<ImageFormat>) -> Option<String> {
+    let detected = detected?;
+    let claimed = ImageFormat::from_path(path).ok()?;
+    if claimed == detected {
+        return None;
     }
+
+    Some(format!(
+        "input has a .{} extension but is actually {:?}",
+        path.extension().and_then(|s| s.to_str()).unwrap_or(""),
+        detected,
+    ))
 }
 
 // Helper functions
 
-fn save_jpeg(
+fn save_jpeg<W: std::io::Write>(
     img: &DynamicImage,
-    path: &PathBuf,
+    writer: W,
     quality: u8,
+    restart_interval: u16,
 ) -> Result<(), image::ImageError> {
     use image::codecs::jpeg::JpegEncoder;
-    use std::fs::File;
-    use std::io::BufWriter;
 
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
+    if restart_interval > 0 {
+        return save_jpeg_with_restarts(img, writer, quality, restart_interval);
+    }
     let mut encoder = JpegEncoder::new_with_quality(writer, quality);
     encoder.encode_image(img)
 }
 
-fn save_png(
+// `image`'s encoder can't write restart markers, so these go through jpeg-encoder.
+fn save_jpeg_with_restarts<W: std::io::Write>(
     img: &DynamicImage,
-    path: &PathBuf,
+    writer: W,
     quality: u8,
+    restart_interval: u16,
 ) -> Result<(), image::ImageError> {
-    use image::codecs::png::{PngEncoder, CompressionType, FilterType};
-    use image::{ColorType, ImageEncoder};
-    use std::fs::File;
-    use std::io::BufWriter;
-
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
-
-    // Map quality to compression type
-    let compression = if quality < 40 {
-        CompressionType::Fast
-    } else if quality < 80 {
-        CompressionType::Default
-    } else {
-        CompressionType::Best
+    use image::error::{EncodingError, ImageFormatHint, LimitError, LimitErrorKind};
+    use jpeg_encoder::{ColorType, Encoder};
+
+    let (Ok(width), Ok(height)) = (u16::try_from(img.width()), u16::try_from(img.height())) else {
+        return Err(image::ImageError::Limits(LimitError::from_kind(
+            LimitErrorKind::DimensionError,
+        )));
     };
+    let mut encoder = Encoder::new(writer, quality);
+    encoder.set_restart_interval(restart_interval);
+    let result = match img {
+        DynamicImage::ImageLuma8(gray) => encoder.encode(gray.as_raw(), width, height, ColorType::Luma),
+        _ => encoder.encode(img.to_rgb8().as_raw(), width, height, ColorType::Rgb),
+    };
+    result.map_err(|e| {
+        image::ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::Jpeg), e))
+    })
+}
 
+// Encodes a JPEG whose protected region keeps more detail than the rest, in two passes:
+// the whole image is encoded at `quality` and decoded again, the original pixels are
+// pasted back over the (block-aligned) region, and the composite is encoded at
+// `region_quality`. Background blocks already carry the coarse quantization from the
+// first pass, so they re-encode cheaply while the region stays sharp.
+fn save_jpeg_protected<W: std::io::Write>(
+    img: &DynamicImage,
+    writer: W,
+    quality: u8,
+    region: CropRect,
+    region_quality: u8,
+    restart_interval: u16,
+) -> Result<(), image::ImageError> {
+    // Chroma is subsampled 2x, so a 16px grid keeps region edges on whole MCUs.
+    const MCU_SIZE: u32 = 16;
+
+    let mut first_pass = Vec::new();
+    save_jpeg(img, &mut first_pass, quality, 0)?;
+    let mut composite =
+        image::load_from_memory_with_format(&first_pass, ImageFormat::Jpeg)?.to_rgb8();
+
+    let source = img.to_rgb8();
+    let x0 = region.x / MCU_SIZE * MCU_SIZE;
+    let y0 = region.y / MCU_SIZE * MCU_SIZE;
+    let x1 = ((region.x + region.width).div_ceil(MCU_SIZE) * MCU_SIZE).min(source.width());
+    let y1 = ((region.y + region.height).div_ceil(MCU_SIZE) * MCU_SIZE).min(source.height());
+    for y in y0..y1 {
+        for x in x0..x1 {
+            composite.put_pixel(x, y, *source.get_pixel(x, y));
+        }
+    }
+
+    save_jpeg(&DynamicImage::ImageRgb8(composite), writer, region_quality, restart_interval)
+}
+
+fn save_png<W: std::io::Write>(
+    img: &DynamicImage,
+    writer: W,
+    level: u8,
+    depth: PngDepth,
+) -> Result<(), image::ImageError> {
+    use image::codecs::png::{PngEncoder, CompressionType, FilterType};
+    use image::ImageEncoder;
+
+    // Level 0 stores the data uncompressed; 1-9 are the usual deflate levels.
     let encoder = PngEncoder::new_with_quality(
         writer,
-        compression,
+        CompressionType::Level(level.min(9)),
         FilterType::Adaptive,
     );
 
-    // Get raw RGBA8 data from the DynamicImage.
-    let rgba = img.to_rgba8();
-    let (width, height) = rgba.dimensions();
+    // `as_bytes` is native-endian for 16-bit data, which is what PngEncoder expects.
+    let converted = depth.convert(img);
 
     // Use the ImageEncoder::write_image method implemented by PngEncoder.
     encoder.write_image(
-        &rgba,
-        width,
-        height,
-        ColorType::Rgba8.into(),
+        converted.as_bytes(),
+        converted.width(),
+        converted.height(),
+        converted.color().into(),
     )
 }
 
-fn save_webp_lossless(
+fn save_webp_lossless<W: std::io::Write>(
     img: &DynamicImage,
-    path: &PathBuf,
+    writer: W,
 ) -> Result<(), image::ImageError> {
     use image::codecs::webp::WebPEncoder;
     use image::{ExtendedColorType, ImageEncoder};
-    use std::fs::File;
-    use std::io::BufWriter;
-
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
 
     // WebPEncoder in image 0.25 only supports lossless encoding and requires Rgb8/Rgba8 data.
     let rgba = img.to_rgba8();
@@ -310,3 +6471,250 @@ fn save_webp_lossless(
         ExtendedColorType::Rgba8,
     )
 }
+
+// Encodes through libwebp, which (unlike `image`) supports lossy and near-lossless
+// WebP. Pass `quality` for lossy output, or `near_lossless` for lossless output with
+// near-lossless preprocessing.
+fn save_webp_libwebp<W: std::io::Write>(
+    img: &DynamicImage,
+    mut writer: W,
+    quality: Option<u8>,
+    near_lossless: Option<u8>,
+) -> Result<(), image::ImageError> {
+    use image::error::{EncodingError, ImageFormatHint};
+
+    let encoding_error = |message: String| {
+        image::ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::WebP),
+            message,
+        ))
+    };
+
+    let Ok(mut config) = webp::WebPConfig::new() else {
+        return Err(encoding_error("could not initialize libwebp".to_string()));
+    };
+    match (quality, near_lossless) {
+        (Some(quality), _) => {
+            config.lossless = 0;
+            config.quality = quality as f32;
+        }
+        (None, level) => {
+            config.lossless = 1;
+            config.near_lossless = level.unwrap_or(100) as i32;
+        }
+    }
+
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let encoded = match webp::Encoder::from_rgba(&rgba, width, height).encode_advanced(&config) {
+        Ok(encoded) => encoded,
+        Err(e) => return Err(encoding_error(format!("libwebp failed: {e:?}"))),
+    };
+
+    writer.write_all(&encoded)?;
+    Ok(())
+}
+
+// QOI through the `qoi` crate, as RGBA when the image has alpha and RGB otherwise.
+fn save_qoi<W: std::io::Write>(img: &DynamicImage, mut writer: W) -> Result<(), image::ImageError> {
+    use image::error::{EncodingError, ImageFormatHint};
+
+    let (width, height) = (img.width(), img.height());
+    let encoded = if img.color().has_alpha() {
+        qoi::encode_to_vec(img.to_rgba8().as_raw(), width, height)
+    } else {
+        qoi::encode_to_vec(img.to_rgb8().as_raw(), width, height)
+    };
+    match encoded {
+        Ok(encoded) => {
+            writer.write_all(&encoded)?;
+            Ok(())
+        }
+        Err(e) => Err(image::ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::Qoi),
+            e.to_string(),
+        ))),
+    }
+}
+
+// JPEG XL at a JPEG-style quality, which libjxl turns into a target distance
+// (about 1.0, visually lossless, at 90). Alpha is kept when the image has it.
+#[cfg(feature = "jpegxl")]
+fn save_jxl<W: std::io::Write>(img: &DynamicImage, mut writer: W, quality: u8) -> Result<(), image::ImageError> {
+    use image::error::{EncodingError, ImageFormatHint};
+    use jpegxl_rs::encode::EncoderFrame;
+
+    let error = |e: jpegxl_rs::EncodeError| {
+        image::ImageError::Encoding(EncodingError::new(ImageFormatHint::Name("JPEG XL".to_string()), e.to_string()))
+    };
+    let has_alpha = img.color().has_alpha();
+    let mut encoder = jpegxl_rs::encoder_builder()
+        .jpeg_quality(quality as f32)
+        .has_alpha(has_alpha)
+        .build()
+        .map_err(error)?;
+    let (width, height) = (img.width(), img.height());
+    let encoded = if has_alpha {
+        let rgba = img.to_rgba8();
+        encoder.encode_frame(&EncoderFrame::new(rgba.as_raw()).num_channels(4), width, height)
+    } else {
+        encoder.encode::<u8>(img.to_rgb8().as_raw(), width, height)
+    };
+    writer.write_all(&encoded.map_err(error)?)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "jpegxl"))]
+fn save_jxl<W: std::io::Write>(_img: &DynamicImage, _writer: W, _quality: u8) -> Result<(), image::ImageError> {
+    Err(image::ImageError::IoError(std::io::Error::other(
+        "JPEG XL output is not available in this build",
+    )))
+}
+
+// Binary PPM (P6). PPM has no alpha channel; `encode_processed` flattens it first.
+fn save_ppm<W: std::io::Write>(img: &DynamicImage, writer: W) -> Result<(), image::ImageError> {
+    use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+    use image::{ExtendedColorType, ImageEncoder};
+
+    let rgb = img.to_rgb8();
+    let (width, height) = rgb.dimensions();
+    PnmEncoder::new(writer)
+        .with_subtype(PnmSubtype::Pixmap(SampleEncoding::Binary))
+        .write_image(&rgb, width, height, ExtendedColorType::Rgb8)
+}
+
+// PAM (P7) keeps the alpha channel.
+fn save_pam<W: std::io::Write>(img: &DynamicImage, writer: W) -> Result<(), image::ImageError> {
+    use image::codecs::pnm::{PnmEncoder, PnmSubtype};
+    use image::{ExtendedColorType, ImageEncoder};
+
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    PnmEncoder::new(writer)
+        .with_subtype(PnmSubtype::ArbitraryMap)
+        .write_image(&rgba, width, height, ExtendedColorType::Rgba8)
+}
+
+// Headerless RGBA8, row by row from the top left.
+fn save_raw<W: std::io::Write>(img: &DynamicImage, mut writer: W) -> Result<(), image::ImageError> {
+    writer.write_all(&img.to_rgba8())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quality_lists() {
+        let cases: &[(&str, Result<&[u8], &str>)] = &[
+            ("40, 60, 80", Ok(&[40, 60, 80])),
+            ("80 40,60", Ok(&[40, 60, 80])),
+            ("60, 60 ,60", Ok(&[60])),
+            ("1,100", Ok(&[1, 100])),
+            ("", Err("Error: enter at least one quality")),
+            (" , ", Err("Error: enter at least one quality")),
+            ("0", Err("Error: 0 is not a quality from 1 to 100")),
+            ("50, 101", Err("Error: 101 is not a quality from 1 to 100")),
+            ("high", Err("Error: high is not a quality from 1 to 100")),
+        ];
+        for &(text, expected) in cases {
+            let expected = expected.map(<[u8]>::to_vec).map_err(str::to_string);
+            assert_eq!(parse_qualities(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn applies_name_templates() {
+        let cases = [
+            ("{name}_web", "photo", "photo_web"),
+            ("web-{name}", "photo", "web-photo"),
+            ("{name}-{name}", "photo", "photo-photo"),
+            ("_small", "photo", "photo_small"),
+            ("", "photo", "photo"),
+            ("  ", "photo", "photo"),
+        ];
+        for (template, stem, expected) in cases {
+            assert_eq!(apply_name_template(template, stem), expected, "{template:?}");
+        }
+    }
+
+    #[test]
+    fn numbers_colliding_output_names() {
+        // Each case names its outputs in order, as stem.extension.
+        let cases: &[(&[&str], &[&str])] = &[
+            (&["photo.webp", "photo.webp", "photo.webp"], &["photo.webp", "photo_1.webp", "photo_2.webp"]),
+            // Windows and macOS don't tell these apart.
+            (&["Photo.jpg", "photo.jpg", "PHOTO.JPG"], &["Photo.jpg", "photo_1.jpg", "PHOTO_2.JPG"]),
+            (&["photo.jpg", "photo.png"], &["photo.jpg", "photo.png"]),
+            (&["photo_1.jpg", "photo.jpg", "photo.jpg"], &["photo_1.jpg", "photo.jpg", "photo_2.jpg"]),
+        ];
+        for &(wanted, expected) in cases {
+            let mut used = HashSet::new();
+            let names: Vec<String> = wanted
+                .iter()
+                .map(|name| {
+                    let (stem, extension) = name.rsplit_once('.').unwrap();
+                    unique_output_name(stem, extension, &mut used)
+                })
+                .collect();
+            assert_eq!(names, expected);
+        }
+    }
+
+    #[test]
+    fn budget_only_lowers_outputs_that_use_quality() {
+        let dir = std::env::temp_dir().join(format!("image-compressor-budget-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Noise, so the JPEG's size follows its quality and the PNG barely compresses.
+        let mut seed = 1u32;
+        let noise = image::RgbImage::from_fn(128, 128, |_, _| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let [r, g, b, _] = seed.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+        let task = |input: &str, output: &str| {
+            noise.save(dir.join(input)).unwrap();
+            CompressionTask {
+                input_path: dir.join(input),
+                output_path: dir.join(output),
+                quality: 90,
+                ..CompressionTask::default()
+            }
+        };
+        let tasks = vec![task("scan.png", "scan_out.png"), task("photo.png", "photo_out.jpg")];
+        let size = |task: &CompressionTask| encode_task_with_progress(task, &|_| {}, None).unwrap().bytes.len() as u64;
+        let png = size(&tasks[0]);
+        let jpeg = size(&tasks[1]);
+        let lowest_jpeg = size(&CompressionTask { quality: 10, ..tasks[1].clone() });
+        assert!(png > jpeg, "the PNG should be the largest output");
+
+        let batch = BatchTask {
+            tasks,
+            filters: BatchFilters::default(),
+            size_budget: None,
+            report_dir: None,
+            unsupported: Vec::new(),
+            copy_unsupported_to: None,
+            parallel: None,
+        };
+        let (events, received) = channel();
+        let budget = png + (jpeg + lowest_jpeg) / 2;
+        let result = perform_budget_batch(batch, budget, &PauseControl::default(), &events);
+        let logs: Vec<String> = received
+            .try_iter()
+            .filter_map(|event| match event {
+                WorkerEvent::Log(line) => Some(line),
+                _ => None,
+            })
+            .collect();
+        let written = |name: &str| std::fs::metadata(dir.join(name)).unwrap().len();
+        let (png_written, jpeg_written) = (written("scan_out.png"), written("photo_out.jpg"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(result.is_ok(), "{result:?}");
+        assert!(!logs.iter().any(|line| line.starts_with("scan.png: lowering")), "{logs:?}");
+        assert_eq!(png_written, png);
+        assert!(jpeg_written < jpeg);
+    }
+}
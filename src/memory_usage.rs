@@ -0,0 +1,42 @@
+// The app's resident memory, sampled now and then for the status bar so it's
+// clear when a giant image is what's slowing things down.
+
+use std::time::{Duration, Instant};
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct MemoryMonitor {
+    system: System,
+    pid: Option<Pid>,
+    sampled_at: Option<Instant>,
+    resident: Option<u64>,
+}
+
+impl MemoryMonitor {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            pid: sysinfo::get_current_pid().ok(),
+            sampled_at: None,
+            resident: None,
+        }
+    }
+
+    // Resident bytes as of the last sample, refreshed once SAMPLE_INTERVAL is up.
+    // None where the platform doesn't report it.
+    pub fn resident(&mut self) -> Option<u64> {
+        let pid = self.pid?;
+        if self.sampled_at.is_none_or(|at| at.elapsed() >= SAMPLE_INTERVAL) {
+            self.system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&[pid]),
+                false,
+                ProcessRefreshKind::nothing().with_memory(),
+            );
+            self.resident = self.system.process(pid).map(|process| process.memory());
+            self.sampled_at = Some(Instant::now());
+        }
+        self.resident
+    }
+}
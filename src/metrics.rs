@@ -0,0 +1,83 @@
+// Objective image comparison: PSNR over RGB and SSIM over luma. Both images
+// must have the same dimensions.
+
+use image::{DynamicImage, GrayImage};
+
+// Side of the square windows SSIM is computed over.
+const SSIM_WINDOW: u32 = 8;
+
+#[derive(Clone, Copy)]
+pub struct Metrics {
+    // Peak signal-to-noise ratio in dB; infinite for identical images.
+    pub psnr: f64,
+    // Structural similarity, 1.0 for identical images.
+    pub ssim: f64,
+}
+
+pub fn compare(a: &DynamicImage, b: &DynamicImage) -> Metrics {
+    Metrics {
+        psnr: psnr(a, b),
+        ssim: ssim(&a.to_luma8(), &b.to_luma8()),
+    }
+}
+
+fn psnr(a: &DynamicImage, b: &DynamicImage) -> f64 {
+    let (a, b) = (a.to_rgb8(), b.to_rgb8());
+    let squared_error: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum();
+    let mse = squared_error / a.as_raw().len().max(1) as f64;
+    if mse == 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+// Mean SSIM over non-overlapping windows; a trailing partial window is included.
+fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (width, height) = a.dimensions();
+    let mut total = 0.0;
+    let mut windows = 0;
+
+    for y0 in (0..height).step_by(SSIM_WINDOW as usize) {
+        for x0 in (0..width).step_by(SSIM_WINDOW as usize) {
+            let x1 = (x0 + SSIM_WINDOW).min(width);
+            let y1 = (y0 + SSIM_WINDOW).min(height);
+            let n = ((x1 - x0) * (y1 - y0)) as f64;
+
+            let (mut sum_a, mut sum_b) = (0.0, 0.0);
+            let (mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let pa = a.get_pixel(x, y).0[0] as f64;
+                    let pb = b.get_pixel(x, y).0[0] as f64;
+                    sum_a += pa;
+                    sum_b += pb;
+                    sum_aa += pa * pa;
+                    sum_bb += pb * pb;
+                    sum_ab += pa * pb;
+                }
+            }
+
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+
+    if windows == 0 {
+        return 1.0;
+    }
+    total / windows as f64
+}
@@ -0,0 +1,180 @@
+// Author, copyright and description written into the output as EXIF, for
+// images that get published. The encoders write bare files, so the EXIF block
+// is spliced into the finished JPEG, PNG or WebP afterwards.
+
+use serde::{Deserialize, Serialize};
+
+use crate::OutputFormat;
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputMetadata {
+    pub artist: String,
+    pub copyright: String,
+    pub description: String,
+}
+
+impl OutputMetadata {
+    pub fn is_empty(&self) -> bool {
+        [&self.artist, &self.copyright, &self.description]
+            .iter()
+            .all(|value| value.trim().is_empty())
+    }
+
+    // A little-endian TIFF structure holding the non-empty fields, as EXIF
+    // blocks carry it.
+    fn to_tiff(&self) -> Result<Vec<u8>, String> {
+        use exif::{Field, In, Tag, Value};
+
+        let fields: Vec<Field> = [
+            (Tag::ImageDescription, &self.description),
+            (Tag::Artist, &self.artist),
+            (Tag::Copyright, &self.copyright),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(tag, value)| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.trim().as_bytes().to_vec()]),
+        })
+        .collect();
+
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        match writer.write(&mut tiff, true) {
+            Ok(()) => Ok(tiff.into_inner()),
+            Err(e) => Err(format!("Error building metadata: {e}")),
+        }
+    }
+}
+
+// Adds `metadata` to `bytes`, an encoded image in `format`. Formats that have
+// nowhere to put it are returned as they are, with a note saying so.
+pub fn embed(
+    bytes: Vec<u8>,
+    format: OutputFormat,
+    metadata: &OutputMetadata,
+    notes: &mut Vec<String>,
+) -> Result<Vec<u8>, String> {
+    if metadata.is_empty() {
+        return Ok(bytes);
+    }
+    let tiff = metadata.to_tiff()?;
+    match format {
+        OutputFormat::Jpeg => embed_jpeg(bytes, &tiff),
+        OutputFormat::Png => embed_png(bytes, &tiff),
+        OutputFormat::WebP => embed_webp(bytes, &tiff),
+        _ => {
+            notes.push("this format can't hold author or copyright metadata".to_string());
+            Ok(bytes)
+        }
+    }
+}
+
+// An APP1 segment straight after the JFIF header, or after SOI when there is none.
+fn embed_jpeg(bytes: Vec<u8>, tiff: &[u8]) -> Result<Vec<u8>, String> {
+    let length = 2 + 6 + tiff.len();
+    if !bytes.starts_with(&[0xFF, 0xD8]) || length > u16::MAX as usize {
+        return Err("Error adding metadata: unexpected JPEG layout or metadata too long".to_string());
+    }
+    let mut at = 2;
+    if bytes.get(2..4) == Some(&[0xFF, 0xE0]) && bytes.len() >= 6 {
+        at += 2 + u16::from_be_bytes([bytes[4], bytes[5]]) as usize;
+    }
+
+    let at = at.min(bytes.len());
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend((length as u16).to_be_bytes());
+    segment.extend(b"Exif\0\0");
+    segment.extend(tiff);
+    Ok(splice(bytes, at, &segment))
+}
+
+// An eXIf chunk, which has to come before the image data.
+fn embed_png(bytes: Vec<u8>, tiff: &[u8]) -> Result<Vec<u8>, String> {
+    // Chunks start after the 8-byte signature: length, type, data, CRC.
+    let mut at = 8;
+    while at + 8 <= bytes.len() && &bytes[at + 4..at + 8] != b"IDAT" {
+        let length = u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as usize;
+        at += 12 + length;
+    }
+    if at + 8 > bytes.len() {
+        return Err("Error adding metadata: PNG has no image data".to_string());
+    }
+
+    let mut chunk = (tiff.len() as u32).to_be_bytes().to_vec();
+    chunk.extend(b"eXIf");
+    chunk.extend(tiff);
+    let crc = crc32fast::hash(&chunk[4..]);
+    chunk.extend(crc.to_be_bytes());
+    Ok(splice(bytes, at, &chunk))
+}
+
+// An EXIF chunk at the end of the file. Simple (VP8/VP8L-only) files are first
+// turned into the extended layout, whose VP8X header flags the metadata.
+fn embed_webp(bytes: Vec<u8>, tiff: &[u8]) -> Result<Vec<u8>, String> {
+    const VP8X_ALPHA: u8 = 0x10;
+    const VP8X_EXIF: u8 = 0x08;
+
+    if bytes.len() < 30 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WEBP" {
+        return Err("Error adding metadata: unexpected WebP layout".to_string());
+    }
+    let mut webp = bytes;
+    match &webp[12..16] {
+        b"VP8X" => webp[20] |= VP8X_EXIF,
+        b"VP8 " | b"VP8L" => {
+            let Some((width, height, alpha)) = webp_header(&webp) else {
+                return Err("Error adding metadata: unexpected WebP layout".to_string());
+            };
+            let mut vp8x = b"VP8X".to_vec();
+            vp8x.extend(10u32.to_le_bytes());
+            vp8x.push(VP8X_EXIF | if alpha { VP8X_ALPHA } else { 0 });
+            vp8x.extend([0; 3]);
+            vp8x.extend(&(width - 1).to_le_bytes()[..3]);
+            vp8x.extend(&(height - 1).to_le_bytes()[..3]);
+            webp = splice(webp, 12, &vp8x);
+        }
+        _ => return Err("Error adding metadata: unexpected WebP layout".to_string()),
+    }
+
+    webp.extend(b"EXIF");
+    webp.extend((tiff.len() as u32).to_le_bytes());
+    webp.extend(tiff);
+    // Chunks are padded to an even length.
+    if !tiff.len().is_multiple_of(2) {
+        webp.push(0);
+    }
+    let riff_size = (webp.len() - 8) as u32;
+    webp[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(webp)
+}
+
+// Canvas size of a simple WebP and whether it has alpha, read from its VP8 or
+// VP8L bitstream header.
+fn webp_header(webp: &[u8]) -> Option<(u32, u32, bool)> {
+    let data = webp.get(20..30)?;
+    match &webp[12..16] {
+        // Key frame start code, then 14-bit width and height.
+        b"VP8 " => {
+            let width = u16::from_le_bytes([data[6], data[7]]) & 0x3FFF;
+            let height = u16::from_le_bytes([data[8], data[9]]) & 0x3FFF;
+            Some((width as u32, height as u32, false))
+        }
+        // Signature byte, then width - 1 and height - 1 in 14 bits each and
+        // the alpha bit.
+        b"VP8L" => {
+            let bits = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1, bits >> 28 & 1 == 1))
+        }
+        _ => None,
+    }
+}
+
+fn splice(mut bytes: Vec<u8>, at: usize, insert: &[u8]) -> Vec<u8> {
+    bytes.splice(at..at, insert.iter().copied());
+    bytes
+}
@@ -0,0 +1,99 @@
+// Pulls the raster images out of a PDF so they can be batch compressed like a
+// folder of files. Needs the `pdf` feature. JPEG streams are copied out as they
+// are; 8-bit Flate images in gray or RGB become PNGs. Anything else (JPEG 2000,
+// fax, indexed or ICC-based colors) is counted and skipped.
+
+use std::path::{Path, PathBuf};
+
+pub struct Extracted {
+    pub files: Vec<PathBuf>,
+    pub skipped: usize,
+}
+
+// Writes the images of `pdf` into `dir`, named after their page:
+// "report_p3_1.jpg" is the first image on page 3.
+#[cfg(feature = "pdf")]
+pub fn extract(pdf: &Path, dir: &Path) -> Result<Extracted, String> {
+    use std::collections::HashSet;
+
+    use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+    use lopdf::Document;
+
+    let doc = match Document::load(pdf) {
+        Ok(doc) => doc,
+        Err(e) => return Err(format!("Error reading PDF: {e}")),
+    };
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return Err(format!("Error creating folder for PDF images: {e}"));
+    }
+    let stem = pdf.file_stem().and_then(|s| s.to_str()).unwrap_or("pdf");
+
+    let mut extracted = Extracted { files: Vec::new(), skipped: 0 };
+    // Logos and backgrounds are often shared between pages; extract them once.
+    let mut seen = HashSet::new();
+    for (page, page_id) in doc.get_pages() {
+        let images = match doc.get_page_images(page_id) {
+            Ok(images) => images,
+            Err(e) => return Err(format!("Error reading images on PDF page {page}: {e}")),
+        };
+        let mut index = 0;
+        for image in images {
+            if !seen.insert(image.id) {
+                continue;
+            }
+            let filters = image.filters.clone().unwrap_or_default();
+            let filters: Vec<&str> = filters.iter().map(String::as_str).collect();
+            let (width, height) = (image.width as u32, image.height as u32);
+
+            let (bytes, extension) = if filters == ["DCTDecode"] {
+                (image.content.to_vec(), "jpg")
+            } else if filters.iter().all(|f| *f == "FlateDecode")
+                && image.bits_per_component == Some(8)
+            {
+                let Ok(pixels) = doc
+                    .get_object(image.id)
+                    .and_then(|object| object.as_stream())
+                    .and_then(|stream| stream.decompressed_content())
+                else {
+                    extracted.skipped += 1;
+                    continue;
+                };
+                let decoded = match image.color_space.as_deref() {
+                    Some("DeviceRGB") => {
+                        RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+                    }
+                    Some("DeviceGray") => {
+                        GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+                    }
+                    _ => None,
+                };
+                let Some(decoded) = decoded else {
+                    extracted.skipped += 1;
+                    continue;
+                };
+                let mut png = Vec::new();
+                if let Err(e) = decoded.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+                {
+                    return Err(format!("Error converting PDF image: {e}"));
+                }
+                (png, "png")
+            } else {
+                extracted.skipped += 1;
+                continue;
+            };
+
+            index += 1;
+            let path = dir.join(format!("{stem}_p{page}_{index}.{extension}"));
+            if let Err(e) = std::fs::write(&path, bytes) {
+                return Err(format!("Error writing PDF image: {e}"));
+            }
+            extracted.files.push(path);
+        }
+    }
+    Ok(extracted)
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn extract(_: &Path, _: &Path) -> Result<Extracted, String> {
+    Err("Error: PDF input is not available in this build".to_string())
+}
@@ -0,0 +1,197 @@
+// Background output-size estimates for the single-file settings. Each request is
+// encoded in memory on a helper thread, but only once the settings have been
+// left alone for a moment, so dragging a slider doesn't start an encode every
+// frame. Requests that pile up while an encode is running are collapsed so only
+// the newest one is worked on, and an encode overtaken by a newer request is
+// abandoned after decoding rather than finished. The encode is also
+// decoded again, kept as a thumbnail for side-by-side comparisons, and scored
+// against the uncompressed image.
+//
+// Finished estimates are remembered for the session, keyed by the settings and
+// the input's modification time, so going back to an earlier file or setting
+// shows its estimate straight away. An edited file no longer matches and is
+// encoded again.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use image::DynamicImage;
+
+use crate::decode_cache::DecodeCache;
+use crate::{encode_processed, metrics, process_task, CompressionTask};
+
+// Longest side both images are scaled to before scoring, to keep it quick. Also
+// the size of the kept thumbnail.
+const SCORE_SIZE: u32 = 512;
+// How long the settings must stay unchanged before an estimate is started.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+// Estimates remembered, oldest dropped first. Each holds a thumbnail of up to
+// SCORE_SIZE, so this stays small.
+const CACHE_SIZE: usize = 48;
+
+#[derive(Clone)]
+pub struct Estimate {
+    // Encoded size in bytes.
+    pub size: usize,
+    // SSIM against the uncompressed image; `None` when the output can't be decoded here.
+    pub ssim: Option<f64>,
+    // The encode decoded again and scaled down, likewise `None` when it can't be decoded.
+    pub thumbnail: Option<DynamicImage>,
+}
+
+pub struct SizeEstimator {
+    request_tx: Sender<(u64, CompressionTask)>,
+    result_rx: Receiver<(u64, Result<Estimate, String>)>,
+    // Sequence number of the newest request sent; older results are dropped.
+    // Shared so the helper thread can tell when its work has been overtaken.
+    latest: u64,
+    newest: Arc<AtomicU64>,
+    last_task: Option<CompressionTask>,
+    // When `last_task` was requested, while it waits out the debounce unsent.
+    waiting_since: Option<Instant>,
+    // The input's modification time when `last_task` was requested.
+    last_modified: Option<SystemTime>,
+    estimate: Option<Result<Estimate, String>>,
+    // Earlier estimates and the input modification times they were made at,
+    // newest last.
+    cache: Vec<(CompressionTask, SystemTime, Estimate)>,
+}
+
+impl SizeEstimator {
+    // Decodes through `cache`, so estimates reuse the image the preview loaded.
+    pub fn new(cache: DecodeCache) -> Self {
+        let (request_tx, request_rx) = channel::<(u64, CompressionTask)>();
+        let (result_tx, result_rx) = channel();
+        let newest = Arc::new(AtomicU64::new(0));
+        let thread_newest = Arc::clone(&newest);
+
+        thread::spawn(move || {
+            while let Ok(mut request) = request_rx.recv() {
+                while let Ok(newer) = request_rx.try_recv() {
+                    request = newer;
+                }
+                let (seq, task) = request;
+                let processed = process_task(&task, &|_| {}, Some(&cache));
+                if thread_newest.load(Ordering::Relaxed) != seq {
+                    continue;
+                }
+                let estimate = processed.and_then(|processed| {
+                    let original = processed.image.clone();
+                    let encoded = encode_processed(&task, processed, &|_| {})?;
+                    // Raw output has no header to decode it by.
+                    let thumbnail = image::load_from_memory(&encoded.bytes)
+                        .ok()
+                        .map(|decoded| decoded.thumbnail(SCORE_SIZE, SCORE_SIZE));
+                    Ok(Estimate {
+                        size: encoded.bytes.len(),
+                        ssim: thumbnail.as_ref().and_then(|decoded| score(&original, decoded)),
+                        thumbnail,
+                    })
+                });
+                if result_tx.send((seq, estimate)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            request_tx,
+            result_rx,
+            latest: 0,
+            newest,
+            last_task: None,
+            waiting_since: None,
+            last_modified: None,
+            estimate: None,
+            cache: Vec::new(),
+        }
+    }
+
+    // Asks for an estimate of `task` unless it matches the last one requested.
+    // A remembered estimate is used at once, and `true` returned since it
+    // replaces the current one; otherwise the request is sent by `poll` once no
+    // other request has followed for a moment.
+    pub fn request(&mut self, task: CompressionTask) -> bool {
+        if self.last_task.as_ref() == Some(&task) {
+            return false;
+        }
+        let modified = std::fs::metadata(&task.input_path).and_then(|meta| meta.modified()).ok();
+        let cached = self
+            .cache
+            .iter()
+            .rev()
+            .find(|(cached, cached_modified, _)| *cached == task && Some(*cached_modified) == modified);
+        if let Some((_, _, estimate)) = cached {
+            self.estimate = Some(Ok(estimate.clone()));
+            self.waiting_since = None;
+        } else {
+            self.estimate = None;
+            self.waiting_since = Some(Instant::now());
+        }
+        self.last_task = Some(task);
+        self.last_modified = modified;
+        self.waiting_since.is_none()
+    }
+
+    // Forgets the current estimate, e.g. when there is nothing to estimate.
+    pub fn clear(&mut self) {
+        self.last_task = None;
+        self.waiting_since = None;
+        self.last_modified = None;
+        self.estimate = None;
+    }
+
+    // Sends a request that has waited long enough and picks up finished
+    // estimates; call once per frame. Returns whether a new estimate arrived.
+    pub fn poll(&mut self) -> bool {
+        if let (Some(since), Some(task)) = (self.waiting_since, &self.last_task)
+            && since.elapsed() >= DEBOUNCE
+        {
+            self.latest += 1;
+            self.newest.store(self.latest, Ordering::Relaxed);
+            let _ = self.request_tx.send((self.latest, task.clone()));
+            self.waiting_since = None;
+        }
+
+        let mut updated = false;
+        while let Ok((seq, estimate)) = self.result_rx.try_recv() {
+            // A result for the last request sent is stale once another is waiting.
+            if seq == self.latest
+                && let Some(task) = &self.last_task
+                && self.waiting_since.is_none()
+            {
+                // Without a modification time there is no telling when the file changes.
+                if let (Ok(estimate), Some(modified)) = (&estimate, self.last_modified) {
+                    if self.cache.len() == CACHE_SIZE {
+                        self.cache.remove(0);
+                    }
+                    self.cache.push((task.clone(), modified, estimate.clone()));
+                }
+                self.estimate = Some(estimate);
+                updated = true;
+            }
+        }
+        updated
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.last_task.is_some() && self.estimate.is_none()
+    }
+
+    // Result for the last requested task, once it is known.
+    pub fn estimate(&self) -> Option<&Result<Estimate, String>> {
+        self.estimate.as_ref()
+    }
+}
+
+// `decoded` is already scaled down to SCORE_SIZE.
+fn score(original: &DynamicImage, decoded: &DynamicImage) -> Option<f64> {
+    let original = original.thumbnail(SCORE_SIZE, SCORE_SIZE);
+    if (original.width(), original.height()) != (decoded.width(), decoded.height()) {
+        return None;
+    }
+    Some(metrics::compare(&original, decoded).ssim)
+}
@@ -0,0 +1,72 @@
+// Placement for crops to a fixed aspect ratio that keeps the subject rather
+// than the middle of the frame. Detail is measured as gradient energy, the
+// summed brightness change between neighboring pixels, and the window holding
+// the most of it wins. Good enough to find a face against sky or a product on
+// a plain backdrop, without any real saliency model.
+
+use image::{DynamicImage, GrayImage};
+
+// Energy is measured on a copy no larger than this; finer detail doesn't move
+// the window much and would only make the search slower.
+const ANALYSIS_SIZE: u32 = 256;
+
+// Top-left corner of the `width`×`height` window of `img` with the most detail.
+// Ties go to the window nearest the center.
+pub fn most_detailed(img: &DynamicImage, width: u32, height: u32) -> (u32, u32) {
+    let (img_w, img_h) = (img.width(), img.height());
+    if width >= img_w && height >= img_h {
+        return (0, 0);
+    }
+    let small = img.thumbnail(ANALYSIS_SIZE, ANALYSIS_SIZE).to_luma8();
+    let (small_w, small_h) = small.dimensions();
+    let (sx, sy) = (small_w as f64 / img_w as f64, small_h as f64 / img_h as f64);
+    let window_w = ((width as f64 * sx).round() as u32).clamp(1, small_w);
+    let window_h = ((height as f64 * sy).round() as u32).clamp(1, small_h);
+
+    let table = summed_energy(&small);
+    let sum = |x: u32, y: u32| {
+        let (x0, y0, x1, y1) = (x as usize, y as usize, (x + window_w) as usize, (y + window_h) as usize);
+        let stride = small_w as usize + 1;
+        table[y1 * stride + x1] + table[y0 * stride + x0] - table[y0 * stride + x1] - table[y1 * stride + x0]
+    };
+
+    let (max_x, max_y) = (small_w - window_w, small_h - window_h);
+    let center = (max_x as f64 / 2.0, max_y as f64 / 2.0);
+    let mut best = (0, 0);
+    let mut best_key = (0u64, f64::NEG_INFINITY);
+    for y in 0..=max_y {
+        for x in 0..=max_x {
+            let distance = (x as f64 - center.0).powi(2) + (y as f64 - center.1).powi(2);
+            let key = (sum(x, y), -distance);
+            if key.0 > best_key.0 || (key.0 == best_key.0 && key.1 > best_key.1) {
+                best_key = key;
+                best = (x, y);
+            }
+        }
+    }
+
+    // Back to full-size coordinates, kept inside the image.
+    let x = ((best.0 as f64 / sx).round() as u32).min(img_w.saturating_sub(width));
+    let y = ((best.1 as f64 / sy).round() as u32).min(img_h.saturating_sub(height));
+    (x, y)
+}
+
+// Summed-area table of the gradient energy, one row and column larger than the
+// image so that any window's total is four lookups.
+fn summed_energy(gray: &GrayImage) -> Vec<u64> {
+    let (width, height) = gray.dimensions();
+    let stride = width as usize + 1;
+    let mut table = vec![0u64; stride * (height as usize + 1)];
+    for y in 0..height {
+        let mut row = 0u64;
+        for x in 0..width {
+            let here = gray.get_pixel(x, y)[0] as i32;
+            let right = gray.get_pixel((x + 1).min(width - 1), y)[0] as i32;
+            let below = gray.get_pixel(x, (y + 1).min(height - 1))[0] as i32;
+            row += ((here - right).abs() + (here - below).abs()) as u64;
+            let at = (y as usize + 1) * stride + x as usize + 1;
+            table[at] = table[at - stride] + row;
+        }
+    }
+    table
+}
@@ -0,0 +1,71 @@
+// Progress on the app's taskbar button, mirroring the progress bar in the
+// window, so a long batch can be watched while working elsewhere. Only Windows
+// has this; everywhere else `set` does nothing.
+
+#[derive(Default)]
+pub struct TaskbarProgress {
+    // Last value shown, so the taskbar is only told about changes.
+    shown: Option<f32>,
+    // Created on first use; None also when COM refused to provide it.
+    #[cfg(windows)]
+    taskbar: Option<windows::Win32::UI::Shell::ITaskbarList3>,
+}
+
+impl TaskbarProgress {
+    // `fraction` runs from 0 to 1; None clears the progress.
+    pub fn set(&mut self, frame: &eframe::Frame, fraction: Option<f32>) {
+        if self.shown == fraction {
+            return;
+        }
+        self.shown = fraction;
+        self.show(frame, fraction);
+    }
+
+    #[cfg(windows)]
+    fn show(&mut self, frame: &eframe::Frame, fraction: Option<f32>) {
+        use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+        use windows::Win32::Foundation::HWND;
+        use windows::Win32::System::Com::{
+            CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+        };
+        use windows::Win32::UI::Shell::{ITaskbarList3, TaskbarList, TBPF_NOPROGRESS, TBPF_NORMAL};
+
+        let Ok(handle) = frame.window_handle() else {
+            return;
+        };
+        let RawWindowHandle::Win32(handle) = handle.as_raw() else {
+            return;
+        };
+        let hwnd = HWND(handle.hwnd.get() as *mut _);
+
+        if self.taskbar.is_none() {
+            // winit has normally set up COM on this thread already; this only makes sure.
+            unsafe {
+                let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            }
+            self.taskbar = unsafe {
+                CoCreateInstance::<_, ITaskbarList3>(&TaskbarList, None, CLSCTX_INPROC_SERVER)
+                    .and_then(|taskbar| taskbar.HrInit().map(|()| taskbar))
+                    .ok()
+            };
+        }
+        let Some(taskbar) = &self.taskbar else {
+            return;
+        };
+        // A failure only costs the taskbar display, so errors are ignored.
+        unsafe {
+            let _ = match fraction {
+                Some(fraction) => {
+                    let permille = (fraction.clamp(0.0, 1.0) * 1000.0) as u64;
+                    taskbar
+                        .SetProgressState(hwnd, TBPF_NORMAL)
+                        .and_then(|()| taskbar.SetProgressValue(hwnd, permille, 1000))
+                }
+                None => taskbar.SetProgressState(hwnd, TBPF_NOPROGRESS),
+            };
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn show(&mut self, _: &eframe::Frame, _: Option<f32>) {}
+}
@@ -0,0 +1,94 @@
+// Multi-page TIFF support. `image` only ever decodes the first page of a TIFF,
+// so other pages are read through the `tiff` crate's directory API directly.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::ColorType;
+
+// Number of pages (image file directories) in a TIFF; 1 if it can't be read.
+pub fn page_count(bytes: &[u8]) -> usize {
+    let Ok(mut decoder) = Decoder::new(Cursor::new(bytes)) else {
+        return 1;
+    };
+
+    let mut count = 1;
+    while decoder.more_images() {
+        if decoder.next_image().is_err() {
+            break;
+        }
+        count += 1;
+    }
+    count
+}
+
+pub fn decode_page(bytes: &[u8], page: usize) -> Result<DynamicImage, String> {
+    let mut decoder = match Decoder::new(Cursor::new(bytes)) {
+        Ok(decoder) => decoder,
+        Err(e) => return Err(format!("Error loading image: {e}")),
+    };
+    if let Err(e) = decoder.seek_to_image(page) {
+        return Err(format!("Error: TIFF page {} not found ({e})", page + 1));
+    }
+
+    let (width, height) = match decoder.dimensions() {
+        Ok(dimensions) => dimensions,
+        Err(e) => return Err(format!("Error loading image: {e}")),
+    };
+    let color_type = match decoder.colortype() {
+        Ok(color_type) => color_type,
+        Err(e) => return Err(format!("Error loading image: {e}")),
+    };
+    let data = match decoder.read_image() {
+        Ok(data) => data,
+        Err(e) => return Err(format!("Error loading image: {e}")),
+    };
+
+    let image = match (color_type, data) {
+        (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba16)
+        }
+        // Floating point, tone mapped to 8 bits once decoded. `image` has no
+        // float grayscale, so gray pages are spread over RGB.
+        (ColorType::Gray(32), DecodingResult::F32(buf)) => {
+            let rgb = buf.iter().flat_map(|&value| [value; 3]).collect();
+            ImageBuffer::from_raw(width, height, rgb).map(DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGB(32), DecodingResult::F32(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGBA(32), DecodingResult::F32(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba32F)
+        }
+        (color_type, _) => {
+            return Err(format!(
+                "Error: TIFF page {} uses an unsupported color type ({color_type:?})",
+                page + 1
+            ));
+        }
+    };
+
+    image.ok_or_else(|| format!("Error: TIFF page {} has truncated pixel data", page + 1))
+}
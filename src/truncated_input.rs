@@ -0,0 +1,109 @@
+// Inputs cut short, usually by an interrupted download. Their structure is
+// checked here for the end marker each format closes with, since the decoders
+// only report an unexpected end of file, or for JPEG carry on and fill the
+// missing rows with junk. What did arrive of a PNG can still be read row by row.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, ImageError, ImageFormat};
+
+use crate::jpeg_segments;
+
+// What makes the file look cut short, or None when it seems whole or the
+// format has nothing to check.
+pub fn missing_end(bytes: &[u8], format: Option<ImageFormat>) -> Option<&'static str> {
+    match format? {
+        ImageFormat::Jpeg => jpeg_missing_end(bytes),
+        ImageFormat::Png => png_missing_end(bytes),
+        // Some writers pad the file out with zeros after the trailer.
+        ImageFormat::Gif => (bytes.iter().rev().find(|&&byte| byte != 0) != Some(&0x3B)).then_some("no GIF trailer"),
+        ImageFormat::WebP => shorter_than_header(bytes, 4, 8),
+        ImageFormat::Bmp => shorter_than_header(bytes, 2, 0),
+        _ => None,
+    }
+}
+
+// The message for a decode that failed, leading with what has most likely
+// happened to the file rather than the codec's own wording.
+pub fn describe_error(e: &ImageError, missing_end: Option<&str>) -> String {
+    match (missing_end, e) {
+        (Some(reason), _) => format!("Error: the file appears truncated or corrupt ({reason}; {e})"),
+        (None, ImageError::IoError(io)) if io.kind() == std::io::ErrorKind::UnexpectedEof => {
+            format!("Error: the file appears truncated or corrupt ({e})")
+        }
+        (None, ImageError::Decoding(_)) => format!("Error: the file appears corrupt ({e})"),
+        (None, _) => format!("Error loading image: {e}"),
+    }
+}
+
+// The rows of a truncated PNG that arrived, as an image that many rows tall,
+// with how many rows the whole image has. Interlaced PNGs arrive in passes
+// spread over the whole image, so a part of one isn't worth keeping.
+pub fn png_rows(bytes: &[u8]) -> Option<(DynamicImage, u32)> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    if reader.info().interlaced {
+        return None;
+    }
+    let (width, height) = (reader.info().width, reader.info().height);
+    let channels = match reader.output_color_type().0 {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        // Expanded to RGB(A) by the transformations above.
+        png::ColorType::Indexed => return None,
+    };
+
+    let mut data = Vec::new();
+    while let Ok(Some(row)) = reader.next_row() {
+        data.extend_from_slice(row.data());
+    }
+    let rows = (data.len() / (width as usize * channels)) as u32;
+    if rows == 0 {
+        return None;
+    }
+    data.truncate(rows as usize * width as usize * channels);
+    let image = match channels {
+        1 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, rows, data)?),
+        2 => DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, rows, data)?),
+        3 => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, rows, data)?),
+        _ => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, rows, data)?),
+    };
+    Some((image, height))
+}
+
+// The end marker is looked for only from the first scan on, past any EXIF
+// thumbnail and the end marker that closes it. The scan data escapes any 0xFF
+// byte, so its only end-of-image pattern is the real one.
+fn jpeg_missing_end(bytes: &[u8]) -> Option<&'static str> {
+    let mut segments = jpeg_segments::segments(bytes);
+    let Some(scan) = segments.find(|segment| segment.marker == jpeg_segments::START_OF_SCAN) else {
+        return segments.cut_short().then_some("cut off in its headers");
+    };
+    let complete = scan.data.windows(2).any(|pair| pair == [0xFF, 0xD9]);
+    (!complete).then_some("no end-of-image marker")
+}
+
+fn png_missing_end(bytes: &[u8]) -> Option<&'static str> {
+    let mut pos = 8;
+    while let Some(header) = bytes.get(pos..pos + 8) {
+        if &header[4..] == b"IEND" {
+            return None;
+        }
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        pos = pos.saturating_add(12).saturating_add(length);
+    }
+    Some("no IEND chunk")
+}
+
+// For formats whose header holds the file's size, as a little-endian u32 at
+// `offset` counting from `base`.
+fn shorter_than_header(bytes: &[u8], offset: usize, base: usize) -> Option<&'static str> {
+    let &[a, b, c, d] = bytes.get(offset..offset + 4)? else {
+        return None;
+    };
+    let expected = base + u32::from_le_bytes([a, b, c, d]) as usize;
+    (expected > bytes.len()).then_some("shorter than its header says")
+}
@@ -0,0 +1,61 @@
+// Sending compressed output to an HTTP endpoint, e.g. a presigned S3 URL or a
+// small publishing service. Requires the `upload` feature; without it every
+// attempt reports that uploads aren't available.
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum UploadMethod {
+    // S3 presigned URLs expect PUT.
+    #[default]
+    Put,
+    Post,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct UploadTarget {
+    pub url: String,
+    pub method: UploadMethod,
+    // Sent as the `Authorization` header when not empty, e.g. "Bearer abc123".
+    pub authorization: String,
+}
+
+// Longest server reply shown in the status line.
+#[cfg(feature = "upload")]
+const MAX_REPLY_CHARS: usize = 120;
+
+// Sends `bytes` to the target and returns a short description of the reply.
+#[cfg(feature = "upload")]
+pub fn send(target: &UploadTarget, bytes: &[u8], mime_type: &str) -> Result<String, String> {
+    let method = match target.method {
+        UploadMethod::Put => "PUT",
+        UploadMethod::Post => "POST",
+    };
+    let mut request = ureq::request(method, &target.url).set("Content-Type", mime_type);
+    if !target.authorization.trim().is_empty() {
+        request = request.set("Authorization", target.authorization.trim());
+    }
+
+    let response = match request.send_bytes(bytes) {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            return Err(format!("Error uploading: server replied {code} {}", response.status_text()));
+        }
+        Err(ureq::Error::Transport(e)) => return Err(format!("Error uploading: {e}")),
+    };
+
+    let status = format!("{} {}", response.status(), response.status_text());
+    let body = response.into_string().unwrap_or_default();
+    let body = body.trim();
+    if body.is_empty() {
+        return Ok(status);
+    }
+    let mut reply: String = body.chars().take(MAX_REPLY_CHARS).collect();
+    if reply.len() < body.len() {
+        reply.push('…');
+    }
+    Ok(format!("{status}: {reply}"))
+}
+
+#[cfg(not(feature = "upload"))]
+pub fn send(_target: &UploadTarget, _bytes: &[u8], _mime_type: &str) -> Result<String, String> {
+    Err("Error: uploads are not available in this build".to_string())
+}
@@ -0,0 +1,187 @@
+// Text or image watermarks blended onto the output. Sizes are relative to the
+// image so the same settings look alike on a preview thumbnail and the full file.
+
+use std::path::PathBuf;
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watermark {
+    pub text: String,
+    pub color: [u8; 3],
+    // Overlay picture; used instead of the text when set.
+    pub image: Option<PathBuf>,
+    pub position: WatermarkPosition,
+    // 0 is invisible, 1 fully opaque.
+    pub opacity: f32,
+    // Text height, or the overlay's longer edge, as a percentage of the image's
+    // shorter side.
+    pub size: f32,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Self {
+            text: "©".to_string(),
+            color: [255, 255, 255],
+            image: None,
+            position: WatermarkPosition::BottomRight,
+            opacity: 0.5,
+            size: 5.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomRight,
+}
+
+impl WatermarkPosition {
+    pub const ALL: [WatermarkPosition; 5] = [
+        WatermarkPosition::TopLeft,
+        WatermarkPosition::TopRight,
+        WatermarkPosition::Center,
+        WatermarkPosition::BottomLeft,
+        WatermarkPosition::BottomRight,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WatermarkPosition::TopLeft => "Top left",
+            WatermarkPosition::TopRight => "Top right",
+            WatermarkPosition::Center => "Center",
+            WatermarkPosition::BottomLeft => "Bottom left",
+            WatermarkPosition::BottomRight => "Bottom right",
+        }
+    }
+}
+
+// Distance kept from the edges, as a fraction of the image's shorter side.
+const MARGIN: f32 = 0.02;
+
+pub fn apply(img: DynamicImage, watermark: &Watermark) -> Result<DynamicImage, String> {
+    let short_side = img.width().min(img.height()) as f32;
+    let target = (short_side * watermark.size / 100.0).max(1.0);
+
+    let layer = match &watermark.image {
+        Some(path) => {
+            let overlay = match image::open(path) {
+                Ok(overlay) => overlay,
+                Err(e) => return Err(format!("Error loading watermark image: {e}")),
+            };
+            let longer = overlay.width().max(overlay.height()) as f32;
+            let scale = target / longer;
+            let (width, height) = (
+                ((overlay.width() as f32 * scale).round() as u32).max(1),
+                ((overlay.height() as f32 * scale).round() as u32).max(1),
+            );
+            overlay
+                .resize_exact(width, height, image::imageops::FilterType::Lanczos3)
+                .to_rgba8()
+        }
+        None => render_text(&watermark.text, target, watermark.color)?,
+    };
+
+    let had_alpha = img.color().has_alpha();
+    let mut base = img.to_rgba8();
+    let margin = (short_side * MARGIN).round() as i64;
+    let (free_x, free_y) = (
+        base.width() as i64 - layer.width() as i64,
+        base.height() as i64 - layer.height() as i64,
+    );
+    let (x, y) = match watermark.position {
+        WatermarkPosition::TopLeft => (margin, margin),
+        WatermarkPosition::TopRight => (free_x - margin, margin),
+        WatermarkPosition::Center => (free_x / 2, free_y / 2),
+        WatermarkPosition::BottomLeft => (margin, free_y - margin),
+        WatermarkPosition::BottomRight => (free_x - margin, free_y - margin),
+    };
+    blend(&mut base, &layer, x, y, watermark.opacity.clamp(0.0, 1.0));
+
+    // Don't hand an alpha channel to later steps if the image didn't have one.
+    let blended = DynamicImage::ImageRgba8(base);
+    if had_alpha {
+        Ok(blended)
+    } else {
+        Ok(DynamicImage::ImageRgb8(blended.to_rgb8()))
+    }
+}
+
+// Renders one line of text in egui's default font, `height` pixels tall.
+pub fn render_text(text: &str, height: f32, color: [u8; 3]) -> Result<RgbaImage, String> {
+    let font = match FontRef::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT) {
+        Ok(font) => font,
+        Err(e) => return Err(format!("Error loading font: {e}")),
+    };
+    let scale = PxScale::from(height);
+    let scaled = font.as_scaled(scale);
+
+    let mut glyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    for ch in text.chars().filter(|ch| !ch.is_control()) {
+        let id = scaled.glyph_id(ch);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scale, point(caret, scaled.ascent())));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+
+    let width = (caret.ceil() as u32).max(1);
+    let line_height = ((scaled.ascent() - scaled.descent()).ceil() as u32).max(1);
+    let mut layer = RgbaImage::new(width, line_height);
+    let [r, g, b] = color;
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let x = bounds.min.x as i64 + gx as i64;
+            let y = bounds.min.y as i64 + gy as i64;
+            if x < 0 || y < 0 || x >= width as i64 || y >= line_height as i64 {
+                return;
+            }
+            let pixel = layer.get_pixel_mut(x as u32, y as u32);
+            // Glyphs can overlap slightly; keep the stronger coverage.
+            let alpha = ((coverage * 255.0).round() as u8).max(pixel.0[3]);
+            *pixel = Rgba([r, g, b, alpha]);
+        });
+    }
+    Ok(layer)
+}
+
+// Alpha-composites `layer` over `base` with its top left corner at (x, y).
+pub fn blend(base: &mut RgbaImage, layer: &RgbaImage, x: i64, y: i64, opacity: f32) {
+    for (lx, ly, over) in layer.enumerate_pixels() {
+        let (bx, by) = (x + lx as i64, y + ly as i64);
+        if bx < 0 || by < 0 || bx >= base.width() as i64 || by >= base.height() as i64 {
+            continue;
+        }
+        let under = base.get_pixel_mut(bx as u32, by as u32);
+
+        let alpha = over.0[3] as f32 / 255.0 * opacity;
+        let under_alpha = under.0[3] as f32 / 255.0;
+        let out_alpha = alpha + under_alpha * (1.0 - alpha);
+        if out_alpha <= 0.0 {
+            continue;
+        }
+        for channel in 0..3 {
+            let value = (over.0[channel] as f32 * alpha
+                + under.0[channel] as f32 * under_alpha * (1.0 - alpha))
+                / out_alpha;
+            under.0[channel] = value.round().clamp(0.0, 255.0) as u8;
+        }
+        under.0[3] = (out_alpha * 255.0).round() as u8;
+    }
+}
@@ -0,0 +1,94 @@
+// Images inside a ZIP archive, for compressing them without unpacking it by
+// hand. The chosen entries are read into memory and written to a temporary
+// folder, which is then used like any other batch input folder.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+
+use crate::{unique_output_name, INPUT_EXTENSIONS};
+
+// Larger entries are refused rather than read into memory, e.g. a small ZIP
+// that would unpack to gigabytes.
+const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+
+// An image in the archive, e.g. "holiday/beach.jpg".
+pub struct Entry {
+    pub index: usize,
+    pub name: String,
+}
+
+pub fn list_images(zip: &Path) -> Result<Vec<Entry>, String> {
+    let mut archive = open(zip)?;
+    let mut images = Vec::new();
+    for index in 0..archive.len() {
+        let file = match archive.by_index(index) {
+            Ok(file) => file,
+            Err(e) => return Err(format!("Error reading ZIP entry: {e}")),
+        };
+        // Entries whose names would escape the folder are never offered.
+        let Some(path) = file.enclosed_name() else {
+            continue;
+        };
+        let supported = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| INPUT_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if file.is_file() && supported {
+            images.push(Entry { index, name: path.to_string_lossy().replace('\\', "/") });
+        }
+    }
+    Ok(images)
+}
+
+// Writes `entries` into `dir`, folders flattened into the file name so
+// "holiday/beach.jpg" becomes "holiday_beach.jpg". A name that is already taken,
+// e.g. by an entry really called "holiday_beach.jpg", gets a number on the end.
+pub fn extract(zip: &Path, entries: &[&Entry], dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut archive = open(zip)?;
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return Err(format!("Error creating folder for ZIP images: {e}"));
+    }
+    let too_big = || format!("it is over the {} MB limit", MAX_ENTRY_SIZE / (1024 * 1024));
+    let mut used = HashSet::new();
+    let mut files = Vec::new();
+    for entry in entries {
+        let mut bytes = Vec::new();
+        let read = archive.by_index(entry.index).map_err(|e| e.to_string()).and_then(|file| {
+            if file.size() > MAX_ENTRY_SIZE {
+                return Err(too_big());
+            }
+            // The size in the header isn't checked until the end, so the read is capped too.
+            match file.take(MAX_ENTRY_SIZE + 1).read_to_end(&mut bytes) {
+                Ok(_) if bytes.len() as u64 > MAX_ENTRY_SIZE => Err(too_big()),
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.to_string()),
+            }
+        });
+        if let Err(e) = read {
+            return Err(format!("Error reading {} from ZIP: {e}", entry.name));
+        }
+        let flattened = Path::new(&entry.name).with_extension("").to_string_lossy().replace('/', "_");
+        let extension = Path::new(&entry.name).extension().map(|ext| ext.to_string_lossy()).unwrap_or_default();
+        let path = dir.join(unique_output_name(&flattened, &extension, &mut used));
+        if let Err(e) = std::fs::write(&path, bytes) {
+            return Err(format!("Error writing ZIP image: {e}"));
+        }
+        files.push(path);
+    }
+    Ok(files)
+}
+
+fn open(zip: &Path) -> Result<ZipArchive<File>, String> {
+    let file = match File::open(zip) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Error opening ZIP: {e}")),
+    };
+    match ZipArchive::new(file) {
+        Ok(archive) => Ok(archive),
+        Err(e) => Err(format!("Error reading ZIP: {e}")),
+    }
+}